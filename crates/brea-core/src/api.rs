@@ -0,0 +1,199 @@
+use crate::db::PropertyQueryBuilder;
+use crate::{BreaError, Database, PriceObservation, Property, PropertyStatus, PropertyType, Result};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::str::FromStr;
+use tokio::net::TcpListener;
+
+/// Listings `GET /properties` returns when no `limit` is given.
+pub const DEFAULT_PAGE_SIZE: i64 = 100;
+/// Most listings a single `GET /properties` returns, whatever the `limit`.
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
+/// Read-only JSON API over the database:
+///
+/// - `GET /properties`, filtered by [`PropertyFilters`]
+/// - `GET /properties/:id`, with its price history when `with_history` is
+///   true
+/// - `GET /properties/:id/price-history`, oldest price first, optionally
+///   between the RFC 3339 timestamps `from` and `to`
+///
+/// Errors come back as `{"error": "..."}`, with a 400 for bad parameters and
+/// a 404 for unknown ids.
+pub fn router(db: Database) -> Router {
+    Router::new()
+        .route("/properties", get(list_properties))
+        .route("/properties/:id", get(get_property))
+        .route("/properties/:id/price-history", get(get_price_history))
+        .with_state(db)
+}
+
+/// Serves [`router`] on `listener` until the process is stopped.
+pub async fn serve(db: Database, listener: TcpListener) -> Result<()> {
+    axum::serve(listener, router(db)).await?;
+    Ok(())
+}
+
+/// Query parameters of `GET /properties`. All are optional and ranges are
+/// inclusive, like the `list` command's filters.
+#[derive(Debug, Default, Deserialize)]
+pub struct PropertyFilters {
+    /// Any name `PropertyType` parses, e.g. "apartment" or "departamento"
+    pub property_type: Option<String>,
+    pub district: Option<String>,
+    pub source: Option<String>,
+    /// "active" (the default), "sold" or "removed"
+    pub status: Option<String>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    pub min_size: Option<f64>,
+    pub max_size: Option<f64>,
+    pub min_rooms: Option<i32>,
+    pub max_rooms: Option<i32>,
+    pub min_baths: Option<i32>,
+    pub max_baths: Option<i32>,
+    pub min_age: Option<i32>,
+    pub max_age: Option<i32>,
+    pub parking: Option<bool>,
+    /// One of [`crate::db::queries::SORTABLE_FIELDS`], `id` by default
+    pub sort_by: Option<String>,
+    /// Sort in descending order
+    #[serde(default)]
+    pub desc: bool,
+    /// Up to [`MAX_PAGE_SIZE`], [`DEFAULT_PAGE_SIZE`] by default
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+impl PropertyFilters {
+    fn query(&self) -> Result<PropertyQueryBuilder<'_>> {
+        let status = match self.status.as_deref().map(str::to_lowercase).as_deref() {
+            None | Some("active") => PropertyStatus::Active,
+            Some("sold") => PropertyStatus::Sold,
+            Some("removed") => PropertyStatus::Removed,
+            Some(other) => {
+                return Err(BreaError::InvalidArgument(format!(
+                    "unknown status '{}', expected active, sold or removed",
+                    other
+                )))
+            }
+        };
+
+        let mut query = PropertyQueryBuilder::new()
+            .with_status_typed(status)
+            .with_price_range(self.min_price, self.max_price)
+            .with_size_range(self.min_size, self.max_size)
+            .with_rooms_range(self.min_rooms, self.max_rooms)
+            .with_bathrooms_range(self.min_baths, self.max_baths)
+            .with_antiquity_range(self.min_age, self.max_age);
+        if let Some(property_type) = &self.property_type {
+            let property_type = PropertyType::from_str(property_type).map_err(BreaError::InvalidPropertyType)?;
+            query = query.with_property_type(&property_type);
+        }
+        if let Some(district) = &self.district {
+            query = query.with_district(district);
+        }
+        if let Some(source) = &self.source {
+            query = query.with_source(source);
+        }
+        if let Some(has_parking) = self.parking {
+            query = query.with_parking(has_parking);
+        }
+
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(0, MAX_PAGE_SIZE);
+        Ok(query
+            .order_by(self.sort_by.as_deref().unwrap_or("id"), self.desc)?
+            .with_limit(Some(limit))
+            .with_offset(self.offset))
+    }
+}
+
+/// Query parameters of `GET /properties/:id/price-history`.
+#[derive(Debug, Default, Deserialize)]
+pub struct HistoryRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// One entry of `GET /properties/:id/price-history`.
+pub type PricePoint = PriceObservation;
+
+/// Query parameters of `GET /properties/:id`.
+#[derive(Debug, Default, Deserialize)]
+pub struct PropertyOptions {
+    /// Include the property's `price_history`, oldest price first
+    #[serde(default)]
+    pub with_history: bool,
+}
+
+async fn list_properties(
+    State(db): State<Database>,
+    Query(filters): Query<PropertyFilters>,
+) -> std::result::Result<Json<Vec<Property>>, ApiError> {
+    Ok(Json(filters.query()?.execute(db.pool()).await?))
+}
+
+async fn get_property(
+    State(db): State<Database>,
+    Path(id): Path<i64>,
+    Query(options): Query<PropertyOptions>,
+) -> std::result::Result<Response, ApiError> {
+    let property = if options.with_history {
+        db.get_property_with_history(id).await?.map(|property| Json(property).into_response())
+    } else {
+        db.get_property(id).await?.map(|property| Json(property).into_response())
+    };
+    property.ok_or(ApiError::not_found(id))
+}
+
+async fn get_price_history(
+    State(db): State<Database>,
+    Path(id): Path<i64>,
+    Query(range): Query<HistoryRange>,
+) -> std::result::Result<Json<Vec<PricePoint>>, ApiError> {
+    if db.get_property(id).await?.is_none() {
+        return Err(ApiError::not_found(id));
+    }
+    let from = range.from.unwrap_or(DateTime::UNIX_EPOCH);
+    let to = range.to.unwrap_or_else(Utc::now);
+    let history = db.get_price_history_range(id, from, to).await?;
+    Ok(Json(
+        history
+            .into_iter()
+            .map(|(price_usd, observed_at)| PricePoint { price_usd, observed_at })
+            .collect(),
+    ))
+}
+
+/// An error response with a JSON body.
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn not_found(id: i64) -> Self {
+        Self { status: StatusCode::NOT_FOUND, message: format!("no property with id {}", id) }
+    }
+}
+
+impl From<BreaError> for ApiError {
+    fn from(error: BreaError) -> Self {
+        let status = match error {
+            BreaError::InvalidArgument(_) | BreaError::InvalidPropertyType(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self { status, message: error.to_string() }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(serde_json::json!({ "error": self.message }))).into_response()
+    }
+}
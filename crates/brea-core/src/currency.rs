@@ -0,0 +1,78 @@
+use crate::{BreaError, Currency, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+/// Environment variable holding how many ARS one USD buys, e.g. `1200`
+pub const ARS_PER_USD_ENV: &str = "BREA_ARS_PER_USD";
+
+/// Source of exchange rates used to normalize listing prices to USD.
+#[async_trait]
+pub trait RateProvider: Send + Sync + std::fmt::Debug {
+    /// USD value of one unit of `currency` at the time `at`.
+    async fn usd_rate(&self, currency: Currency, at: DateTime<Utc>) -> Result<f64>;
+}
+
+/// Fixed ARS/USD rate, regardless of the date asked for.
+///
+/// Good enough until a historical source is wired in; without a configured
+/// rate, ARS prices can't be converted and are reported as an error.
+#[derive(Debug, Clone, Default)]
+pub struct StaticRateProvider {
+    ars_per_usd: Option<f64>,
+}
+
+impl StaticRateProvider {
+    pub fn new(ars_per_usd: f64) -> Self {
+        Self { ars_per_usd: Some(ars_per_usd) }
+    }
+
+    /// Reads the rate from `BREA_ARS_PER_USD`, leaving it unset if the
+    /// variable is missing or not a positive number.
+    pub fn from_env() -> Self {
+        let ars_per_usd = std::env::var(ARS_PER_USD_ENV)
+            .ok()
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .filter(|rate| *rate > 0.0);
+        Self { ars_per_usd }
+    }
+}
+
+#[async_trait]
+impl RateProvider for StaticRateProvider {
+    async fn usd_rate(&self, currency: Currency, _at: DateTime<Utc>) -> Result<f64> {
+        match currency {
+            Currency::Usd => Ok(1.0),
+            Currency::Ars => self
+                .ars_per_usd
+                .map(|ars_per_usd| 1.0 / ars_per_usd)
+                .ok_or_else(|| {
+                    BreaError::Currency(format!(
+                        "no ARS/USD rate configured, set {} to the number of ARS per USD",
+                        ARS_PER_USD_ENV
+                    ))
+                }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_static_rates() {
+        let provider = StaticRateProvider::new(1000.0);
+        assert_eq!(provider.usd_rate(Currency::Usd, Utc::now()).await.unwrap(), 1.0);
+        assert_eq!(provider.usd_rate(Currency::Ars, Utc::now()).await.unwrap(), 0.001);
+    }
+
+    #[tokio::test]
+    async fn test_missing_ars_rate() {
+        let provider = StaticRateProvider::default();
+        assert_eq!(provider.usd_rate(Currency::Usd, Utc::now()).await.unwrap(), 1.0);
+        assert!(matches!(
+            provider.usd_rate(Currency::Ars, Utc::now()).await,
+            Err(BreaError::Currency(_))
+        ));
+    }
+}
@@ -1,4 +1,6 @@
 use super::types::{DbPropertyStatus, DbTimestamp};
+use crate::BreaError;
+use sha2::{Digest, Sha256};
 use sqlx::sqlite::SqlitePool;
 use std::fmt;
 
@@ -13,6 +15,16 @@ impl Migration {
     pub const fn new(version: i32, up: &'static str, down: &'static str) -> Self {
         Self { version, up, down }
     }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// SHA-256 of the `up` SQL, recorded when the migration is applied so
+    /// later edits to an applied migration are caught.
+    pub fn checksum(&self) -> String {
+        hex::encode(Sha256::digest(self.up.as_bytes()))
+    }
 }
 
 impl fmt::Display for Migration {
@@ -405,39 +417,414 @@ pub const MIGRATIONS: &[Migration] = &[
         ALTER TABLE properties_old RENAME TO properties;
         "#,
     ),
+    Migration::new(
+        8,
+        r#"
+        -- Disable foreign key constraints while the table is rebuilt
+        PRAGMA foreign_keys = OFF;
+
+        -- Create a temporary table with the operation column, which is now
+        -- part of the listing identity so sale and rent listings don't collide
+        CREATE TABLE properties_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            external_id TEXT NOT NULL,
+            source TEXT NOT NULL,
+            operation TEXT NOT NULL DEFAULT 'sale',
+            property_type TEXT,
+            district TEXT NOT NULL,
+            title TEXT NOT NULL,
+            description TEXT,
+            price_usd REAL NOT NULL,
+            address TEXT NOT NULL,
+            covered_size REAL,
+            rooms INTEGER,
+            antiquity INTEGER,
+            url TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'active',
+            created_at DATETIME NOT NULL,
+            updated_at TEXT NOT NULL,
+            UNIQUE(source, external_id, operation)
+        );
+
+        -- Copy data from the old table; every existing listing is a sale
+        INSERT INTO properties_new (
+            id, external_id, source, operation, property_type, district, title,
+            description, price_usd, address, covered_size, rooms, antiquity,
+            url, status, created_at, updated_at
+        )
+        SELECT
+            id, external_id, source, 'sale', property_type, district, title,
+            description, price_usd, address, covered_size, rooms, antiquity,
+            url, status, created_at, updated_at
+        FROM properties;
+
+        -- Drop the old table
+        DROP TABLE properties;
+
+        -- Rename the new table to the original name
+        ALTER TABLE properties_new RENAME TO properties;
+
+        -- Recreate the status index
+        CREATE INDEX idx_properties_status ON properties(status);
+
+        -- Re-enable foreign key constraints
+        PRAGMA foreign_keys = ON;
+        "#,
+        r#"
+        -- Disable foreign key constraints while the table is rebuilt
+        PRAGMA foreign_keys = OFF;
+
+        -- Create a temporary table with the old schema
+        CREATE TABLE properties_old (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            external_id TEXT NOT NULL,
+            source TEXT NOT NULL,
+            property_type TEXT,
+            district TEXT NOT NULL,
+            title TEXT NOT NULL,
+            description TEXT,
+            price_usd REAL NOT NULL,
+            address TEXT NOT NULL,
+            covered_size REAL,
+            rooms INTEGER,
+            antiquity INTEGER,
+            url TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'active',
+            created_at DATETIME NOT NULL,
+            updated_at TEXT NOT NULL,
+            UNIQUE(source, external_id)
+        );
+
+        -- Copy data back, keeping the sale listing when a rent one shares its id
+        INSERT OR IGNORE INTO properties_old (
+            id, external_id, source, property_type, district, title,
+            description, price_usd, address, covered_size, rooms, antiquity,
+            url, status, created_at, updated_at
+        )
+        SELECT
+            id, external_id, source, property_type, district, title,
+            description, price_usd, address, covered_size, rooms, antiquity,
+            url, status, created_at, updated_at
+        FROM properties
+        ORDER BY operation = 'sale' DESC, id;
+
+        -- Drop the current table
+        DROP TABLE properties;
+
+        -- Rename the old table to the original name
+        ALTER TABLE properties_old RENAME TO properties;
+
+        -- Recreate the status index
+        CREATE INDEX idx_properties_status ON properties(status);
+
+        -- Re-enable foreign key constraints
+        PRAGMA foreign_keys = ON;
+        "#,
+    ),
+    Migration::new(
+        9,
+        r#"
+        -- Full-text index over the free-text listing fields. Rows share their
+        -- rowid with properties.id and are kept in sync by the Database layer.
+        CREATE VIRTUAL TABLE properties_fts USING fts5(
+            title,
+            description,
+            address,
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+
+        -- Index the listings that are already stored
+        INSERT INTO properties_fts (rowid, title, description, address)
+        SELECT id, title, description, address FROM properties;
+        "#,
+        r#"
+        DROP TABLE properties_fts;
+        "#,
+    ),
+    Migration::new(
+        10,
+        r#"
+        -- Coordinates filled in by the geocoder, NULL until then
+        ALTER TABLE properties ADD COLUMN latitude REAL;
+        ALTER TABLE properties ADD COLUMN longitude REAL;
+        "#,
+        r#"
+        ALTER TABLE properties DROP COLUMN longitude;
+        ALTER TABLE properties DROP COLUMN latitude;
+        "#,
+    ),
+    Migration::new(
+        11,
+        r#"
+        -- When a listing was last observed by a scrape, used to tell
+        -- listings that briefly dropped out apart from sold or removed ones
+        ALTER TABLE properties ADD COLUMN last_seen_at TEXT;
+        UPDATE properties SET last_seen_at = updated_at;
+        "#,
+        r#"
+        ALTER TABLE properties DROP COLUMN last_seen_at;
+        "#,
+    ),
+    Migration::new(
+        12,
+        r#"
+        -- Keep the listed price and its currency; price_usd becomes the
+        -- converted value. Existing rows were all stored as USD.
+        ALTER TABLE properties ADD COLUMN raw_price REAL NOT NULL DEFAULT 0;
+        ALTER TABLE properties ADD COLUMN currency TEXT NOT NULL DEFAULT 'USD';
+        UPDATE properties SET raw_price = price_usd;
+        "#,
+        r#"
+        ALTER TABLE properties DROP COLUMN currency;
+        ALTER TABLE properties DROP COLUMN raw_price;
+        "#,
+    ),
+    Migration::new(
+        13,
+        "CREATE INDEX IF NOT EXISTS idx_property_images_hash ON property_images(hash)",
+        "DROP INDEX IF EXISTS idx_property_images_hash",
+    ),
+    Migration::new(
+        14,
+        "ALTER TABLE property_images ADD COLUMN phash BLOB",
+        "ALTER TABLE property_images DROP COLUMN phash",
+    ),
+    Migration::new(
+        15,
+        r#"
+        CREATE TABLE IF NOT EXISTS saved_searches (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            district TEXT,
+            property_type TEXT,
+            min_price REAL,
+            max_price REAL,
+            min_size REAL,
+            max_size REAL,
+            created_at DATETIME NOT NULL,
+            last_checked_at DATETIME
+        );
+        "#,
+        "DROP TABLE IF EXISTS saved_searches",
+    ),
+    Migration::new(
+        16,
+        r#"
+        -- The oldest photo of each property becomes its primary one
+        ALTER TABLE property_images ADD COLUMN is_primary BOOLEAN NOT NULL DEFAULT 0;
+        UPDATE property_images SET is_primary = 1
+            WHERE id IN (SELECT MIN(id) FROM property_images GROUP BY property_id);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_property_images_primary
+            ON property_images(property_id) WHERE is_primary = 1;
+        "#,
+        r#"
+        DROP INDEX IF EXISTS idx_property_images_primary;
+        ALTER TABLE property_images DROP COLUMN is_primary;
+        "#,
+    ),
+    Migration::new(
+        17,
+        r#"
+        -- Back the district/type/price filters of list and watch, and the
+        -- per-property history lookups
+        CREATE INDEX IF NOT EXISTS idx_properties_filter
+            ON properties(district, property_type, price_usd);
+        CREATE INDEX IF NOT EXISTS idx_price_history_property
+            ON property_price_history(property_id, observed_at);
+        "#,
+        r#"
+        DROP INDEX IF EXISTS idx_price_history_property;
+        DROP INDEX IF EXISTS idx_properties_filter;
+        "#,
+    ),
+    Migration::new(
+        18,
+        r#"
+        -- Listing URL without tracking parameters, see Property::canonical_url.
+        -- Filled in as listings are saved again.
+        ALTER TABLE properties ADD COLUMN canonical_url TEXT;
+        CREATE INDEX IF NOT EXISTS idx_properties_canonical_url ON properties(canonical_url);
+        "#,
+        r#"
+        DROP INDEX IF EXISTS idx_properties_canonical_url;
+        ALTER TABLE properties DROP COLUMN canonical_url;
+        "#,
+    ),
+    Migration::new(
+        19,
+        r#"
+        -- NULL when the listing doesn't say whether the building is finished
+        ALTER TABLE properties ADD COLUMN under_construction BOOLEAN;
+        UPDATE properties SET under_construction = 0 WHERE antiquity IS NOT NULL;
+        "#,
+        "ALTER TABLE properties DROP COLUMN under_construction",
+    ),
+    Migration::new(
+        20,
+        "ALTER TABLE properties ADD COLUMN bathrooms INTEGER",
+        "ALTER TABLE properties DROP COLUMN bathrooms",
+    ),
+    Migration::new(
+        21,
+        "ALTER TABLE properties ADD COLUMN has_parking BOOLEAN",
+        "ALTER TABLE properties DROP COLUMN has_parking",
+    ),
+    Migration::new(
+        22,
+        "ALTER TABLE properties ADD COLUMN expenses_ars REAL",
+        "ALTER TABLE properties DROP COLUMN expenses_ars",
+    ),
+    Migration::new(
+        23,
+        r#"
+        -- Keep distinct prices observed at the same instant, only dropping
+        -- repeats of the same price. The unique index starts with
+        -- (property_id, observed_at), so it replaces idx_price_history_property.
+        PRAGMA foreign_keys = OFF;
+        CREATE TABLE property_price_history_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            property_id INTEGER NOT NULL,
+            price_usd REAL NOT NULL,
+            observed_at DATETIME NOT NULL,
+            FOREIGN KEY(property_id) REFERENCES properties(id),
+            UNIQUE(property_id, observed_at, price_usd)
+        );
+        INSERT INTO property_price_history_new (id, property_id, price_usd, observed_at)
+            SELECT id, property_id, price_usd, observed_at FROM property_price_history;
+        DROP TABLE property_price_history;
+        ALTER TABLE property_price_history_new RENAME TO property_price_history;
+        PRAGMA foreign_keys = ON;
+        "#,
+        r#"
+        PRAGMA foreign_keys = OFF;
+        CREATE TABLE property_price_history_old (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            property_id INTEGER NOT NULL,
+            price_usd REAL NOT NULL,
+            observed_at DATETIME NOT NULL,
+            FOREIGN KEY(property_id) REFERENCES properties(id),
+            UNIQUE(property_id, observed_at)
+        );
+        INSERT INTO property_price_history_old (id, property_id, price_usd, observed_at)
+            SELECT id, property_id, price_usd, observed_at FROM property_price_history
+            WHERE id IN (SELECT MIN(id) FROM property_price_history GROUP BY property_id, observed_at);
+        DROP TABLE property_price_history;
+        ALTER TABLE property_price_history_old RENAME TO property_price_history;
+        CREATE INDEX IF NOT EXISTS idx_price_history_property
+            ON property_price_history(property_id, observed_at);
+        PRAGMA foreign_keys = ON;
+        "#,
+    ),
+    Migration::new(
+        24,
+        r#"
+        -- Free-form labels users put on listings, at most once per listing
+        CREATE TABLE IF NOT EXISTS property_tags (
+            property_id INTEGER NOT NULL,
+            tag TEXT NOT NULL,
+            FOREIGN KEY(property_id) REFERENCES properties(id),
+            PRIMARY KEY(property_id, tag)
+        );
+        CREATE INDEX IF NOT EXISTS idx_property_tags_tag ON property_tags(tag);
+        "#,
+        "DROP TABLE IF EXISTS property_tags",
+    ),
+    Migration::new(
+        25,
+        "ALTER TABLE properties ADD COLUMN notes TEXT",
+        "ALTER TABLE properties DROP COLUMN notes",
+    ),
+    Migration::new(
+        26,
+        r#"
+        -- Property types are compared lowercased, as older rows hold their
+        -- display form, and an index on the bare column can't serve that
+        DROP INDEX IF EXISTS idx_properties_filter;
+        CREATE INDEX IF NOT EXISTS idx_properties_filter
+            ON properties(district, LOWER(property_type), price_usd);
+        "#,
+        r#"
+        DROP INDEX IF EXISTS idx_properties_filter;
+        CREATE INDEX IF NOT EXISTS idx_properties_filter
+            ON properties(district, property_type, price_usd);
+        "#,
+    ),
 ];
 
-pub async fn apply_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+pub async fn apply_migrations(pool: &SqlitePool) -> crate::Result<()> {
+    // Other pooled connections keep the schema they loaded until they next
+    // read from the database, so run everything on one connection
+    let mut conn = pool.acquire().await?;
+
     // Create migrations table if it doesn't exist
     sqlx::query(
         "CREATE TABLE IF NOT EXISTS migrations (
             version INTEGER PRIMARY KEY,
-            applied_at DATETIME NOT NULL
+            applied_at DATETIME NOT NULL,
+            checksum TEXT
         )"
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await?;
 
+    // Tables created before checksums were recorded lack the column
+    let has_checksum: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info('migrations') WHERE name = 'checksum')"
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    if !has_checksum {
+        sqlx::query("ALTER TABLE migrations ADD COLUMN checksum TEXT")
+            .execute(&mut *conn)
+            .await?;
+    }
+
     // Get applied migrations
-    let applied_versions: Vec<i32> = sqlx::query_scalar("SELECT version FROM migrations ORDER BY version")
-        .fetch_all(pool)
+    let applied: Vec<(i32, Option<String>)> = sqlx::query_as("SELECT version, checksum FROM migrations ORDER BY version")
+        .fetch_all(&mut *conn)
         .await?;
 
+    // Verify applied migrations still match the code, trusting the ones
+    // applied before checksums were recorded
+    for (version, checksum) in &applied {
+        let Some(migration) = MIGRATIONS.iter().find(|m| m.version == *version) else {
+            continue;
+        };
+        match checksum {
+            Some(checksum) if *checksum != migration.checksum() => {
+                return Err(BreaError::Schema(format!(
+                    "migration {} was changed after being applied (checksum {} in the database, {} in the code)",
+                    version,
+                    checksum,
+                    migration.checksum()
+                )));
+            }
+            Some(_) => {}
+            None => {
+                sqlx::query("UPDATE migrations SET checksum = ? WHERE version = ?")
+                    .bind(migration.checksum())
+                    .bind(version)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+    }
+
     // Apply pending migrations
     for migration in MIGRATIONS {
-        if !applied_versions.contains(&migration.version) {
+        if !applied.iter().any(|(version, _)| *version == migration.version) {
             // Apply migration
             sqlx::query(migration.up)
-                .execute(pool)
+                .execute(&mut *conn)
                 .await?;
 
             // Record migration
             sqlx::query(
-                "INSERT INTO migrations (version, applied_at) VALUES (?, ?)"
+                "INSERT INTO migrations (version, applied_at, checksum) VALUES (?, ?, ?)"
             )
             .bind(migration.version)
             .bind(chrono::Utc::now())
-            .execute(pool)
+            .bind(migration.checksum())
+            .execute(&mut *conn)
             .await?;
         }
     }
@@ -452,15 +839,27 @@ pub async fn rollback_migration(pool: &SqlitePool, version: i32) -> Result<(), s
         .find(|m| m.version == version)
         .ok_or_else(|| sqlx::Error::Decode("Migration not found".into()))?;
 
+    // Reading from the database first also reloads a schema this connection
+    // cached before the migration was applied. SQLite doesn't check it when
+    // preparing `ALTER TABLE ... DROP COLUMN`, which would fail otherwise.
+    let mut conn = pool.acquire().await?;
+    let applied: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM migrations WHERE version = ?)")
+        .bind(version)
+        .fetch_one(&mut *conn)
+        .await?;
+    if !applied {
+        return Err(sqlx::Error::Decode("Migration not applied".into()));
+    }
+
     // Apply down migration
     sqlx::query(migration.down)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
     // Remove migration record
     sqlx::query("DELETE FROM migrations WHERE version = ?")
         .bind(version)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
     Ok(())
@@ -6,154 +6,835 @@ pub use migrations::apply_migrations;
 pub use queries::{PropertyQueryBuilder, PropertyImageQueryBuilder};
 pub use types::{DbPropertyStatus, STATUS_ACTIVE, STATUS_SOLD, STATUS_REMOVED};
 
-use crate::{Property, PropertyImage, Result};
+use crate::export::{json_record, price_history_record, TimestampFormat};
+use crate::{
+    BreaError, Currency, Operation, PriceObservation, Property, PropertyImage, PropertyKey, PropertyStatus, PropertyType,
+    PropertyWithHistory, Result, SavedSearch,
+};
 use chrono::{DateTime, Utc};
-use sqlx::{sqlite::SqlitePool, Row};
+use futures::{Stream, TryStreamExt};
+use serde::Serialize;
+use sqlx::sqlite::{Sqlite, SqliteConnectOptions, SqliteConnection, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use sqlx::{Connection, Executor, QueryBuilder, Row};
+use crate::currency::{RateProvider, StaticRateProvider};
+use crate::districts::normalize_district;
+use crate::images::hamming_distance;
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::io::Write;
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use tempfile::NamedTempFile;
+use tracing::warn;
 use std::path::PathBuf;
 use crate::db::migrations::Migration;
 use crate::db::types::DbTimestamp;
 
+/// Number of price history entries kept per property by default when
+/// cleaning up.
+pub const DEFAULT_PRICE_HISTORY_RETENTION: usize = 10;
+
+/// Environment variable holding [`Database::with_min_price_change`]'s
+/// percentage.
+pub const MIN_PRICE_CHANGE_ENV: &str = "BREA_MIN_PRICE_CHANGE";
+
+/// Rows per multi-row `INSERT` in `save_properties_batch`, keeping each
+/// statement well below SQLite's bound-parameter limit.
+const BATCH_INSERT_CHUNK_SIZE: usize = 500;
+
+/// Listings whose status was changed by `Database::reconcile_listing_status`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StatusChanges {
+    /// Ids of properties newly marked sold
+    pub sold: Vec<i64>,
+    /// Ids of properties newly marked removed
+    pub removed: Vec<i64>,
+}
+
+/// How a fresh scrape differs from what was stored, see
+/// `Database::diff_against`.
+#[derive(Debug, Default, Clone)]
+pub struct ScrapeDiff {
+    /// Listings first stored by the fresh scrape
+    pub added: Vec<Property>,
+    /// Active listings the fresh scrape didn't find
+    pub removed: Vec<Property>,
+    /// Listings whose price the fresh scrape changed, with the old and the
+    /// new price
+    pub price_changed: Vec<(Property, f64, f64)>,
+}
+
+/// What `Database::repair_integrity` fixes. By default everything is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepairOptions {
+    /// Delete photos whose property no longer exists
+    pub orphaned_images: bool,
+    /// Merge properties stored more than once into the oldest of them
+    pub duplicates: bool,
+    /// Only report what would be fixed, changing nothing
+    pub dry_run: bool,
+}
+
+impl Default for RepairOptions {
+    fn default() -> Self {
+        Self { orphaned_images: true, duplicates: true, dry_run: false }
+    }
+}
+
+/// What `Database::repair_integrity` fixed, or would fix on a dry run.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RepairReport {
+    /// Ids of the photos whose property was gone
+    pub orphaned_images: Vec<i64>,
+    /// `(kept, merged)` ids of each duplicate property and the one it was
+    /// merged into
+    pub merged_duplicates: Vec<(i64, i64)>,
+}
+
+/// What `Database::merge_from` brought in.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MergeReport {
+    /// Listings only the other database had
+    pub inserted: usize,
+    /// Listings both had, updated with the other's more recent scrape
+    pub updated: usize,
+    /// Listings both had, already as recent here
+    pub skipped: usize,
+    /// Price history entries added
+    pub price_history: usize,
+}
+
+/// A listing's price moving between two scrapes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PriceChange {
+    pub property_id: i64,
+    pub old_price: f64,
+    pub new_price: f64,
+    pub observed_at: DbTimestamp,
+}
+
+/// What `Database::save_property` did with a scraped listing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveOutcome {
+    /// First time the listing was seen
+    Inserted,
+    /// The stored listing differed from the scraped one
+    Updated { price_change: Option<PriceChange> },
+    /// The scrape matched what was stored; only `last_seen_at` moved
+    Unchanged,
+}
+
+/// Summary of the stored listings, see `Database::compute_stats`.
+///
+/// Prices of zero (listings that don't publish one) are left out of the
+/// price figures, and sizes of zero out of the size ones.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DbStats {
+    pub total: i64,
+    pub by_status: Vec<(String, i64)>,
+    /// Listings without a property type are counted as "unknown"
+    pub by_property_type: Vec<(String, i64)>,
+    pub median_price_usd: f64,
+    pub average_price_usd: f64,
+    pub average_covered_size: f64,
+    /// Average USD per covered m² of each district's listings
+    pub price_per_m2_by_district: Vec<(String, f64)>,
+}
+
+/// Price figures of one district and property type, see
+/// `Database::district_type_stats`. Listings without a price are left out,
+/// and those without a covered size are left out of `price_per_m2`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PriceStats {
+    /// Listings with a price
+    pub count: i64,
+    /// `None` when no listing has a price
+    pub price_usd: Option<Distribution>,
+    /// USD per covered m², `None` when no listing has both a price and a size
+    pub price_per_m2: Option<Distribution>,
+}
+
+/// Spread of a set of values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Distribution {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+}
+
+/// Median of values sorted in ascending order.
+fn median(sorted: &[f64]) -> Option<f64> {
+    match sorted.len() {
+        0 => None,
+        n if n % 2 == 0 => Some((sorted[n / 2 - 1] + sorted[n / 2]) / 2.0),
+        n => Some(sorted[n / 2]),
+    }
+}
+
+/// Reads [`MIN_PRICE_CHANGE_ENV`], falling back to 0 (every change) if the
+/// variable is missing or not a non-negative number.
+fn min_price_change_from_env() -> f64 {
+    std::env::var(MIN_PRICE_CHANGE_ENV)
+        .ok()
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .filter(|percent| *percent >= 0.0)
+        .unwrap_or(0.0)
+}
+
+/// Numbers the databases of `Database::new_in_memory_shared`, so each one
+/// gets a name of its own.
+static IN_MEMORY_DATABASES: AtomicUsize = AtomicUsize::new(0);
+
+/// Connection settings for `Database::with_options`.
+#[derive(Debug, Clone)]
+pub struct DatabaseOptions {
+    /// Connections kept in the pool
+    pub max_connections: u32,
+    /// How long a connection waits on a locked database before failing
+    pub busy_timeout: Duration,
+    /// Use write-ahead logging, letting readers proceed while a write is in
+    /// progress. Has no effect on in-memory databases.
+    pub wal: bool,
+}
+
+impl Default for DatabaseOptions {
+    fn default() -> Self {
+        Self {
+            max_connections: 5,
+            busy_timeout: Duration::from_secs(5),
+            wal: true,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Database {
     pool: SqlitePool,
     migrations: Vec<Migration>,
+    rates: Arc<dyn RateProvider>,
+    validate: bool,
+    /// Percentage a price must move by to be added to the history
+    min_price_change: f64,
 }
 
 impl Database {
+    /// Opens the database, refusing schemas too old for the current code.
+    /// Use [`Database::new_without_migrations`] to open one for migrating.
     pub async fn new(db_path: impl AsRef<Path>) -> Result<Self> {
-        // Ensure parent directory exists
-        if let Some(parent) = db_path.as_ref().parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-        let pool = SqlitePool::connect(&format!("sqlite:{}", db_path.as_ref().display())).await?;
-        let migrations = Vec::new();
-        Ok(Self { pool, migrations })
+        let db = Self::with_options(db_path, DatabaseOptions::default()).await?;
+        db.check_schema().await?;
+        Ok(db)
     }
 
     pub async fn new_without_migrations(db_path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_options(db_path, DatabaseOptions::default()).await
+    }
+
+    /// Scrapers leave sizes, rooms and age empty when a listing doesn't give
+    /// them, which databases created before migration 6 reject.
+    async fn check_schema(&self) -> Result<()> {
+        let not_null: Vec<String> = sqlx::query_scalar(
+            "SELECT name FROM pragma_table_info('properties') WHERE \"notnull\" AND name IN ('covered_size', 'rooms', 'antiquity') ORDER BY cid"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        if !not_null.is_empty() {
+            return Err(BreaError::Schema(format!(
+                "properties.{} must allow NULL, run `brea database -a up` to upgrade the database",
+                not_null.join(", properties.")
+            )));
+        }
+        Ok(())
+    }
+
+    pub async fn with_options(db_path: impl AsRef<Path>, options: DatabaseOptions) -> Result<Self> {
         // Ensure parent directory exists
         if let Some(parent) = db_path.as_ref().parent() {
             std::fs::create_dir_all(parent)?;
         }
-        let pool = SqlitePool::connect(&format!("sqlite:{}", db_path.as_ref().display())).await?;
+        let journal_mode = if options.wal {
+            SqliteJournalMode::Wal
+        } else {
+            SqliteJournalMode::Delete
+        };
+        let connect_options = SqliteConnectOptions::from_str(&format!("sqlite:{}", db_path.as_ref().display()))?
+            .create_if_missing(true)
+            .busy_timeout(options.busy_timeout)
+            .journal_mode(journal_mode);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(options.max_connections)
+            .connect_with(connect_options)
+            .await?;
         let migrations = Vec::new();
-        Ok(Self { pool, migrations })
+        Ok(Self {
+            pool,
+            migrations,
+            rates: Arc::new(StaticRateProvider::from_env()),
+            validate: false,
+            min_price_change: min_price_change_from_env(),
+        })
+    }
+
+    /// A new in-memory database shared by every connection of the pool, so
+    /// migrations applied on one are seen by all. Migrations aren't applied.
+    ///
+    /// SQLite drops an in-memory database when its last connection closes,
+    /// so the pool never closes idle connections and keeps at least one
+    /// open. The data lives as long as the `Database` or one of its clones
+    /// does, and is gone once they are all dropped.
+    pub async fn new_in_memory_shared() -> Result<Self> {
+        let name = format!("brea-memory-{}", IN_MEMORY_DATABASES.fetch_add(1, Ordering::Relaxed));
+        let options = DatabaseOptions::default();
+        let connect_options = SqliteConnectOptions::from_str(&format!("sqlite:file:{}?mode=memory&cache=shared", name))?
+            .busy_timeout(options.busy_timeout);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(options.max_connections)
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(connect_options)
+            .await?;
+        Ok(Self {
+            pool,
+            migrations: Vec::new(),
+            rates: Arc::new(StaticRateProvider::from_env()),
+            validate: false,
+            min_price_change: min_price_change_from_env(),
+        })
     }
 
     pub fn pool(&self) -> &SqlitePool {
         &self.pool
     }
 
-    pub async fn save_property(&self, property: &mut Property) -> Result<()> {
-        // First try to find an existing property with the same source and external_id
-        let existing_property = sqlx::query_as::<_, Property>(
-            "SELECT * FROM properties WHERE source = ? AND external_id = ?"
+    /// Every known migration as `(version, applied_at, pending)`, oldest
+    /// first. Pending migrations have no `applied_at`.
+    pub async fn migration_status(&self) -> Result<Vec<(i32, Option<DateTime<Utc>>, bool)>> {
+        let has_table: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'migrations')"
         )
-        .bind(&property.source)
-        .bind(&property.external_id)
-        .fetch_optional(&self.pool)
+        .fetch_one(&self.pool)
         .await?;
+        let applied: HashMap<i32, DateTime<Utc>> = if has_table {
+            sqlx::query_as("SELECT version, applied_at FROM migrations")
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        Ok(migrations::MIGRATIONS
+            .iter()
+            .map(|migration| {
+                let applied_at = applied.get(&migration.version()).copied();
+                (migration.version(), applied_at, applied_at.is_none())
+            })
+            .collect())
+    }
+
+    /// Replaces the exchange rates used to compute `price_usd` on save,
+    /// which default to [`StaticRateProvider::from_env`].
+    pub fn with_rate_provider(mut self, rates: Arc<dyn RateProvider>) -> Self {
+        self.rates = rates;
+        self
+    }
+
+    /// Makes saves fail with [`BreaError::InvalidProperty`] on listings that
+    /// don't pass [`Property::validate`], instead of storing them. Off by
+    /// default.
+    pub fn with_validation(mut self, validate: bool) -> Self {
+        self.validate = validate;
+        self
+    }
+
+    /// Only adds a new price to the history when it differs from the last
+    /// one recorded there by at least `percent` percent, so rounding in currency conversion
+    /// doesn't fill it with near repeats. Smaller changes still update the
+    /// current price. Defaults to the value of `BREA_MIN_PRICE_CHANGE`, or 0
+    /// to record every change.
+    pub fn with_min_price_change(mut self, percent: f64) -> Self {
+        self.min_price_change = percent.max(0.0);
+        self
+    }
+
+    /// Whether going from `old`, the last price in the history, to `new` is a
+    /// price change worth a history row. Measuring from the stored price
+    /// instead would let small steps add up without ever being recorded.
+    fn records_price_change(&self, old: f64, new: f64) -> bool {
+        if old == new {
+            return false;
+        }
+        old == 0.0 || (new - old).abs() / old.abs() * 100.0 >= self.min_price_change
+    }
+
+    fn check_valid(&self, property: &Property) -> Result<()> {
+        if !self.validate {
+            return Ok(());
+        }
+        property.validate().map_err(|problems| {
+            BreaError::InvalidProperty(format!("{}: {}", property.key(), problems.join(", ")))
+        })
+    }
+
+    /// Sets `price_usd` from the listed price and currency.
+    async fn convert_price(&self, property: &mut Property) -> Result<()> {
+        let rate = self.rates.usd_rate(property.currency, Utc::now()).await?;
+        property.price_usd = property.raw_price * rate;
+        Ok(())
+    }
+
+    pub async fn save_property(&self, property: &mut Property) -> Result<SaveOutcome> {
+        self.check_valid(property)?;
+        self.convert_price(property).await?;
+        property.district = normalize_district(&property.district);
+        property.last_seen_at = DbTimestamp::now();
+
+        // First try to find an existing property with the same source and external_id
+        let existing_property = Self::find_existing(&self.pool, property).await?;
 
         match existing_property {
             Some(existing) => {
-                // Update the property's ID to match the existing one
-                property.id = existing.id;
-                // Record price history if the price has changed
-                if existing.price_usd != property.price_usd {
-                    self.record_price_history(
-                        existing.id,
-                        property.price_usd,
-                        DbTimestamp::now()
-                    ).await?;
+                // Take the existing ID, the key it was stored under when
+                // matched by URL, and whatever else the scrape doesn't own
+                property.keep_user_fields(&existing);
+                // Record price history if the price has changed by enough
+                // since it was last recorded, see `with_min_price_change`
+                let recorded_price = Self::last_recorded_price(&self.pool, existing.id)
+                    .await?
+                    .unwrap_or(existing.price_usd);
+                let price_change = if self.records_price_change(recorded_price, property.price_usd) {
+                    let observed_at = DbTimestamp::now();
+                    self.record_price_history(existing.id, property.price_usd, observed_at.clone()).await?;
+                    Some(PriceChange {
+                        property_id: existing.id,
+                        old_price: recorded_price,
+                        new_price: property.price_usd,
+                        observed_at,
+                    })
+                } else {
+                    None
+                };
+                let changed = existing.price_usd != property.price_usd || Self::listing_changed(&existing, property);
+                // Incremental exports rely on `updated_at` only moving when
+                // something did
+                if !changed {
+                    property.updated_at = existing.updated_at.clone();
                 }
+
                 // Update the existing property
-                self.update_property(property).await
+                self.update_property(property).await?;
+
+                Ok(if changed {
+                    SaveOutcome::Updated { price_change }
+                } else {
+                    SaveOutcome::Unchanged
+                })
             }
             None => {
                 // Insert as a new property
-                let id = sqlx::query(
-                    r#"
-                    INSERT INTO properties (
-                        external_id, source, property_type, district, title,
-                        description, price_usd, address, covered_size, rooms,
-                        antiquity, url, status, created_at, updated_at
-                    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-                    "#,
-                )
-                .bind(&property.external_id)
-                .bind(&property.source)
-                .bind(&property.property_type)
-                .bind(&property.district)
-                .bind(&property.title)
-                .bind(&property.description)
-                .bind(property.price_usd)
-                .bind(&property.address)
-                .bind(property.covered_size)
-                .bind(property.rooms)
-                .bind(property.antiquity)
-                .bind(&property.url)
-                .bind(&property.status)
-                .bind(&property.created_at)
-                .bind(&property.updated_at)
-                .execute(&self.pool)
-                .await?
-                .last_insert_rowid();
-
-                property.id = id;
+                property.id = Self::insert_row(&self.pool, property).await?;
+                Self::index_for_search(&self.pool, property).await?;
 
                 // Record initial price history
                 self.record_price_history(
-                    id,
+                    property.id,
                     property.price_usd,
                     DbTimestamp::now()
                 ).await?;
 
-                Ok(())
+                Ok(SaveOutcome::Inserted)
+            }
+        }
+    }
+
+    /// Like [`Self::save_property`], but a listing whose price can't be
+    /// converted to USD, such as an ARS one with no rate configured, is
+    /// skipped with a warning and `None` returned, so that one listing
+    /// doesn't abort a whole scrape.
+    pub async fn save_property_or_skip(&self, property: &mut Property) -> Result<Option<SaveOutcome>> {
+        match self.save_property(property).await {
+            Ok(outcome) => Ok(Some(outcome)),
+            Err(BreaError::Currency(e)) => {
+                warn!("Skipping {}: {}", property.key(), e);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether a re-scraped listing carries details that differ from the stored ones.
+    fn listing_changed(existing: &Property, scraped: &Property) -> bool {
+        existing.property_type != scraped.property_type
+            || existing.district != scraped.district
+            || existing.title != scraped.title
+            || existing.description != scraped.description
+            || existing.raw_price != scraped.raw_price
+            || existing.currency != scraped.currency
+            || existing.address != scraped.address
+            || existing.covered_size != scraped.covered_size
+            || existing.rooms != scraped.rooms
+            || existing.antiquity != scraped.antiquity
+            || existing.under_construction != scraped.under_construction
+            || existing.bathrooms != scraped.bathrooms
+            || existing.has_parking != scraped.has_parking
+            || existing.expenses_ars != scraped.expenses_ars
+            || existing.url != scraped.url
+            || existing.status != scraped.status
+    }
+
+    /// Saves many properties at once, with the same upsert semantics as
+    /// `save_property`.
+    ///
+    /// Everything runs in a single transaction: existing listings (matched on
+    /// source, external_id and operation, or on the page's URL) are updated
+    /// in place, and new ones are written with multi-row `INSERT`s. Each
+    /// property gets its `id` backfilled and its initial or changed price
+    /// recorded in the history.
+    /// With validation on, one invalid listing fails the whole batch. As
+    /// with [`Self::save_property_or_skip`], listings whose price can't be
+    /// converted to USD are skipped with a warning and keep an `id` of 0.
+    pub async fn save_properties_batch(&self, properties: &mut [Property]) -> Result<()> {
+        for property in properties.iter() {
+            self.check_valid(property)?;
+        }
+        let mut unpriced = HashSet::new();
+        for (index, property) in properties.iter_mut().enumerate() {
+            match self.convert_price(property).await {
+                Ok(()) => {}
+                Err(BreaError::Currency(e)) => {
+                    warn!("Skipping {}: {}", property.key(), e);
+                    unpriced.insert(index);
+                }
+                Err(e) => return Err(e),
+            }
+            property.district = normalize_district(&property.district);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        // Split the batch into updates of stored listings and brand new ones.
+        // A listing repeated within the batch, under its key or its page's
+        // URL as `find_existing` matches them, is inserted once and the
+        // later copies are applied as updates on top of it.
+        let mut new_listings: HashMap<(PropertyKey, Operation), usize> = HashMap::new();
+        let mut new_urls: HashMap<(String, Operation), usize> = HashMap::new();
+        let mut to_insert = Vec::new();
+        let mut to_update = Vec::new();
+        let seen_at = DbTimestamp::now();
+        for (index, property) in properties.iter_mut().enumerate() {
+            if unpriced.contains(&index) {
+                continue;
+            }
+            property.last_seen_at = seen_at.clone();
+            match Self::find_existing(&mut *tx, property).await? {
+                Some(existing) => {
+                    property.keep_user_fields(&existing);
+                    let price_changed = existing.price_usd != property.price_usd;
+                    if !price_changed && !Self::listing_changed(&existing, property) {
+                        property.updated_at = existing.updated_at;
+                    }
+                    let recorded_price = Self::last_recorded_price(&mut *tx, existing.id)
+                        .await?
+                        .unwrap_or(existing.price_usd);
+                    if self.records_price_change(recorded_price, property.price_usd) {
+                        Self::insert_price_history(&mut *tx, existing.id, property.price_usd, &DbTimestamp::now()).await?;
+                    }
+                    Self::update_row(&mut *tx, property).await?;
+                    Self::index_for_search(&mut *tx, property).await?;
+                }
+                None => {
+                    let key = (property.key(), property.operation);
+                    let url = (property.canonical_url(), property.operation);
+                    match new_listings.get(&key).or_else(|| new_urls.get(&url)) {
+                        Some(&first) => to_update.push((index, first)),
+                        None => {
+                            new_listings.insert(key, index);
+                            new_urls.insert(url, index);
+                            to_insert.push(index);
+                        }
+                    }
+                }
+            }
+        }
+
+        for chunk in to_insert.chunks(BATCH_INSERT_CHUNK_SIZE) {
+            let rows = {
+                let mut builder = QueryBuilder::<Sqlite>::new(
+                    "INSERT INTO properties (
+                        external_id, source, operation, property_type, district, title,
+                        description, price_usd, raw_price, currency, address, latitude, longitude,
+                        covered_size, rooms, antiquity, under_construction, bathrooms, has_parking,
+                        expenses_ars, notes, url, canonical_url, status, created_at, updated_at, last_seen_at
+                    ) "
+                );
+                builder.push_values(chunk.iter().map(|&index| &properties[index]), |mut row, property| {
+                    row.push_bind(&property.external_id)
+                        .push_bind(&property.source)
+                        .push_bind(property.operation)
+                        .push_bind(&property.property_type)
+                        .push_bind(&property.district)
+                        .push_bind(&property.title)
+                        .push_bind(&property.description)
+                        .push_bind(property.price_usd)
+                        .push_bind(property.raw_price)
+                        .push_bind(property.currency)
+                        .push_bind(&property.address)
+                        .push_bind(property.latitude)
+                        .push_bind(property.longitude)
+                        .push_bind(property.covered_size)
+                        .push_bind(property.rooms)
+                        .push_bind(property.antiquity)
+                        .push_bind(property.under_construction)
+                        .push_bind(property.bathrooms)
+                        .push_bind(property.has_parking)
+                        .push_bind(property.expenses_ars)
+                        .push_bind(&property.notes)
+                        .push_bind(&property.url)
+                        .push_bind(property.canonical_url())
+                        .push_bind(&property.status)
+                        .push_bind(&property.created_at)
+                        .push_bind(&property.updated_at)
+                        .push_bind(&property.last_seen_at);
+                });
+                builder.push(" RETURNING id, source, external_id, operation");
+                builder.build().fetch_all(&mut *tx).await?
+            };
+
+            // RETURNING order isn't guaranteed, so match rows back by listing key
+            for row in rows {
+                let listing = PropertyKey::new(row.get::<String, _>("source"), row.get::<String, _>("external_id"));
+                let key = (listing, row.get("operation"));
+                if let Some(&index) = new_listings.get(&key) {
+                    let property = &mut properties[index];
+                    property.id = row.get("id");
+                    Self::index_for_search(&mut *tx, property).await?;
+                    Self::insert_price_history(&mut *tx, property.id, property.price_usd, &DbTimestamp::now()).await?;
+                }
+            }
+        }
+
+        for (index, first) in to_update {
+            let first = properties[first].clone();
+            let (id, first_price) = (first.id, first.price_usd);
+
+            // Stored under the first copy's key, as `save_property` would
+            let property = &mut properties[index];
+            property.keep_user_fields(&first);
+            let recorded_price = Self::last_recorded_price(&mut *tx, id).await?.unwrap_or(first_price);
+            if self.records_price_change(recorded_price, property.price_usd) {
+                Self::insert_price_history(&mut *tx, id, property.price_usd, &DbTimestamp::now()).await?;
             }
+            Self::update_row(&mut *tx, property).await?;
+            Self::index_for_search(&mut *tx, property).await?;
         }
+
+        tx.commit().await?;
+        Ok(())
     }
 
+    /// Updates a stored listing with what a scrape owns of `property`; notes,
+    /// coordinates already set and `created_at` are left as they are.
     pub async fn update_property(&self, property: &Property) -> Result<()> {
+        Self::update_row(&self.pool, property).await?;
+        Self::index_for_search(&self.pool, property).await
+    }
+
+    /// Full-text search over listing titles, descriptions and addresses.
+    ///
+    /// `query` uses SQLite FTS5 syntax, so plain words match anywhere
+    /// ("terraza cochera" needs both), and `OR`, `"phrases"` and `prefix*`
+    /// work as well. Results are ordered by relevance.
+    pub async fn search_properties(&self, query: &str) -> Result<Vec<Property>> {
+        let has_index: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'properties_fts')"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if !has_index {
+            return Err(BreaError::Schema(
+                "full-text search index is missing, run `brea database up` to create it".to_string(),
+            ));
+        }
+
+        let properties = sqlx::query_as::<_, Property>(
+            r#"
+            SELECT properties.* FROM properties
+            JOIN properties_fts ON properties_fts.rowid = properties.id
+            WHERE properties_fts MATCH ?
+            ORDER BY properties_fts.rank
+            "#
+        )
+        .bind(query)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(properties)
+    }
+
+    async fn find_existing<'e, E>(executor: E, property: &Property) -> Result<Option<Property>>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        // Another source, or the same one under a new id, may list the same
+        // page; the listing's own key wins over a URL match
+        let existing = sqlx::query_as::<_, Property>(
+            r#"
+            SELECT * FROM properties
+            WHERE operation = ?
+                AND ((source = ? AND external_id = ?) OR canonical_url = ?)
+            ORDER BY source = ? AND external_id = ? DESC, id
+            LIMIT 1
+            "#,
+        )
+        .bind(property.operation)
+        .bind(&property.source)
+        .bind(&property.external_id)
+        .bind(property.canonical_url())
+        .bind(&property.source)
+        .bind(&property.external_id)
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(existing)
+    }
+
+    async fn insert_row<'e, E>(executor: E, property: &Property) -> Result<i64>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        let id = sqlx::query(
+            r#"
+            INSERT INTO properties (
+                external_id, source, operation, property_type, district, title,
+                description, price_usd, raw_price, currency, address, latitude, longitude,
+                covered_size, rooms, antiquity, under_construction, bathrooms, has_parking,
+                expenses_ars, notes, url, canonical_url, status, created_at, updated_at, last_seen_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&property.external_id)
+        .bind(&property.source)
+        .bind(property.operation)
+        .bind(&property.property_type)
+        .bind(&property.district)
+        .bind(&property.title)
+        .bind(&property.description)
+        .bind(property.price_usd)
+        .bind(property.raw_price)
+        .bind(property.currency)
+        .bind(&property.address)
+        .bind(property.latitude)
+        .bind(property.longitude)
+        .bind(property.covered_size)
+        .bind(property.rooms)
+        .bind(property.antiquity)
+        .bind(property.under_construction)
+        .bind(property.bathrooms)
+        .bind(property.has_parking)
+        .bind(property.expenses_ars)
+        .bind(&property.notes)
+        .bind(&property.url)
+        .bind(property.canonical_url())
+        .bind(&property.status)
+        .bind(&property.created_at)
+        .bind(&property.updated_at)
+        .bind(&property.last_seen_at)
+        .execute(executor)
+        .await?
+        .last_insert_rowid();
+
+        Ok(id)
+    }
+
+    /// Writes the property's searchable text into `properties_fts`,
+    /// replacing any previous entry for the same id.
+    async fn index_for_search<'e, E>(executor: E, property: &Property) -> Result<()>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        sqlx::query(
+            "INSERT OR REPLACE INTO properties_fts (rowid, title, description, address) VALUES (?, ?, ?, ?)"
+        )
+        .bind(property.id)
+        .bind(&property.title)
+        .bind(&property.description)
+        .bind(&property.address)
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Writes the columns a scrape owns, leaving the user's alone, see
+    /// [`Property`] for which are which.
+    async fn update_row<'e, E>(executor: E, property: &Property) -> Result<()>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
         sqlx::query(
             r#"
             UPDATE properties SET
                 external_id = ?,
                 source = ?,
+                operation = ?,
                 property_type = ?,
                 district = ?,
                 title = ?,
                 description = ?,
                 price_usd = ?,
+                raw_price = ?,
+                currency = ?,
                 address = ?,
+                -- Coordinates are the user's once set, see Property
+                latitude = COALESCE(latitude, ?),
+                longitude = COALESCE(longitude, ?),
                 covered_size = ?,
                 rooms = ?,
                 antiquity = ?,
+                under_construction = ?,
+                bathrooms = ?,
+                has_parking = ?,
+                expenses_ars = ?,
                 url = ?,
+                canonical_url = ?,
                 status = ?,
-                created_at = ?,
-                updated_at = ?
+                updated_at = ?,
+                last_seen_at = ?
             WHERE id = ?
             "#,
         )
         .bind(&property.external_id)
         .bind(&property.source)
+        .bind(property.operation)
         .bind(&property.property_type)
-        .bind(&property.district)
+        .bind(normalize_district(&property.district))
         .bind(&property.title)
         .bind(&property.description)
         .bind(property.price_usd)
+        .bind(property.raw_price)
+        .bind(property.currency)
         .bind(&property.address)
+        .bind(property.latitude)
+        .bind(property.longitude)
         .bind(property.covered_size)
         .bind(property.rooms)
         .bind(property.antiquity)
+        .bind(property.under_construction)
+        .bind(property.bathrooms)
+        .bind(property.has_parking)
+        .bind(property.expenses_ars)
         .bind(&property.url)
+        .bind(property.canonical_url())
         .bind(&property.status)
-        .bind(&property.created_at)
         .bind(&property.updated_at)
+        .bind(&property.last_seen_at)
         .bind(property.id)
-        .execute(&self.pool)
+        .execute(executor)
         .await?;
 
         Ok(())
@@ -181,6 +862,21 @@ impl Database {
         Ok(property)
     }
 
+    /// The listing stored under `key` for `operation`. A site may list the
+    /// same id for sale and for rent, and those are separate listings.
+    pub async fn get_property_by_key(&self, key: &PropertyKey, operation: Operation) -> Result<Option<Property>> {
+        let property = sqlx::query_as::<_, Property>(
+            "SELECT * FROM properties WHERE source = ? AND external_id = ? AND operation = ?"
+        )
+        .bind(&key.source)
+        .bind(&key.external_id)
+        .bind(operation)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(property)
+    }
+
     pub async fn get_properties(&self) -> Result<Vec<Property>> {
         let properties = sqlx::query_as::<_, Property>(
             "SELECT * FROM properties ORDER BY id DESC"
@@ -191,90 +887,719 @@ impl Database {
         Ok(properties)
     }
 
-    pub async fn get_active_properties(&self) -> Result<Vec<Property>> {
-        PropertyQueryBuilder::new()
-            .with_status(DbPropertyStatus::new(STATUS_ACTIVE))
-            .execute(&self.pool)
-            .await
+    /// Streams every property in id order without loading the whole table.
+    pub fn stream_properties(&self) -> impl Stream<Item = Result<Property>> + '_ {
+        self.stream_properties_updated_since(None)
     }
 
-    pub async fn get_sold_properties(&self) -> Result<Vec<Property>> {
+    /// Like [`Self::stream_properties`], but only the properties updated at
+    /// or after `since`, if given.
+    pub fn stream_properties_updated_since(&self, since: Option<DateTime<Utc>>) -> impl Stream<Item = Result<Property>> + '_ {
+        let since = since.map(|since| since.to_rfc3339());
+        sqlx::query_as::<_, Property>(
+            "SELECT * FROM properties WHERE ?1 IS NULL OR julianday(updated_at) >= julianday(?1) ORDER BY id"
+        )
+        .bind(since)
+        .fetch(&self.pool)
+        .map_err(BreaError::from)
+    }
+
+    /// Properties created or changed at or after `since`, in id order.
+    /// `updated_at` doesn't move when a scrape finds a listing unchanged.
+    pub async fn list_properties_updated_since(&self, since: DateTime<Utc>) -> Result<Vec<Property>> {
+        self.stream_properties_updated_since(Some(since)).try_collect().await
+    }
+
+    /// Writes every property, or only those updated at or after `since`, to
+    /// `writer` as JSON Lines, one record at a time, with timestamps written
+    /// as `timestamps` says. With `with_history`, each record also carries
+    /// the property's `price_history` as [`PropertyWithHistory`] has it.
+    /// Returns the number of properties written.
+    pub async fn export_jsonl<W: Write>(
+        &self,
+        mut writer: W,
+        since: Option<DateTime<Utc>>,
+        timestamps: TimestampFormat,
+        with_history: bool,
+    ) -> Result<usize> {
+        let mut properties = self.stream_properties_updated_since(since);
+        let mut count = 0;
+        while let Some(property) = properties.try_next().await? {
+            let mut record = json_record(&property, timestamps)?;
+            if with_history {
+                record["price_history"] = price_history_record(&self.price_observations(property.id).await?, timestamps);
+            }
+            serde_json::to_writer(&mut writer, &record)?;
+            writer.write_all(b"\n")?;
+            count += 1;
+        }
+        writer.flush()?;
+        Ok(count)
+    }
+
+    pub async fn get_properties_by_type(&self, property_type: PropertyType) -> Result<Vec<Property>> {
         PropertyQueryBuilder::new()
-            .with_status(DbPropertyStatus::new(STATUS_SOLD))
+            .with_property_type(&property_type)
+            .order_by("id", true)?
             .execute(&self.pool)
             .await
     }
 
-    pub async fn get_removed_properties(&self) -> Result<Vec<Property>> {
-        PropertyQueryBuilder::new()
-            .with_status(DbPropertyStatus::new(STATUS_REMOVED))
+    /// Lists properties matching the given filters.
+    ///
+    /// `None` filters are ignored. `sort_by` must be one of
+    /// [`queries::SORTABLE_FIELDS`] and defaults to `id`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn list_properties(
+        &self,
+        property_type: Option<PropertyType>,
+        source: Option<&str>,
+        district: Option<&str>,
+        min_price: Option<f64>,
+        max_price: Option<f64>,
+        min_size: Option<f64>,
+        max_size: Option<f64>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+        sort_by: Option<&str>,
+        desc: bool,
+    ) -> Result<Vec<Property>> {
+        let mut query = PropertyQueryBuilder::new();
+        if let Some(property_type) = &property_type {
+            query = query.with_property_type(property_type);
+        }
+        if let Some(source) = source {
+            query = query.with_source(source);
+        }
+        if let Some(district) = district {
+            query = query.with_district(district);
+        }
+        query = query
+            .with_price_range(min_price, max_price)
+            .with_size_range(min_size, max_size)
+            .order_by(sort_by.unwrap_or("id"), desc)?;
+
+        // SQLite only accepts OFFSET after a LIMIT
+        if offset.is_some() && limit.is_none() {
+            query = query.with_limit(Some(-1));
+        } else {
+            query = query.with_limit(limit);
+        }
+
+        query
+            .with_offset(offset)
             .execute(&self.pool)
             .await
     }
 
-    pub async fn get_price_history(&self, property_id: i64) -> Result<Vec<(f64, DateTime<Utc>)>> {
-        let rows = sqlx::query(
-            "SELECT price_usd, observed_at FROM property_price_history WHERE property_id = ? ORDER BY observed_at DESC"
+    /// Number of properties matching `filters`, without fetching them.
+    pub async fn count_properties(&self, filters: PropertyQueryBuilder<'_>) -> Result<i64> {
+        filters.count(&self.pool).await
+    }
+
+    /// Properties no scrape has seen since `cutoff`, least recently seen first.
+    pub async fn get_properties_not_seen_since(&self, cutoff: DateTime<Utc>) -> Result<Vec<Property>> {
+        let properties = sqlx::query_as::<_, Property>(
+            "SELECT * FROM properties WHERE julianday(last_seen_at) < julianday(?) ORDER BY julianday(last_seen_at)"
         )
-        .bind(property_id)
+        .bind(cutoff.to_rfc3339())
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(rows
-            .into_iter()
-            .map(|row| Ok((row.get("price_usd"), row.get("observed_at"))))
-            .collect::<Result<Vec<_>>>()?)
+        Ok(properties)
     }
 
-    pub async fn save_property_image(&self, image: &mut PropertyImage) -> Result<()> {
+    pub async fn add_saved_search(&self, search: &mut SavedSearch) -> Result<()> {
         let id = sqlx::query(
             r#"
-            INSERT INTO property_images (
-                property_id, url, local_path, hash,
-                created_at, updated_at
-            ) VALUES (?, ?, ?, ?, ?, ?)
+            INSERT INTO saved_searches (
+                district, property_type, min_price, max_price,
+                min_size, max_size, created_at, last_checked_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
-        .bind(image.property_id)
-        .bind(&image.url)
-        .bind(&image.local_path)
-        .bind(&image.hash)
-        .bind(&image.created_at)
-        .bind(&image.updated_at)
+        .bind(&search.district)
+        .bind(&search.property_type)
+        .bind(search.min_price)
+        .bind(search.max_price)
+        .bind(search.min_size)
+        .bind(search.max_size)
+        .bind(&search.created_at)
+        .bind(&search.last_checked_at)
         .execute(&self.pool)
         .await?
         .last_insert_rowid();
 
-        image.id = id;
+        search.id = id;
         Ok(())
     }
 
-    pub async fn update_property_image(&self, image: &PropertyImage) -> Result<()> {
-        sqlx::query(
-            r#"
-            UPDATE property_images SET
-                property_id = ?,
-                url = ?,
-                local_path = ?,
-                hash = ?,
-                created_at = ?,
-                updated_at = ?
-            WHERE id = ?
-            "#,
-        )
-        .bind(image.property_id)
-        .bind(&image.url)
-        .bind(&image.local_path)
-        .bind(&image.hash)
-        .bind(&image.created_at)
-        .bind(&image.updated_at)
-        .bind(image.id)
+    pub async fn list_saved_searches(&self) -> Result<Vec<SavedSearch>> {
+        let searches = sqlx::query_as::<_, SavedSearch>("SELECT * FROM saved_searches ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(searches)
+    }
+
+    /// Records that matches up to `checked_at` were reported for every saved search.
+    pub async fn mark_saved_searches_checked(&self, checked_at: DbTimestamp) -> Result<()> {
+        sqlx::query("UPDATE saved_searches SET last_checked_at = ?")
+            .bind(checked_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Active listings for sale that match at least one saved search and
+    /// were created since that search was last checked, or since it was
+    /// saved if it never was. Saved searches don't name an operation and
+    /// `watch` scrapes sales, so rentals never match.
+    pub async fn matching_new_properties(&self) -> Result<Vec<Property>> {
+        let mut matches: Vec<Property> = Vec::new();
+        for search in self.list_saved_searches().await? {
+            let since = *search.last_checked_at.as_ref().unwrap_or(&search.created_at).inner();
+            let mut query = PropertyQueryBuilder::new()
+                .with_status_typed(PropertyStatus::Active)
+                .with_operation(Operation::Sale)
+                .with_created_after(since)
+                .with_price_range(search.min_price, search.max_price)
+                .with_size_range(search.min_size, search.max_size);
+            if let Some(district) = &search.district {
+                query = query.with_district(district);
+            }
+            if let Some(property_type) = &search.property_type {
+                query = query.with_property_type(property_type);
+            }
+
+            for property in query.execute(&self.pool).await? {
+                if !matches.iter().any(|p| p.id == property.id) {
+                    matches.push(property);
+                }
+            }
+        }
+
+        matches.sort_by_key(|p| p.id);
+        Ok(matches)
+    }
+
+    /// Tags a property, returning whether the tag is new to it. Tags are
+    /// free-form and trimmed; adding one the property already has does
+    /// nothing.
+    pub async fn add_tag(&self, property_id: i64, tag: &str) -> Result<bool> {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return Err(BreaError::InvalidArgument("tags can't be empty".to_string()));
+        }
+        let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM properties WHERE id = ?)")
+            .bind(property_id)
+            .fetch_one(&self.pool)
+            .await?;
+        if !exists {
+            return Err(BreaError::InvalidArgument(format!("no property with id {}", property_id)));
+        }
+
+        let result = sqlx::query("INSERT OR IGNORE INTO property_tags (property_id, tag) VALUES (?, ?)")
+            .bind(property_id)
+            .bind(tag)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Takes `tag` off a property, returning whether it had it.
+    pub async fn remove_tag(&self, property_id: i64, tag: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM property_tags WHERE property_id = ? AND tag = ?")
+            .bind(property_id)
+            .bind(tag.trim())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Replaces the notes of a property, or clears them with `None`. Saving
+    /// the listing again after a scrape leaves them alone.
+    pub async fn set_notes(&self, property_id: i64, notes: Option<&str>) -> Result<()> {
+        let result = sqlx::query("UPDATE properties SET notes = ? WHERE id = ?")
+            .bind(notes)
+            .bind(property_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(BreaError::InvalidArgument(format!("no property with id {}", property_id)));
+        }
+        Ok(())
+    }
+
+    /// Properties tagged `tag`, in the order they were stored.
+    pub async fn get_properties_by_tag(&self, tag: &str) -> Result<Vec<Property>> {
+        PropertyQueryBuilder::new()
+            .with_tag(tag)
+            .order_by("id", false)?
+            .execute(&self.pool)
+            .await
+    }
+
+    /// Properties that have not been geocoded yet, oldest first.
+    pub async fn get_properties_without_coordinates(&self, limit: Option<i64>) -> Result<Vec<Property>> {
+        let properties = sqlx::query_as::<_, Property>(
+            "SELECT * FROM properties WHERE latitude IS NULL OR longitude IS NULL ORDER BY id LIMIT ?"
+        )
+        .bind(limit.unwrap_or(-1))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(properties)
+    }
+
+    pub async fn set_coordinates(&self, property_id: i64, latitude: f64, longitude: f64) -> Result<()> {
+        sqlx::query("UPDATE properties SET latitude = ?, longitude = ? WHERE id = ?")
+            .bind(latitude)
+            .bind(longitude)
+            .bind(property_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_active_properties(&self) -> Result<Vec<Property>> {
+        PropertyQueryBuilder::new()
+            .with_status_typed(PropertyStatus::Active)
+            .execute(&self.pool)
+            .await
+    }
+
+    pub async fn get_sold_properties(&self) -> Result<Vec<Property>> {
+        PropertyQueryBuilder::new()
+            .with_status_typed(PropertyStatus::Sold)
+            .execute(&self.pool)
+            .await
+    }
+
+    pub async fn get_removed_properties(&self) -> Result<Vec<Property>> {
+        PropertyQueryBuilder::new()
+            .with_status_typed(PropertyStatus::Removed)
+            .execute(&self.pool)
+            .await
+    }
+
+    /// Computes a [`DbStats`] summary; an empty database yields zeroed stats.
+    pub async fn compute_stats(&self) -> Result<DbStats> {
+        let by_status: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT status, COUNT(*) FROM properties GROUP BY status ORDER BY status",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let by_property_type: Vec<(String, i64)> = sqlx::query_as(
+            r#"
+            SELECT COALESCE(property_type, 'unknown') AS property_type, COUNT(*)
+            FROM properties
+            GROUP BY 1
+            ORDER BY 1
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let prices: Vec<f64> = sqlx::query_scalar(
+            "SELECT price_usd FROM properties WHERE price_usd > 0 ORDER BY price_usd",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let average_covered_size: Option<f64> = sqlx::query_scalar(
+            "SELECT AVG(covered_size) FROM properties WHERE covered_size > 0",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let price_per_m2_by_district: Vec<(String, f64)> = sqlx::query_as(
+            r#"
+            SELECT district, AVG(price_usd / covered_size)
+            FROM properties
+            WHERE price_usd > 0 AND covered_size > 0
+            GROUP BY district
+            ORDER BY district
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let median_price_usd = median(&prices).unwrap_or(0.0);
+        let average_price_usd = if prices.is_empty() {
+            0.0
+        } else {
+            prices.iter().sum::<f64>() / prices.len() as f64
+        };
+
+        Ok(DbStats {
+            total: by_status.iter().map(|(_, count)| count).sum(),
+            by_status,
+            by_property_type,
+            median_price_usd,
+            average_price_usd,
+            average_covered_size: average_covered_size.unwrap_or(0.0),
+            price_per_m2_by_district,
+        })
+    }
+
+    /// Computes [`PriceStats`] over the listings of `property_type` in
+    /// `district`, whatever their status. No matching listing yields a zero
+    /// count and no figures.
+    pub async fn district_type_stats(&self, district: &str, property_type: &PropertyType) -> Result<PriceStats> {
+        let district = normalize_district(district);
+        let price_usd = self.distribution("price_usd", "price_usd > 0", &district, property_type).await?;
+        let price_per_m2 = self
+            .distribution("price_usd / covered_size", "price_usd > 0 AND covered_size > 0", &district, property_type)
+            .await?;
+
+        Ok(PriceStats {
+            count: price_usd.as_ref().map_or(0, |(count, _)| *count),
+            price_usd: price_usd.map(|(_, distribution)| distribution),
+            price_per_m2: price_per_m2.map(|(_, distribution)| distribution),
+        })
+    }
+
+    /// Count and spread of `value` over the listings of one district and
+    /// property type matching `condition`. Both are fixed SQL expressions.
+    async fn distribution(
+        &self,
+        value: &str,
+        condition: &str,
+        district: &str,
+        property_type: &PropertyType,
+    ) -> Result<Option<(i64, Distribution)>> {
+        let filter = format!(
+            "FROM properties WHERE district = ? AND LOWER(property_type) = ? AND {}",
+            condition
+        );
+        let (count, min, max, mean): (i64, Option<f64>, Option<f64>, Option<f64>) =
+            sqlx::query_as(&format!("SELECT COUNT(*), MIN({0}), MAX({0}), AVG({0}) {1}", value, filter))
+                .bind(district)
+                .bind(property_type)
+                .fetch_one(&self.pool)
+                .await?;
+        let values: Vec<f64> = sqlx::query_scalar(&format!("SELECT {0} {1} ORDER BY {0}", value, filter))
+            .bind(district)
+            .bind(property_type)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(match (min, max, mean, median(&values)) {
+            (Some(min), Some(max), Some(mean), Some(median)) => Some((count, Distribution { min, max, mean, median })),
+            _ => None,
+        })
+    }
+
+    pub async fn get_price_history(&self, property_id: i64) -> Result<Vec<(f64, DateTime<Utc>)>> {
+        let rows = sqlx::query(
+            "SELECT price_usd, observed_at FROM property_price_history WHERE property_id = ? ORDER BY observed_at DESC, id DESC"
+        )
+        .bind(property_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Ok((row.get("price_usd"), row.get("observed_at"))))
+            .collect::<Result<Vec<_>>>()?)
+    }
+
+    /// The property `id` with its whole price history, oldest price first.
+    pub async fn get_property_with_history(&self, id: i64) -> Result<Option<PropertyWithHistory>> {
+        let Some(property) = self.get_property(id).await? else {
+            return Ok(None);
+        };
+        let price_history = self.price_observations(id).await?;
+        Ok(Some(PropertyWithHistory { property, price_history }))
+    }
+
+    /// Price history of `property_id`, oldest price first.
+    async fn price_observations(&self, property_id: i64) -> Result<Vec<PriceObservation>> {
+        let mut history: Vec<PriceObservation> = self
+            .get_price_history(property_id)
+            .await?
+            .into_iter()
+            .map(|(price_usd, observed_at)| PriceObservation { price_usd, observed_at })
+            .collect();
+        history.reverse();
+        Ok(history)
+    }
+
+    /// Prices of a property observed between `from` and `to`, both included,
+    /// oldest first as graphs read them.
+    pub async fn get_price_history_range(
+        &self,
+        property_id: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<(f64, DateTime<Utc>)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT price_usd, observed_at FROM property_price_history
+            WHERE property_id = ? AND julianday(observed_at) BETWEEN julianday(?) AND julianday(?)
+            ORDER BY julianday(observed_at), id
+            "#
+        )
+        .bind(property_id)
+        .bind(from.to_rfc3339())
+        .bind(to.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get("price_usd"), row.get("observed_at")))
+            .collect())
+    }
+
+    /// Properties whose latest price is at least `min_pct` percent below a
+    /// higher price recorded since `since`, with the drop in percent, largest
+    /// drop first.
+    ///
+    /// Only history observed since `since` is looked at, so a property needs
+    /// two observations in that window to show up.
+    pub async fn price_drops(&self, since: DateTime<Utc>, min_pct: f64) -> Result<Vec<(Property, f64)>> {
+        let rows: Vec<(i64, f64)> = sqlx::query_as(
+            r#"
+            SELECT property_id, price_usd FROM property_price_history
+            WHERE julianday(observed_at) >= julianday(?)
+            ORDER BY property_id, julianday(observed_at), id
+            "#
+        )
+        .bind(since.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut drops = Vec::new();
+        for history in rows.chunk_by(|a, b| a.0 == b.0) {
+            let Some(((property_id, latest), earlier)) = history.split_last() else {
+                continue;
+            };
+            let highest = earlier.iter().map(|(_, price)| *price).fold(f64::NEG_INFINITY, f64::max);
+            if highest <= 0.0 || *latest >= highest {
+                continue;
+            }
+            let drop_pct = (highest - latest) / highest * 100.0;
+            if drop_pct >= min_pct {
+                if let Some(property) = self.get_property(*property_id).await? {
+                    drops.push((property, drop_pct));
+                }
+            }
+        }
+
+        drops.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(drops)
+    }
+
+    /// Stores a property photo. The first photo saved for a property becomes
+    /// its primary one; saving another with `is_primary` set takes the flag
+    /// over from the previous primary photo.
+    pub async fn save_property_image(&self, image: &mut PropertyImage) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let has_primary: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM property_images WHERE property_id = ? AND is_primary = 1)"
+        )
+        .bind(image.property_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if image.is_primary && has_primary {
+            sqlx::query("UPDATE property_images SET is_primary = 0 WHERE property_id = ?")
+                .bind(image.property_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        image.is_primary = image.is_primary || !has_primary;
+
+        let id = sqlx::query(
+            r#"
+            INSERT INTO property_images (
+                property_id, url, local_path, hash, phash, is_primary,
+                created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(image.property_id)
+        .bind(&image.url)
+        .bind(&image.local_path)
+        .bind(&image.hash)
+        .bind(&image.phash)
+        .bind(image.is_primary)
+        .bind(&image.created_at)
+        .bind(&image.updated_at)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        tx.commit().await?;
+
+        image.id = id;
+        Ok(())
+    }
+
+    /// Makes `image_id` the primary photo of `property_id`, clearing the
+    /// flag on its other photos.
+    pub async fn set_primary_image(&self, property_id: i64, image_id: i64) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let belongs: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM property_images WHERE id = ? AND property_id = ?)"
+        )
+        .bind(image_id)
+        .bind(property_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if !belongs {
+            return Err(BreaError::InvalidArgument(format!(
+                "image {} does not belong to property {}",
+                image_id, property_id
+            )));
+        }
+
+        // Clear first so the unique index never sees two primary photos
+        sqlx::query("UPDATE property_images SET is_primary = 0 WHERE property_id = ? AND id != ?")
+            .bind(property_id)
+            .bind(image_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE property_images SET is_primary = 1 WHERE id = ?")
+            .bind(image_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Updates a stored photo. `is_primary` is left alone, use
+    /// [`Database::set_primary_image`] to change it.
+    pub async fn update_property_image(&self, image: &PropertyImage) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE property_images SET
+                property_id = ?,
+                url = ?,
+                local_path = ?,
+                hash = ?,
+                phash = ?,
+                created_at = ?,
+                updated_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(image.property_id)
+        .bind(&image.url)
+        .bind(&image.local_path)
+        .bind(&image.hash)
+        .bind(&image.phash)
+        .bind(&image.created_at)
+        .bind(&image.updated_at)
+        .bind(image.id)
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
+    /// Whether an image with this content hash was already stored, for any property.
+    pub async fn image_exists_by_hash(&self, hash: &[u8]) -> Result<bool> {
+        let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM property_images WHERE hash = ?)")
+            .bind(hash)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(exists)
+    }
+
+    /// Properties with a photo whose perceptual hash is within
+    /// `max_distance` bits of `phash`.
+    ///
+    /// Relisted properties usually come back under a new external id with
+    /// the same photos, recompressed or resized, which this still matches.
+    pub async fn find_properties_with_similar_images(&self, phash: u64, max_distance: u32) -> Result<Vec<Property>> {
+        let rows = sqlx::query("SELECT property_id, phash FROM property_images WHERE phash IS NOT NULL")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut property_ids = Vec::new();
+        for row in rows {
+            let candidate: Vec<u8> = row.get("phash");
+            let Ok(candidate) = <[u8; 8]>::try_from(candidate.as_slice()) else {
+                continue;
+            };
+            let property_id: i64 = row.get("property_id");
+            if hamming_distance(phash, u64::from_be_bytes(candidate)) <= max_distance
+                && !property_ids.contains(&property_id)
+            {
+                property_ids.push(property_id);
+            }
+        }
+
+        let mut properties = Vec::with_capacity(property_ids.len());
+        for id in property_ids {
+            if let Some(property) = self.get_property(id).await? {
+                properties.push(property);
+            }
+        }
+        Ok(properties)
+    }
+
+    /// Groups of active properties that look like one listing posted more
+    /// than once, under different external ids or sources.
+    ///
+    /// Listings are grouped when they have the same price and either the
+    /// same address and rooms or the same title, ignoring case, punctuation
+    /// and spacing. Each group is ordered oldest first.
+    pub async fn find_duplicate_candidates(&self) -> Result<Vec<Vec<Property>>> {
+        let properties = self.get_active_properties().await?;
+
+        // Union-find over indices into `properties`
+        let mut parent: Vec<usize> = (0..properties.len()).collect();
+        let mut by_address: HashMap<(String, i64, Option<i32>), usize> = HashMap::new();
+        let mut by_title: HashMap<(String, i64), usize> = HashMap::new();
+        for (i, property) in properties.iter().enumerate() {
+            let price = property.price_usd.round() as i64;
+            let address = normalize_text(&property.address);
+            let mut matches = Vec::new();
+            if !address.is_empty() {
+                match by_address.entry((address, price, property.rooms)) {
+                    Entry::Occupied(entry) => matches.push(*entry.get()),
+                    Entry::Vacant(entry) => {
+                        entry.insert(i);
+                    }
+                }
+            }
+            let title = normalize_text(&property.title);
+            if !title.is_empty() {
+                match by_title.entry((title, price)) {
+                    Entry::Occupied(entry) => matches.push(*entry.get()),
+                    Entry::Vacant(entry) => {
+                        entry.insert(i);
+                    }
+                }
+            }
+            for j in matches {
+                let (a, b) = (find_root(&mut parent, i), find_root(&mut parent, j));
+                parent[a] = b;
+            }
+        }
+
+        let mut groups: HashMap<usize, Vec<Property>> = HashMap::new();
+        for (i, property) in properties.into_iter().enumerate() {
+            let root = find_root(&mut parent, i);
+            groups.entry(root).or_default().push(property);
+        }
+
+        let mut groups: Vec<Vec<Property>> = groups.into_values().filter(|group| group.len() > 1).collect();
+        for group in groups.iter_mut() {
+            group.sort_by(|a, b| {
+                a.created_at.inner().cmp(b.created_at.inner()).then(a.id.cmp(&b.id))
+            });
+        }
+        groups.sort_by_key(|group| group[0].id);
+        Ok(groups)
+    }
+
     pub async fn get_property_images(&self, property_id: i64) -> Result<Vec<PropertyImage>> {
         PropertyImageQueryBuilder::new()
             .with_property_id(property_id)
@@ -293,14 +1618,196 @@ impl Database {
         Ok(image)
     }
 
-    pub async fn detect_sold_properties(&self, current_external_ids: &[&str]) -> Result<Vec<Property>> {
+    /// Active `source` listings of `property_type` for `operation` in
+    /// `district` missing from `seen`, the same listings a scrape of that
+    /// search would have returned. Listings outside it are never returned,
+    /// since a scrape only knows about what it searched for, and keys of
+    /// other sources in `seen` are ignored.
+    pub async fn detect_sold_properties(
+        &self,
+        seen: &[PropertyKey],
+        source: &str,
+        district: &str,
+        property_type: &PropertyType,
+        operation: Operation,
+    ) -> Result<Vec<Property>> {
+        let seen_ids: Vec<&str> = seen
+            .iter()
+            .filter(|key| key.source == source)
+            .map(|key| key.external_id.as_str())
+            .collect();
         PropertyQueryBuilder::new()
             .with_status(DbPropertyStatus::new(STATUS_ACTIVE))
-            .with_external_ids_not_in(current_external_ids)
+            .with_source(source)
+            .with_district(district)
+            .with_property_type(property_type)
+            .with_operation(operation)
+            .with_external_ids_not_in(&seen_ids)
             .execute(&self.pool)
             .await
     }
 
+    /// Updates the status of `source`'s `property_type` listings for
+    /// `operation` in `district` missing from the latest scrape of them.
+    /// Listings outside that search are left alone, as the scrape says
+    /// nothing about them.
+    ///
+    /// A listing missing for less than `grace_period_days` (by its
+    /// `last_seen_at`) stays active, as ArgenProp listings come and go
+    /// between pages. Past that it is considered sold, and once missing for
+    /// `removed_after_days` or more it is marked removed.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn reconcile_listing_status(
+        &self,
+        current_external_ids: &[&str],
+        source: &str,
+        district: &str,
+        property_type: &PropertyType,
+        operation: Operation,
+        grace_period_days: i64,
+        removed_after_days: i64,
+    ) -> Result<StatusChanges> {
+        if removed_after_days < grace_period_days {
+            return Err(BreaError::InvalidArgument(format!(
+                "removed_after_days ({}) must not be less than grace_period_days ({})",
+                removed_after_days, grace_period_days
+            )));
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "SELECT id, status, last_seen_at FROM properties WHERE source = "
+        );
+        query.push_bind(source);
+        query.push(" AND district = ");
+        query.push_bind(normalize_district(district));
+        query.push(" AND LOWER(property_type) = ");
+        query.push_bind(property_type.clone());
+        query.push(" AND operation = ");
+        query.push_bind(operation);
+        query.push(" AND status IN (");
+        query.push_bind(STATUS_ACTIVE);
+        query.push(", ");
+        query.push_bind(STATUS_SOLD);
+        query.push(")");
+        if !current_external_ids.is_empty() {
+            query.push(" AND external_id NOT IN (");
+            let mut ids = query.separated(", ");
+            for id in current_external_ids {
+                ids.push_bind(*id);
+            }
+            query.push(")");
+        }
+        let missing = query.build().fetch_all(&self.pool).await?;
+
+        let now = Utc::now();
+        let mut changes = StatusChanges::default();
+        for row in missing {
+            let id: i64 = row.get("id");
+            let status: DbPropertyStatus = row.get("status");
+            let last_seen_at: DbTimestamp = row.get("last_seen_at");
+            let missing_for = now.signed_duration_since(*last_seen_at.inner());
+
+            if missing_for >= chrono::Duration::days(removed_after_days) {
+                changes.removed.push(id);
+            } else if missing_for >= chrono::Duration::days(grace_period_days)
+                && status.as_str() == STATUS_ACTIVE
+            {
+                changes.sold.push(id);
+            }
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let updated_at = DbTimestamp::now();
+        Self::update_status(&mut tx, &changes.sold, PropertyStatus::Sold, &updated_at).await?;
+        Self::update_status(&mut tx, &changes.removed, PropertyStatus::Removed, &updated_at).await?;
+        tx.commit().await?;
+
+        Ok(changes)
+    }
+
+    /// Compares a fresh scrape of `source`'s `property_type` listings for
+    /// `operation` in `district`, already saved and started at `since`, with
+    /// the stored state it replaced.
+    ///
+    /// Active listings missing from `other_external_ids` are removed. Of the
+    /// others, those whose first price was recorded since `since` are added,
+    /// and those whose two most recent prices differ, the latest recorded
+    /// since `since`, changed price.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn diff_against(
+        &self,
+        other_external_ids: &[&str],
+        source: &str,
+        district: &str,
+        property_type: &PropertyType,
+        operation: Operation,
+        since: DateTime<Utc>,
+    ) -> Result<ScrapeDiff> {
+        let stored = PropertyQueryBuilder::new()
+            .with_status_typed(PropertyStatus::Active)
+            .with_source(source)
+            .with_district(district)
+            .with_property_type(property_type)
+            .with_operation(operation)
+            .execute(&self.pool)
+            .await?;
+        let seen: HashSet<&str> = other_external_ids.iter().copied().collect();
+
+        let mut diff = ScrapeDiff::default();
+        for property in stored {
+            if !seen.contains(property.external_id.as_str()) {
+                diff.removed.push(property);
+                continue;
+            }
+            // Newest first
+            let history = self.get_price_history(property.id).await?;
+            match history.as_slice() {
+                [(_, first_seen)] if *first_seen >= since => diff.added.push(property),
+                [(new_price, observed_at), (old_price, _), ..] if *observed_at >= since && new_price != old_price => {
+                    let (old_price, new_price) = (*old_price, *new_price);
+                    diff.price_changed.push((property, old_price, new_price));
+                }
+                _ => {}
+            }
+        }
+        Ok(diff)
+    }
+
+    /// Sets the status of every property in `ids` with a single statement.
+    /// An empty slice changes nothing.
+    pub async fn mark_properties_status(&self, ids: &[i64], status: PropertyStatus) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let mut tx = self.pool.begin().await?;
+        Self::update_status(&mut tx, ids, status, &DbTimestamp::now()).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn update_status(
+        tx: &mut sqlx::Transaction<'_, Sqlite>,
+        ids: &[i64],
+        status: PropertyStatus,
+        updated_at: &DbTimestamp,
+    ) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let mut query = QueryBuilder::<Sqlite>::new("UPDATE properties SET status = ");
+        query.push_bind(status);
+        query.push(", updated_at = ");
+        query.push_bind(updated_at);
+        query.push(" WHERE id IN (");
+        let mut separated = query.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+        query.push(")");
+        query.build().execute(&mut **tx).await?;
+        Ok(())
+    }
+
     pub async fn mark_property_as_sold(&self, property_id: i64) -> Result<()> {
         sqlx::query(
             "UPDATE properties SET status = ?, updated_at = ? WHERE id = ?"
@@ -327,337 +1834,2800 @@ impl Database {
         Ok(())
     }
 
-    pub async fn cleanup_price_history(&self) -> Result<usize> {
-        let result = sqlx::query(
-            r#"
-            DELETE FROM property_price_history
-            WHERE id NOT IN (
-                SELECT id FROM (
+    /// Fixes what `options` asks for and reports it.
+    ///
+    /// Duplicates are properties of the same source and operation with the
+    /// same listing URL, as `save_property` would have matched them, which
+    /// rows stored before URLs were canonicalized can still be. Each is merged
+    /// into the one with the lowest id: its photos, price history and tags
+    /// move over, except for those the kept property already has, and the
+    /// duplicate row is deleted. Everything happens in one transaction.
+    pub async fn repair_integrity(&self, options: RepairOptions) -> Result<RepairReport> {
+        let mut tx = self.pool.begin().await?;
+        let mut report = RepairReport::default();
+
+        if options.orphaned_images {
+            report.orphaned_images = sqlx::query_scalar(
+                "SELECT id FROM property_images WHERE property_id NOT IN (SELECT id FROM properties) ORDER BY id"
+            )
+            .fetch_all(&mut *tx)
+            .await?;
+        }
+        if options.duplicates {
+            report.merged_duplicates = sqlx::query_as(
+                r#"
+                SELECT kept, id FROM (
+                    SELECT id, MIN(id) OVER (
+                        PARTITION BY source, operation, COALESCE(canonical_url, url)
+                    ) AS kept
+                    FROM properties
+                )
+                WHERE id != kept
+                ORDER BY id
+                "#
+            )
+            .fetch_all(&mut *tx)
+            .await?;
+        }
+        if options.dry_run {
+            return Ok(report);
+        }
+
+        for id in &report.orphaned_images {
+            sqlx::query("DELETE FROM property_images WHERE id = ?").bind(id).execute(&mut *tx).await?;
+        }
+
+        let has_search_index: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'properties_fts')"
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        for (kept, merged) in &report.merged_duplicates {
+            // Only one photo per property can be primary
+            sqlx::query(
+                r#"
+                UPDATE property_images SET is_primary = 0
+                WHERE property_id = ?
+                AND EXISTS(SELECT 1 FROM property_images WHERE property_id = ? AND is_primary = 1)
+                "#
+            )
+            .bind(merged)
+            .bind(kept)
+            .execute(&mut *tx)
+            .await?;
+            // Rows the kept property already has stay behind and go with the
+            // duplicate
+            for table in ["property_images", "property_price_history", "property_tags"] {
+                sqlx::query(&format!("UPDATE OR IGNORE {} SET property_id = ? WHERE property_id = ?", table))
+                    .bind(kept)
+                    .bind(merged)
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query(&format!("DELETE FROM {} WHERE property_id = ?", table))
+                    .bind(merged)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            if has_search_index {
+                sqlx::query("DELETE FROM properties_fts WHERE rowid = ?").bind(merged).execute(&mut *tx).await?;
+            }
+            sqlx::query("DELETE FROM properties WHERE id = ?").bind(merged).execute(&mut *tx).await?;
+        }
+
+        tx.commit().await?;
+        Ok(report)
+    }
+
+    /// Brings in the listings of the database file at `other`, e.g. to
+    /// combine scrapes run on two machines, and reports what changed.
+    ///
+    /// Each database numbers its rows on its own, so listings are matched
+    /// as a scrape matches them, on their key, source and external id (with
+    /// the operation), or else on their page's URL, never on id. A listing only `other` has is inserted with its notes and tags. A
+    /// listing both have takes the scraped details of `other` if it was seen
+    /// there more recently, and is skipped otherwise; either way it gets the
+    /// notes, coordinates and tags it lacks from `other`. Price histories are
+    /// merged, leaving out observations already stored for the same instant.
+    /// Photos aren't merged, as their files live on the other machine.
+    ///
+    /// `other` must be migrated to the current schema. Everything happens in
+    /// one transaction.
+    pub async fn merge_from(&self, other: &Path) -> Result<MergeReport> {
+        // ATTACH would create an empty database instead
+        if !other.is_file() {
+            return Err(BreaError::InvalidArgument(format!("no database at {}", other.display())));
+        }
+
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query("ATTACH DATABASE ? AS merged")
+            .bind(other.to_string_lossy().into_owned())
+            .execute(&mut *conn)
+            .await?;
+        let report = Self::merge_attached(&mut conn, other).await;
+        // The connection goes back to the pool, without the other database
+        sqlx::query("DETACH DATABASE merged").execute(&mut *conn).await?;
+        report
+    }
+
+    async fn merge_attached(conn: &mut SqliteConnection, other: &Path) -> Result<MergeReport> {
+        let latest = migrations::MIGRATIONS.last().map_or(0, |migration| migration.version());
+        let has_migrations: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM merged.sqlite_master WHERE type = 'table' AND name = 'migrations')"
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        let version: Option<i32> = if has_migrations {
+            sqlx::query_scalar("SELECT MAX(version) FROM merged.migrations").fetch_one(&mut *conn).await?
+        } else {
+            None
+        };
+        if version.is_none_or(|version| version < latest) {
+            return Err(BreaError::Schema(format!(
+                "{} is not up to date, run `brea database -a up -d {}` on it first",
+                other.display(),
+                other.display()
+            )));
+        }
+
+        let mut tx = conn.begin().await?;
+        let mut report = MergeReport::default();
+        let incoming = sqlx::query_as::<_, Property>("SELECT * FROM merged.properties ORDER BY id")
+            .fetch_all(&mut *tx)
+            .await?;
+        for mut property in incoming {
+            let other_id = property.id;
+            let (notes, latitude, longitude) = (property.notes.clone(), property.latitude, property.longitude);
+            // Matched the way a scrape is, so a listing stored here under
+            // another key but with the same page is merged into it
+            let existing = Self::find_existing(&mut *tx, &property).await?;
+
+            let id = match existing {
+                None => {
+                    property.id = Self::insert_row(&mut *tx, &property).await?;
+                    Self::index_for_search(&mut *tx, &property).await?;
+                    report.inserted += 1;
+                    property.id
+                }
+                Some(existing) => {
+                    if property.last_seen_at.inner() > existing.last_seen_at.inner() {
+                        property.keep_user_fields(&existing);
+                        Self::update_row(&mut *tx, &property).await?;
+                        Self::index_for_search(&mut *tx, &property).await?;
+                        report.updated += 1;
+                    } else {
+                        report.skipped += 1;
+                    }
+                    sqlx::query(
+                        r#"
+                        UPDATE main.properties SET
+                            notes = COALESCE(notes, ?),
+                            latitude = COALESCE(latitude, ?),
+                            longitude = COALESCE(longitude, ?)
+                        WHERE id = ?
+                        "#
+                    )
+                    .bind(notes)
+                    .bind(latitude)
+                    .bind(longitude)
+                    .bind(existing.id)
+                    .execute(&mut *tx)
+                    .await?;
+                    existing.id
+                }
+            };
+
+            sqlx::query(
+                "INSERT OR IGNORE INTO main.property_tags (property_id, tag) SELECT ?, tag FROM merged.property_tags WHERE property_id = ?"
+            )
+            .bind(id)
+            .bind(other_id)
+            .execute(&mut *tx)
+            .await?;
+            // Timestamps may be written with another offset or precision
+            // on the other machine, so instants are compared
+            let history = sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO main.property_price_history (property_id, price_usd, observed_at)
+                SELECT ?, price_usd, observed_at FROM merged.property_price_history AS incoming
+                WHERE property_id = ?
+                AND NOT EXISTS (
+                    SELECT 1 FROM main.property_price_history
+                    WHERE property_id = ? AND julianday(observed_at) = julianday(incoming.observed_at)
+                )
+                "#
+            )
+            .bind(id)
+            .bind(other_id)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+            report.price_history += history.rows_affected() as usize;
+        }
+
+        tx.commit().await?;
+        Ok(report)
+    }
+
+    /// Trims each property's price history down to its `keep` most recent
+    /// entries and returns how many rows were deleted.
+    ///
+    /// The latest observation is what the current price is compared against,
+    /// so it is always kept: `keep == 0` behaves like `keep == 1`.
+    pub async fn cleanup_price_history(&self, keep: usize) -> Result<usize> {
+        let keep = keep.max(1) as i64;
+        let result = sqlx::query(
+            r#"
+            DELETE FROM property_price_history
+            WHERE id NOT IN (
+                SELECT id FROM (
                     SELECT id, ROW_NUMBER() OVER (
                         PARTITION BY property_id
-                        ORDER BY observed_at DESC
+                        ORDER BY observed_at DESC, id DESC
                     ) as rn
                     FROM property_price_history
-                ) WHERE rn <= 10
+                ) WHERE rn <= ?
             )
             "#
         )
+        .bind(keep)
         .execute(&self.pool)
         .await?;
 
         Ok(result.rows_affected() as usize)
     }
 
+    /// Sets the `price_usd` of every stored property to what `reprocessor`
+    /// makes of it, e.g. after fixing a price parsing bug, and returns the
+    /// prices that changed.
+    ///
+    /// Properties `reprocessor` returns `None` for are left alone. Each
+    /// corrected price is recorded in the history as observed now, so the
+    /// wrong one stays visible as what was stored before.
+    pub async fn reprocess_prices(&self, reprocessor: impl Fn(&Property) -> Option<f64>) -> Result<Vec<PriceChange>> {
+        let observed_at = DbTimestamp::now();
+        let changes: Vec<PriceChange> = self
+            .get_properties()
+            .await?
+            .iter()
+            .filter_map(|property| {
+                let new_price = reprocessor(property)?;
+                (new_price != property.price_usd).then(|| PriceChange {
+                    property_id: property.id,
+                    old_price: property.price_usd,
+                    new_price,
+                    observed_at: observed_at.clone(),
+                })
+            })
+            .collect();
+
+        let mut tx = self.pool.begin().await?;
+        for change in &changes {
+            sqlx::query("UPDATE properties SET price_usd = ?, updated_at = ? WHERE id = ?")
+                .bind(change.new_price)
+                .bind(&observed_at)
+                .bind(change.property_id)
+                .execute(&mut *tx)
+                .await?;
+            Self::insert_price_history(&mut *tx, change.property_id, change.new_price, &observed_at).await?;
+        }
+        tx.commit().await?;
+        Ok(changes)
+    }
+
+    /// Recomputes `price_usd` from each property's stored `raw_price` and
+    /// currency with the current exchange rates, see
+    /// [`Database::reprocess_prices`]. Properties priced in a currency there
+    /// is no rate for are left alone.
+    pub async fn reprocess_prices_from_raw(&self) -> Result<Vec<PriceChange>> {
+        let now = Utc::now();
+        let mut rates = HashMap::new();
+        for currency in [Currency::Usd, Currency::Ars] {
+            if let Ok(rate) = self.rates.usd_rate(currency, now).await {
+                rates.insert(currency, rate);
+            }
+        }
+        self.reprocess_prices(|property| rates.get(&property.currency).map(|rate| property.raw_price * rate))
+            .await
+    }
+
+    /// Rebuilds the database file, reclaiming the pages freed by deletes.
+    ///
+    /// `VACUUM` can't run inside a transaction, so it goes straight to the
+    /// pool rather than through any transaction-taking helper. In WAL mode
+    /// the rebuilt pages land in the log first, hence the checkpoint.
+    pub async fn vacuum(&self) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query("VACUUM").execute(&mut *conn).await?;
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(&mut *conn).await?;
+        Ok(())
+    }
+
+    /// Refreshes the statistics SQLite's query planner relies on.
+    pub async fn analyze(&self) -> Result<()> {
+        sqlx::query("ANALYZE").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Records `price_usd` as observed at `observed_at`.
+    ///
+    /// Timestamps keep their sub-second precision, and two different prices
+    /// at the very same instant are both kept, the later-recorded one counting
+    /// as the newer. Recording the same price at the same instant again, as a
+    /// retried save does, is a no-op.
     async fn record_price_history(&self, property_id: i64, price_usd: f64, observed_at: DbTimestamp) -> Result<()> {
+        Self::insert_price_history(&self.pool, property_id, price_usd, &observed_at).await
+    }
+
+    async fn insert_price_history<'e, E>(executor: E, property_id: i64, price_usd: f64, observed_at: &DbTimestamp) -> Result<()>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
         sqlx::query(
             r#"
             INSERT INTO property_price_history (property_id, price_usd, observed_at)
             VALUES (?, ?, ?)
-            ON CONFLICT(property_id, observed_at) DO NOTHING
+            ON CONFLICT(property_id, observed_at, price_usd) DO NOTHING
             "#
         )
         .bind(property_id)
         .bind(price_usd)
-        .bind(&observed_at)
-        .execute(&self.pool)
+        .bind(observed_at)
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The newest price in the history of `property_id`, if it has any.
+    async fn last_recorded_price<'e, E>(executor: E, property_id: i64) -> Result<Option<f64>>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        let price = sqlx::query_scalar(
+            "SELECT price_usd FROM property_price_history WHERE property_id = ? ORDER BY observed_at DESC, id DESC LIMIT 1"
+        )
+        .bind(property_id)
+        .fetch_optional(executor)
         .await?;
 
-        Ok(())
-    }
-}
+        Ok(price)
+    }
+}
+
+/// Lowercases `text` and keeps only its letters and digits, one space
+/// between words, so "Av. Santa Fe 1234" and "av santa fe  1234" compare equal.
+fn normalize_text(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn find_root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Currency;
+    use chrono::Utc;
+
+    async fn test_connection() -> Database {
+        let mut db = Database::new_in_memory_shared().await.unwrap();
+        db.rates = Arc::new(StaticRateProvider::new(1000.0));
+        apply_migrations(&db.pool).await.unwrap();
+        db
+    }
+
+    fn test_property(external_id: &str) -> Property {
+        let now = DbTimestamp::now();
+        Property {
+            id: 0,
+            external_id: external_id.to_string(),
+            source: "test".to_string(),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::Apartment),
+            district: "Test District".to_string(),
+            title: "Test Property".to_string(),
+            description: Some("Test description".to_string()),
+            price_usd: 100000.0,
+            raw_price: 100000.0,
+            currency: Currency::Usd,
+            address: "123 Test St".to_string(),
+            latitude: None,
+            longitude: None,
+            covered_size: Some(100.0),
+            rooms: Some(2),
+            antiquity: Some(5),
+            under_construction: None,
+            bathrooms: None,
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
+            url: format!("https://example.com/{}", external_id),
+            status: DbPropertyStatus::new(STATUS_ACTIVE),
+            created_at: now.clone(),
+            updated_at: now,
+            last_seen_at: DbTimestamp::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_database_creation() {
+        let _db = test_connection().await;
+    }
+
+    #[tokio::test]
+    async fn test_property_crud() {
+        let db = test_connection().await;
+        let now = DbTimestamp::now();
+
+        // Create a property
+        let mut property = Property {
+            id: 0,
+            external_id: "test-123".to_string(),
+            source: "test".to_string(),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::Apartment),
+            district: "Test District".to_string(),
+            title: "Test Property".to_string(),
+            description: Some("Test description".to_string()),
+            price_usd: 100000.0,
+            raw_price: 100000.0,
+            currency: Currency::Usd,
+            address: "123 Test St".to_string(),
+            latitude: None,
+            longitude: None,
+            covered_size: Some(100.0),
+            rooms: Some(2),
+            antiquity: Some(5),
+            under_construction: None,
+            bathrooms: None,
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
+            url: "https://example.com/test".to_string(),
+            status: DbPropertyStatus::new(STATUS_ACTIVE),
+            created_at: now.clone(),
+            updated_at: now,
+            last_seen_at: DbTimestamp::now(),
+        };
+
+        // Save property
+        db.save_property(&mut property).await.unwrap();
+        assert!(property.id > 0);
+
+        // Get property
+        let retrieved = db.get_property(property.id).await.unwrap().unwrap();
+        assert_eq!(retrieved.external_id, "test-123");
+        assert_eq!(retrieved.price_usd, 100000.0);
+
+        // Update property
+        property.price_usd = 150000.0;
+        property.updated_at = DbTimestamp::now();
+        db.update_property(&property).await.unwrap();
+
+        // Verify update
+        let updated = db.get_property(property.id).await.unwrap().unwrap();
+        assert_eq!(updated.price_usd, 150000.0);
+    }
+
+    #[tokio::test]
+    async fn test_property_image_crud() {
+        let db = test_connection().await;
+        let now = DbTimestamp::now();
+
+        // Create a property first
+        let mut property = Property {
+            id: 0,
+            external_id: "test-123".to_string(),
+            source: "test".to_string(),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::Apartment),
+            district: "Test District".to_string(),
+            title: "Test Property".to_string(),
+            description: Some("Test description".to_string()),
+            price_usd: 100000.0,
+            raw_price: 100000.0,
+            currency: Currency::Usd,
+            address: "123 Test St".to_string(),
+            latitude: None,
+            longitude: None,
+            covered_size: Some(100.0),
+            rooms: Some(2),
+            antiquity: Some(5),
+            under_construction: None,
+            bathrooms: None,
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
+            url: "https://example.com/test".to_string(),
+            status: DbPropertyStatus::new(STATUS_ACTIVE),
+            created_at: now.clone(),
+            updated_at: now.clone(),
+            last_seen_at: DbTimestamp::now(),
+        };
+
+        db.save_property(&mut property).await.unwrap();
+
+        // Create an image
+        let mut image = PropertyImage {
+            id: 0,
+            property_id: property.id,
+            url: "https://example.com/image.jpg".to_string(),
+            local_path: "/tmp/images/test.jpg".to_string(),
+            hash: vec![1, 2, 3, 4],
+            phash: None,
+            is_primary: false,
+            created_at: now.clone(),
+            updated_at: now,
+        };
+
+        // Save image
+        db.save_property_image(&mut image).await.unwrap();
+        assert!(image.id > 0);
+
+        // Get images
+        let images = db.get_property_images(property.id).await.unwrap();
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].url, "https://example.com/image.jpg");
+    }
+
+    #[tokio::test]
+    async fn test_price_history_cleanup() {
+        let db = test_connection().await;
+        let now = DbTimestamp::now();
+
+        // Create a property
+        let mut property = Property {
+            id: 0,
+            external_id: "test-123".to_string(),
+            source: "test".to_string(),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::Apartment),
+            district: "Test District".to_string(),
+            title: "Test Property".to_string(),
+            description: Some("Test description".to_string()),
+            price_usd: 100000.0,
+            raw_price: 100000.0,
+            currency: Currency::Usd,
+            address: "123 Test St".to_string(),
+            latitude: None,
+            longitude: None,
+            covered_size: Some(100.0),
+            rooms: Some(2),
+            antiquity: Some(5),
+            under_construction: None,
+            bathrooms: None,
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
+            url: "https://example.com/test".to_string(),
+            status: DbPropertyStatus::new(STATUS_ACTIVE),
+            created_at: now.clone(),
+            updated_at: now.clone(),
+            last_seen_at: DbTimestamp::now(),
+        };
+
+        db.save_property(&mut property).await.unwrap();
+
+        // Record some price history
+        for i in 0..15 {
+            let price = 100000.0 + (i as f64 * 10000.0);
+            let timestamp = DbTimestamp::now();
+            db.record_price_history(property.id, price, timestamp).await.unwrap();
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        }
+
+        // Cleanup price history
+        let removed = db.cleanup_price_history(DEFAULT_PRICE_HISTORY_RETENTION).await.unwrap();
+        assert!(removed > 0, "Should have removed some price history entries");
+
+        // Verify cleanup
+        let history = db.get_price_history(property.id).await.unwrap();
+        assert!(history.len() <= 10, "Should have at most 10 price history entries");
+        
+        // Verify the entries are ordered by date descending
+        for i in 1..history.len() {
+            assert!(history[i-1].1 > history[i].1, "Price history should be ordered by date descending");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_type_safe_query_builder() {
+        let db = test_connection().await;
+        let now = DbTimestamp::now();
+
+        // Create some properties
+        let mut property1 = Property {
+            id: 0,
+            external_id: "test-1".to_string(),
+            source: "test".to_string(),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::Apartment),
+            district: "Test District".to_string(),
+            title: "Test Property 1".to_string(),
+            description: Some("Test description 1".to_string()),
+            price_usd: 100000.0,
+            raw_price: 100000.0,
+            currency: Currency::Usd,
+            address: "123 Test St".to_string(),
+            latitude: None,
+            longitude: None,
+            covered_size: Some(100.0),
+            rooms: Some(2),
+            antiquity: Some(5),
+            under_construction: None,
+            bathrooms: None,
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
+            url: "https://example.com/test1".to_string(),
+            status: DbPropertyStatus::new(STATUS_ACTIVE),
+            created_at: now.clone(),
+            updated_at: now.clone(),
+            last_seen_at: DbTimestamp::now(),
+        };
+
+        let mut property2 = Property {
+            id: 0,
+            external_id: "test-2".to_string(),
+            source: "test".to_string(),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::House),
+            district: "Test District".to_string(),
+            title: "Test Property 2".to_string(),
+            description: Some("Test description 2".to_string()),
+            price_usd: 200000.0,
+            raw_price: 200000.0,
+            currency: Currency::Usd,
+            address: "456 Test St".to_string(),
+            latitude: None,
+            longitude: None,
+            covered_size: Some(150.0),
+            rooms: Some(3),
+            antiquity: Some(10),
+            under_construction: None,
+            bathrooms: None,
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
+            url: "https://example.com/test2".to_string(),
+            status: DbPropertyStatus::new(STATUS_SOLD),
+            created_at: now.clone(),
+            updated_at: now.clone(),
+            last_seen_at: DbTimestamp::now(),
+        };
+
+        db.save_property(&mut property1).await.unwrap();
+        db.save_property(&mut property2).await.unwrap();
+
+        // Test query builder
+        let active_properties = PropertyQueryBuilder::new()
+            .with_status(DbPropertyStatus::new(STATUS_ACTIVE))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        assert_eq!(active_properties.len(), 1);
+        assert_eq!(active_properties[0].external_id, "test-1");
+
+        let sold_properties = PropertyQueryBuilder::new()
+            .with_status(DbPropertyStatus::new(STATUS_SOLD))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        assert_eq!(sold_properties.len(), 1);
+        assert_eq!(sold_properties[0].external_id, "test-2");
+    }
+
+    #[tokio::test]
+    async fn test_get_properties_by_type() {
+        let db = test_connection().await;
+
+        for (external_id, property_type) in [
+            ("apartment-1", PropertyType::Apartment),
+            ("house-1", PropertyType::House),
+            ("apartment-2", PropertyType::Apartment),
+            ("house-2", PropertyType::House),
+        ] {
+            let mut property = test_property(external_id);
+            property.property_type = Some(property_type);
+            db.save_property(&mut property).await.unwrap();
+        }
+        // A legacy row holding the display form
+        sqlx::query("UPDATE properties SET property_type = 'House' WHERE external_id = 'house-2'")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let houses = db.get_properties_by_type(PropertyType::House).await.unwrap();
+        let ids: Vec<_> = houses.iter().map(|p| p.external_id.as_str()).collect();
+        assert_eq!(ids, ["house-2", "house-1"]);
+
+        let apartments = db.get_properties_by_type(PropertyType::Apartment).await.unwrap();
+        assert_eq!(apartments.len(), 2);
+        assert!(apartments.iter().all(|p| p.property_type == Some(PropertyType::Apartment)));
+
+        assert!(db.get_properties_by_type(PropertyType::Office).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_type_safe_status_transitions() {
+        let db = test_connection().await;
+        let now = DbTimestamp::now();
+
+        // Create a property
+        let mut property = Property {
+            id: 0,
+            external_id: "test-123".to_string(),
+            source: "test".to_string(),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::Apartment),
+            district: "Test District".to_string(),
+            title: "Test Property".to_string(),
+            description: Some("Test description".to_string()),
+            price_usd: 100000.0,
+            raw_price: 100000.0,
+            currency: Currency::Usd,
+            address: "123 Test St".to_string(),
+            latitude: None,
+            longitude: None,
+            covered_size: Some(100.0),
+            rooms: Some(2),
+            antiquity: Some(5),
+            under_construction: None,
+            bathrooms: None,
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
+            url: "https://example.com/test".to_string(),
+            status: DbPropertyStatus::new(STATUS_ACTIVE),
+            created_at: now.clone(),
+            updated_at: now.clone(),
+            last_seen_at: DbTimestamp::now(),
+        };
+
+        db.save_property(&mut property).await.unwrap();
+
+        // Test status transitions
+        db.mark_property_as_sold(property.id).await.unwrap();
+        let sold = db.get_property(property.id).await.unwrap().unwrap();
+        assert_eq!(sold.status.as_str(), STATUS_SOLD);
+
+        db.mark_property_as_removed(property.id).await.unwrap();
+        let removed = db.get_property(property.id).await.unwrap().unwrap();
+        assert_eq!(removed.status.as_str(), STATUS_REMOVED);
+    }
+
+    #[tokio::test]
+    async fn test_validation_rejects_invalid_listings() {
+        let mut free = test_property("free");
+        free.raw_price = 0.0;
+
+        // Stored as before unless validation is on
+        let db = test_connection().await;
+        db.save_property(&mut free.clone()).await.unwrap();
+
+        let db = test_connection().await.with_validation(true);
+        let error = db.save_property(&mut free.clone()).await.unwrap_err();
+        assert!(matches!(error, BreaError::InvalidProperty(_)));
+        assert!(error.to_string().contains("test free: price 0 is not positive"));
+
+        let mut batch = vec![test_property("valid"), free];
+        assert!(matches!(
+            db.save_properties_batch(&mut batch).await,
+            Err(BreaError::InvalidProperty(_))
+        ));
+        assert!(db.get_properties().await.unwrap().is_empty());
+
+        db.save_property(&mut test_property("valid")).await.unwrap();
+        assert_eq!(db.get_properties().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_mark_properties_status() {
+        let db = test_connection().await;
+        let mut properties: Vec<Property> = (0..60).map(|i| test_property(&format!("test-{}", i))).collect();
+        for property in &mut properties {
+            db.save_property(property).await.unwrap();
+        }
+        let ids: Vec<i64> = properties.iter().take(50).map(|property| property.id).collect();
+
+        db.mark_properties_status(&ids, PropertyStatus::Sold).await.unwrap();
+        let sold = PropertyQueryBuilder::new()
+            .with_status_typed(PropertyStatus::Sold)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        let mut sold_ids: Vec<i64> = sold.iter().map(|property| property.id).collect();
+        sold_ids.sort();
+        assert_eq!(sold_ids, ids);
+
+        db.mark_properties_status(&[], PropertyStatus::Removed).await.unwrap();
+        let active = PropertyQueryBuilder::new()
+            .with_status_typed(PropertyStatus::Active)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(active.len(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_typed_status_filter() {
+        let db = test_connection().await;
+        let mut property = test_property("test-1");
+        db.save_property(&mut property).await.unwrap();
+        db.mark_property_as_sold(property.id).await.unwrap();
+
+        let typo = PropertyQueryBuilder::new()
+            .with_status(DbPropertyStatus::new("sould"))
+            .execute(db.pool())
+            .await
+            .unwrap();
+        assert!(typo.is_empty());
+
+        let sold = PropertyQueryBuilder::new()
+            .with_status_typed(PropertyStatus::Sold)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(sold.len(), 1);
+        assert_eq!(db.get_sold_properties().await.unwrap().len(), 1);
+        assert!(db.get_active_properties().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_type_safe_timestamp() {
+        let db = test_connection().await;
+        let now = DbTimestamp::now();
+
+        // Create a property
+        let mut property = Property {
+            id: 0,
+            external_id: "test-123".to_string(),
+            source: "test".to_string(),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::Apartment),
+            district: "Test District".to_string(),
+            title: "Test Property".to_string(),
+            description: Some("Test description".to_string()),
+            price_usd: 100000.0,
+            raw_price: 100000.0,
+            currency: Currency::Usd,
+            address: "123 Test St".to_string(),
+            latitude: None,
+            longitude: None,
+            covered_size: Some(100.0),
+            rooms: Some(2),
+            antiquity: Some(5),
+            under_construction: None,
+            bathrooms: None,
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
+            url: "https://example.com/test".to_string(),
+            status: DbPropertyStatus::new(STATUS_ACTIVE),
+            created_at: now.clone(),
+            updated_at: now.clone(),
+            last_seen_at: DbTimestamp::now(),
+        };
+
+        db.save_property(&mut property).await.unwrap();
+
+        // Test timestamp handling
+        let retrieved = db.get_property(property.id).await.unwrap().unwrap();
+        assert_eq!(retrieved.created_at.to_string(), now.to_string());
+        assert_eq!(retrieved.updated_at.to_string(), now.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_sale_and_rent_listings_do_not_collide() {
+        let db = test_connection().await;
+
+        let mut sale = test_property("test-123");
+        let mut rent = test_property("test-123");
+        rent.operation = Operation::Rent;
+        rent.raw_price = 800.0;
+
+        db.save_property(&mut sale).await.unwrap();
+        db.save_property(&mut rent).await.unwrap();
+        assert_ne!(sale.id, rent.id);
+
+        let stored_rent = db.get_property(rent.id).await.unwrap().unwrap();
+        assert_eq!(stored_rent.operation, Operation::Rent);
+        assert_eq!(stored_rent.price_usd, 800.0);
+
+        let stored_sale = db.get_property(sale.id).await.unwrap().unwrap();
+        assert_eq!(stored_sale.operation, Operation::Sale);
+        assert_eq!(stored_sale.price_usd, 100000.0);
+    }
+
+    #[tokio::test]
+    async fn test_batch_save_matches_individual_saves() {
+        let batched = test_connection().await;
+        let looped = test_connection().await;
+
+        let mut batch: Vec<Property> = (0..1000)
+            .map(|i| {
+                let mut property = test_property(&format!("test-{}", i));
+                property.raw_price = 100000.0 + i as f64;
+                property
+            })
+            .collect();
+        // The same page twice under different ids, the second time behind
+        // tracking parameters and cheaper
+        let mut relisted = test_property("relisted-a");
+        relisted.url = "https://example.com/relisted".to_string();
+        batch.push(relisted.clone());
+        relisted.external_id = "relisted-b".to_string();
+        relisted.url = "https://www.example.com/relisted?utm_source=feed".to_string();
+        relisted.raw_price = 90000.0;
+        batch.push(relisted);
+        let mut single = batch.clone();
+
+        batched.save_properties_batch(&mut batch).await.unwrap();
+        for property in single.iter_mut() {
+            looped.save_property(property).await.unwrap();
+        }
+
+        let mut from_batch = batched.get_properties().await.unwrap();
+        let mut from_loop = looped.get_properties().await.unwrap();
+        assert_eq!(from_batch.len(), 1001);
+        assert_eq!(from_loop.len(), 1001);
+        from_batch.sort_by_key(|p| p.id);
+        from_loop.sort_by_key(|p| p.id);
+        for (a, b) in from_batch.iter().zip(from_loop.iter()) {
+            assert_eq!(a.id, b.id);
+            assert_eq!(a.external_id, b.external_id);
+            assert_eq!(a.price_usd, b.price_usd);
+            assert_eq!(a.url, b.url);
+        }
+
+        // Both copies of the relisted page landed on one row, with both prices
+        assert_eq!(batch[1000].id, batch[1001].id);
+        assert_eq!(batch[1001].id, single[1001].id);
+        assert_eq!(batch[1001].external_id, "relisted-a");
+        assert_eq!(batched.get_price_history(batch[1000].id).await.unwrap().len(), 2);
+        assert_eq!(looped.get_price_history(single[1000].id).await.unwrap().len(), 2);
+
+        // Ids are backfilled and each listing starts with one history entry
+        for property in &batch[..1000] {
+            let stored = batched.get_property(property.id).await.unwrap().unwrap();
+            assert_eq!(stored.external_id, property.external_id);
+            assert_eq!(batched.get_price_history(property.id).await.unwrap().len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_jsonl_streams_every_row() {
+        let db = test_connection().await;
+        let mut properties: Vec<Property> = (0..5000)
+            .map(|i| test_property(&format!("test-{}", i)))
+            .collect();
+        db.save_properties_batch(&mut properties).await.unwrap();
+
+        let mut output = Vec::new();
+        let count = db.export_jsonl(&mut output, None, TimestampFormat::Rfc3339, false).await.unwrap();
+        assert_eq!(count, 5000);
+
+        let lines: Vec<&str> = std::str::from_utf8(&output).unwrap().lines().collect();
+        assert_eq!(lines.len(), 5000);
+        let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["external_id"], "test-0");
+    }
+
+    #[tokio::test]
+    async fn test_export_updated_since() {
+        let db = test_connection().await;
+        let mut properties: Vec<Property> = (0..3).map(|i| test_property(&format!("test-{}", i))).collect();
+        for property in &mut properties {
+            property.updated_at = DbTimestamp::from_rfc3339("2024-03-01T00:00:00Z").unwrap();
+            db.save_property(property).await.unwrap();
+        }
+        let since = DateTime::parse_from_rfc3339("2024-03-10T00:00:00Z").unwrap().with_timezone(&Utc);
+        assert!(db.list_properties_updated_since(since).await.unwrap().is_empty());
+
+        // A price change moves updated_at, a rescrape finding nothing new doesn't
+        properties[1].raw_price = 90000.0;
+        properties[1].updated_at = DbTimestamp::now();
+        db.save_property(&mut properties[1]).await.unwrap();
+        properties[2].updated_at = DbTimestamp::now();
+        assert_eq!(db.save_property(&mut properties[2]).await.unwrap(), SaveOutcome::Unchanged);
+
+        let changed = db.list_properties_updated_since(since).await.unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].external_id, "test-1");
+
+        let mut output = Vec::new();
+        assert_eq!(db.export_jsonl(&mut output, Some(since), TimestampFormat::Rfc3339, false).await.unwrap(), 1);
+        assert_eq!(db.export_jsonl(Vec::new(), None, TimestampFormat::Unix, false).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_export_jsonl_with_history() {
+        let db = test_connection().await;
+        let mut property = test_property("test-1");
+        db.save_property(&mut property).await.unwrap();
+        property.raw_price = 90000.0;
+        db.save_property(&mut property).await.unwrap();
+
+        let with_history = db.get_property_with_history(property.id).await.unwrap().unwrap();
+        let prices: Vec<f64> = with_history.price_history.iter().map(|observation| observation.price_usd).collect();
+        assert_eq!(prices, [100000.0, 90000.0]);
+        assert!(db.get_property_with_history(999).await.unwrap().is_none());
+
+        let mut output = Vec::new();
+        db.export_jsonl(&mut output, None, TimestampFormat::Unix, true).await.unwrap();
+        let record: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(record["price_history"][1]["price_usd"], 90000.0);
+        assert_eq!(
+            record["price_history"][0]["observed_at"],
+            with_history.price_history[0].observed_at.timestamp()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repair_integrity() {
+        let db = test_connection().await;
+        let mut kept = test_property("kept");
+        let mut duplicate = test_property("duplicate");
+        let mut other = test_property("other");
+        for property in [&mut kept, &mut duplicate, &mut other] {
+            db.save_property(property).await.unwrap();
+        }
+        // Stored under another id before listing URLs were matched on save
+        sqlx::query("UPDATE properties SET url = ?, canonical_url = ? WHERE id = ?")
+            .bind(&kept.url)
+            .bind(kept.canonical_url())
+            .bind(duplicate.id)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        let observed_at = DbTimestamp::from_rfc3339("2024-03-20T12:00:00Z").unwrap();
+        db.record_price_history(duplicate.id, 95000.0, observed_at).await.unwrap();
+
+        let now = DbTimestamp::now();
+        let image = |property_id: i64, url: &str| PropertyImage {
+            id: 0,
+            property_id,
+            url: url.to_string(),
+            local_path: String::new(),
+            hash: vec![1, 2, 3, 4],
+            phash: None,
+            is_primary: false,
+            created_at: now.clone(),
+            updated_at: now.clone(),
+        };
+        for (property_id, url) in [(kept.id, "https://example.com/1.jpg"), (duplicate.id, "https://example.com/1.jpg"), (duplicate.id, "https://example.com/2.jpg")] {
+            db.save_property_image(&mut image(property_id, url)).await.unwrap();
+        }
+        // An orphan, which only a connection without foreign keys can leave
+        let mut conn = db.pool().acquire().await.unwrap();
+        sqlx::query("PRAGMA foreign_keys = OFF").execute(&mut *conn).await.unwrap();
+        sqlx::query("INSERT INTO property_images (property_id, url, local_path, hash, created_at, updated_at) VALUES (999, 'x', '', x'00', ?, ?)")
+            .bind(&now)
+            .bind(&now)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query("PRAGMA foreign_keys = ON").execute(&mut *conn).await.unwrap();
+        drop(conn);
+
+        let dry_run = db.repair_integrity(RepairOptions { dry_run: true, ..Default::default() }).await.unwrap();
+        assert_eq!(dry_run.orphaned_images.len(), 1);
+        assert_eq!(dry_run.merged_duplicates, [(kept.id, duplicate.id)]);
+        assert!(db.get_property(duplicate.id).await.unwrap().is_some());
+
+        assert_eq!(db.repair_integrity(RepairOptions::default()).await.unwrap(), dry_run);
+        assert!(db.get_property(duplicate.id).await.unwrap().is_none());
+        assert!(db.get_property(other.id).await.unwrap().is_some());
+        let images = db.get_property_images(kept.id).await.unwrap();
+        let urls: Vec<&str> = images.iter().map(|image| image.url.as_str()).collect();
+        assert_eq!(urls, ["https://example.com/1.jpg", "https://example.com/2.jpg"]);
+        assert_eq!(images.iter().filter(|image| image.is_primary).count(), 1);
+        let prices: Vec<f64> = db.get_price_history(kept.id).await.unwrap().into_iter().map(|(price, _)| price).collect();
+        assert_eq!(prices, [100000.0, 100000.0, 95000.0]);
+        let leftover: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM property_images WHERE property_id NOT IN (SELECT id FROM properties)")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(leftover, 0);
+
+        assert_eq!(db.repair_integrity(RepairOptions::default()).await.unwrap(), RepairReport::default());
+    }
+
+    #[tokio::test]
+    async fn test_property_tags() {
+        let db = test_connection().await;
+        let mut first = test_property("test-1");
+        let mut second = test_property("test-2");
+        db.save_property(&mut first).await.unwrap();
+        db.save_property(&mut second).await.unwrap();
+
+        assert!(db.add_tag(first.id, "shortlist").await.unwrap());
+        assert!(!db.add_tag(first.id, " shortlist ").await.unwrap());
+        assert!(db.add_tag(second.id, "shortlist").await.unwrap());
+        assert!(db.add_tag(second.id, "needs visit").await.unwrap());
+        assert!(matches!(db.add_tag(first.id, "  ").await, Err(BreaError::InvalidArgument(_))));
+        let missing = db.add_tag(9999, "shortlist").await.unwrap_err();
+        assert!(missing.to_string().contains("no property with id 9999"));
+
+        let ids = |properties: Vec<Property>| properties.into_iter().map(|p| p.id).collect::<Vec<_>>();
+        assert_eq!(ids(db.get_properties_by_tag("shortlist").await.unwrap()), [first.id, second.id]);
+        assert_eq!(ids(db.get_properties_by_tag("needs visit").await.unwrap()), [second.id]);
+        assert!(db.get_properties_by_tag("Shortlist").await.unwrap().is_empty());
+
+        assert!(db.remove_tag(first.id, "shortlist").await.unwrap());
+        assert!(!db.remove_tag(first.id, "shortlist").await.unwrap());
+        assert_eq!(ids(db.get_properties_by_tag("shortlist").await.unwrap()), [second.id]);
+    }
+
+    #[tokio::test]
+    async fn test_notes_survive_rescrapes() {
+        let db = test_connection().await;
+        let mut property = test_property("test-1");
+        db.save_property(&mut property).await.unwrap();
+        db.set_notes(property.id, Some("Call the owner about the terrace")).await.unwrap();
+
+        // Scraped again with a new price and, as always, no notes
+        let mut rescraped = test_property("test-1");
+        rescraped.raw_price = 95000.0;
+        db.save_property(&mut rescraped).await.unwrap();
+        assert_eq!(rescraped.notes.as_deref(), Some("Call the owner about the terrace"));
+        let stored = db.get_property(property.id).await.unwrap().unwrap();
+        assert_eq!(stored.price_usd, 95000.0);
+        assert_eq!(stored.notes.as_deref(), Some("Call the owner about the terrace"));
+
+        let mut batch = [test_property("test-1")];
+        db.save_properties_batch(&mut batch).await.unwrap();
+        let stored = db.get_property(property.id).await.unwrap().unwrap();
+        assert_eq!(stored.notes.as_deref(), Some("Call the owner about the terrace"));
+
+        db.set_notes(property.id, None).await.unwrap();
+        assert_eq!(db.get_property(property.id).await.unwrap().unwrap().notes, None);
+        assert!(db.set_notes(9999, Some("nope")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rescrape_keeps_user_fields() {
+        let db = test_connection().await;
+        let mut removed = test_property("test-1");
+        removed.created_at = DbTimestamp::from_rfc3339("2024-01-01T00:00:00Z").unwrap();
+        let mut sold = test_property("test-2");
+        db.save_property(&mut removed).await.unwrap();
+        db.save_property(&mut sold).await.unwrap();
+        db.set_notes(removed.id, Some("Duplicate of test-2")).await.unwrap();
+        db.set_coordinates(removed.id, -34.5875, -58.4253).await.unwrap();
+        db.mark_property_as_removed(removed.id).await.unwrap();
+        db.mark_property_as_sold(sold.id).await.unwrap();
+
+        // The scraper sees both again, with a new title and its own guess
+        // at the coordinates
+        let rescrape = |external_id: &str| {
+            let mut property = test_property(external_id);
+            property.title = "Retitled".to_string();
+            property.latitude = Some(-34.6);
+            property.longitude = Some(-58.4);
+            property
+        };
+        let mut rescraped = rescrape("test-1");
+        db.save_property(&mut rescraped).await.unwrap();
+        let mut batch = [rescrape("test-2")];
+        db.save_properties_batch(&mut batch).await.unwrap();
+
+        let stored = db.get_property(removed.id).await.unwrap().unwrap();
+        assert_eq!(stored.title, "Retitled");
+        assert_eq!(stored.notes.as_deref(), Some("Duplicate of test-2"));
+        assert_eq!((stored.latitude, stored.longitude), (Some(-34.5875), Some(-58.4253)));
+        assert_eq!(stored.status.as_str(), STATUS_REMOVED);
+        assert_eq!(stored.created_at.to_string(), removed.created_at.to_string());
+        assert_eq!(rescraped.status.as_str(), STATUS_REMOVED);
+
+        // A sold listing showing up again is back on the market, and
+        // coordinates are taken from the scrape while there are none
+        let stored = db.get_property(sold.id).await.unwrap().unwrap();
+        assert_eq!(stored.status.as_str(), STATUS_ACTIVE);
+        assert_eq!((stored.latitude, stored.longitude), (Some(-34.6), Some(-58.4)));
+    }
+
+    #[tokio::test]
+    async fn test_min_price_change() {
+        let db = test_connection().await.with_min_price_change(1.0);
+        let mut property = test_property("test-1");
+        db.save_property(&mut property).await.unwrap();
+
+        // 0.05% up: the price moves, the history doesn't
+        let mut rescraped = test_property("test-1");
+        rescraped.raw_price = 100050.0;
+        let outcome = db.save_property(&mut rescraped).await.unwrap();
+        assert!(matches!(outcome, SaveOutcome::Updated { price_change: None }));
+        assert_eq!(db.get_property(property.id).await.unwrap().unwrap().price_usd, 100050.0);
+        assert_eq!(db.get_price_history(property.id).await.unwrap().len(), 1);
+
+        let mut batch = [test_property("test-1")];
+        db.save_properties_batch(&mut batch).await.unwrap();
+        assert_eq!(db.get_price_history(property.id).await.unwrap().len(), 1);
+
+        // 2% down is recorded
+        let mut rescraped = test_property("test-1");
+        rescraped.raw_price = 98000.0;
+        let outcome = db.save_property(&mut rescraped).await.unwrap();
+        assert!(matches!(outcome, SaveOutcome::Updated { price_change: Some(_) }));
+        let prices: Vec<f64> = db.get_price_history(property.id).await.unwrap().into_iter().map(|(price, _)| price).collect();
+        assert_eq!(prices, [98000.0, 100000.0]);
+    }
+
+    #[tokio::test]
+    async fn test_min_price_change_adds_up_small_steps() {
+        let db = test_connection().await.with_min_price_change(1.0);
+        let mut property = test_property("test-1");
+        db.save_property(&mut property).await.unwrap();
+        let prices = || async {
+            let history = db.get_price_history(property.id).await.unwrap();
+            history.into_iter().map(|(price, _)| price).collect::<Vec<f64>>()
+        };
+
+        // Each step is 0.6% down, under the threshold, but the second one
+        // puts the price 1.2% below the last recorded one
+        let mut rescraped = test_property("test-1");
+        rescraped.raw_price = 99400.0;
+        let outcome = db.save_property(&mut rescraped).await.unwrap();
+        assert!(matches!(outcome, SaveOutcome::Updated { price_change: None }));
+        let mut rescraped = test_property("test-1");
+        rescraped.raw_price = 98800.0;
+        let outcome = db.save_property(&mut rescraped).await.unwrap();
+        let SaveOutcome::Updated { price_change: Some(change) } = outcome else {
+            panic!("expected a recorded price change, got {:?}", outcome);
+        };
+        assert_eq!((change.old_price, change.new_price), (100000.0, 98800.0));
+        assert_eq!(prices().await, [98800.0, 100000.0]);
+
+        // The same through the batch path
+        let mut batch = [test_property("test-1")];
+        batch[0].raw_price = 98200.0;
+        db.save_properties_batch(&mut batch).await.unwrap();
+        assert_eq!(prices().await, [98800.0, 100000.0]);
+        let mut batch = [test_property("test-1")];
+        batch[0].raw_price = 97600.0;
+        db.save_properties_batch(&mut batch).await.unwrap();
+        assert_eq!(prices().await, [97600.0, 98800.0, 100000.0]);
+    }
+
+    #[tokio::test]
+    async fn test_merge_from() {
+        let at = |timestamp: &str| DbTimestamp::from_rfc3339(timestamp).unwrap();
+        // Both sides on disk, as the other database is attached by path
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new_without_migrations(dir.path().join("main.db")).await.unwrap();
+        apply_migrations(db.pool()).await.unwrap();
+        let other_path = dir.path().join("laptop.db");
+        let other = Database::new_without_migrations(&other_path).await.unwrap();
+        apply_migrations(other.pool()).await.unwrap();
+
+        // Ids don't line up: "shared" is 2 here and 1 there
+        let mut local_only = test_property("local-only");
+        let mut shared = test_property("shared");
+        let mut stale = test_property("stale");
+        let mut relisted = test_property("relisted");
+        for property in [&mut local_only, &mut shared, &mut stale, &mut relisted] {
+            db.save_property(property).await.unwrap();
+        }
+        let mut other_shared = test_property("shared");
+        other_shared.raw_price = 95000.0;
+        let mut other_stale = test_property("stale");
+        other_stale.title = "Older title".to_string();
+        let mut other_only = test_property("other-only");
+        // The same page under the id the site gave it later
+        let mut other_relisted = test_property("relisted-again");
+        other_relisted.url = "https://www.example.com/relisted/".to_string();
+        other_relisted.title = "Relisted".to_string();
+        for property in [&mut other_shared, &mut other_stale, &mut other_only, &mut other_relisted] {
+            other.save_property(property).await.unwrap();
+        }
+        set_last_seen_days_ago(&other, other_stale.id, 3).await;
+        other.set_notes(other_shared.id, Some("Seen from the laptop")).await.unwrap();
+        other.add_tag(other_only.id, "shortlist").await.unwrap();
+
+        // Both recorded the same observation, the laptop one more
+        for database in [&db, &other] {
+            sqlx::query("DELETE FROM property_price_history").execute(database.pool()).await.unwrap();
+        }
+        db.record_price_history(shared.id, 100000.0, at("2024-03-01T12:00:00Z")).await.unwrap();
+        other.record_price_history(other_shared.id, 100000.0, at("2024-03-01T09:00:00-03:00")).await.unwrap();
+        other.record_price_history(other_shared.id, 95000.0, at("2024-03-10T12:00:00Z")).await.unwrap();
+        other.record_price_history(other_only.id, 100000.0, at("2024-03-05T12:00:00Z")).await.unwrap();
+
+        let report = db.merge_from(&other_path).await.unwrap();
+        assert_eq!(report, MergeReport { inserted: 1, updated: 2, skipped: 1, price_history: 2 });
+
+        let stored = db.get_property(shared.id).await.unwrap().unwrap();
+        assert_eq!(stored.price_usd, 95000.0);
+        assert_eq!(stored.notes.as_deref(), Some("Seen from the laptop"));
+        let prices: Vec<f64> = db.get_price_history(shared.id).await.unwrap().into_iter().map(|(price, _)| price).collect();
+        assert_eq!(prices, [95000.0, 100000.0]);
+        assert_eq!(db.get_property(stale.id).await.unwrap().unwrap().title, stale.title);
+        // Matched by URL, it keeps the key it was stored under
+        let stored = db.get_property(relisted.id).await.unwrap().unwrap();
+        assert_eq!((stored.external_id.as_str(), stored.title.as_str()), ("relisted", "Relisted"));
+        assert!(db.get_property_by_key(&other_relisted.key(), Operation::Sale).await.unwrap().is_none());
+
+        let merged = db.get_properties_by_tag("shortlist").await.unwrap();
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].external_id, "other-only");
+        assert_ne!(merged[0].id, other_only.id);
+        assert_eq!(db.get_price_history(merged[0].id).await.unwrap().len(), 1);
+
+        // Merging again brings nothing new
+        let again = db.merge_from(&other_path).await.unwrap();
+        assert_eq!(again, MergeReport { inserted: 0, updated: 0, skipped: 4, price_history: 0 });
+
+        assert!(matches!(
+            db.merge_from(&dir.path().join("missing.db")).await,
+            Err(BreaError::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_prices_at_the_same_instant() {
+        let db = test_connection().await;
+        let mut property = test_property("test-1");
+        db.save_property(&mut property).await.unwrap();
+
+        // Two saves a fraction of a second apart both count
+        let first = DbTimestamp::from_rfc3339("2024-03-20T12:00:00.100Z").unwrap();
+        let second = DbTimestamp::from_rfc3339("2024-03-20T12:00:00.200Z").unwrap();
+        db.record_price_history(property.id, 95000.0, first.clone()).await.unwrap();
+        db.record_price_history(property.id, 90000.0, second).await.unwrap();
+
+        // So do two different prices at the very same instant, while a retry
+        // of the same observation doesn't add another
+        let instant = DbTimestamp::from_rfc3339("2024-03-21T12:00:00Z").unwrap();
+        db.record_price_history(property.id, 85000.0, instant.clone()).await.unwrap();
+        db.record_price_history(property.id, 80000.0, instant.clone()).await.unwrap();
+        db.record_price_history(property.id, 80000.0, instant).await.unwrap();
+        db.record_price_history(property.id, 95000.0, first).await.unwrap();
+
+        let prices: Vec<f64> = db.get_price_history(property.id).await.unwrap().into_iter().map(|(price, _)| price).collect();
+        // Newest first, the price saved today ahead of the 2024 ones
+        assert_eq!(prices, [100000.0, 80000.0, 85000.0, 90000.0, 95000.0]);
+    }
+
+    #[tokio::test]
+    async fn test_price_history_range() {
+        let db = test_connection().await;
+        let mut property = test_property("test-1");
+        db.save_property(&mut property).await.unwrap();
+        for (day, price) in [(1, 110000.0), (10, 105000.0), (20, 95000.0), (30, 90000.0)] {
+            let observed_at = DbTimestamp::from_rfc3339(&format!("2024-03-{:02}T12:00:00Z", day)).unwrap();
+            db.record_price_history(property.id, price, observed_at).await.unwrap();
+        }
+        let at = |day: u32| DateTime::parse_from_rfc3339(&format!("2024-03-{:02}T12:00:00Z", day)).unwrap().with_timezone(&Utc);
+
+        // Both ends are included, and prices come oldest first
+        let window = db.get_price_history_range(property.id, at(10), at(20)).await.unwrap();
+        let prices: Vec<f64> = window.iter().map(|(price, _)| *price).collect();
+        assert_eq!(prices, [105000.0, 95000.0]);
+        assert_eq!(window[0].1, at(10));
+
+        // Offsets are compared as instants, not as text
+        let from = DateTime::parse_from_rfc3339("2024-03-10T09:00:00-03:00").unwrap().with_timezone(&Utc);
+        assert_eq!(db.get_price_history_range(property.id, from, at(10)).await.unwrap().len(), 1);
+
+        assert!(db.get_price_history_range(property.id, at(2), at(9)).await.unwrap().is_empty());
+        assert!(db.get_price_history_range(property.id + 1, at(1), at(30)).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_find_duplicate_candidates() {
+        let db = test_connection().await;
+
+        let mut original = test_property("ap-1");
+        original.title = "Departamento 2 ambientes con balcón".to_string();
+        original.address = "Av. Santa Fe 1234".to_string();
+        // Same address, price and rooms from another source
+        let mut reposted = test_property("zp-77");
+        reposted.source = "other".to_string();
+        reposted.title = "Hermoso 2 ambientes".to_string();
+        reposted.address = "av santa fe  1234".to_string();
+        // Same title and price under a vaguer address
+        let mut retitled = test_property("ap-2");
+        retitled.title = "HERMOSO 2 AMBIENTES!".to_string();
+        retitled.address = "Palermo".to_string();
+        // Same address at a different price is a different unit
+        let mut other_unit = test_property("ap-3");
+        other_unit.title = "Departamento en Santa Fe".to_string();
+        other_unit.address = "Av. Santa Fe 1234".to_string();
+        other_unit.raw_price = 150000.0;
+
+        for property in [&mut original, &mut reposted, &mut retitled, &mut other_unit] {
+            db.save_property(property).await.unwrap();
+        }
+
+        let groups = db.find_duplicate_candidates().await.unwrap();
+        assert_eq!(groups.len(), 1);
+        let ids: Vec<i64> = groups[0].iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![original.id, reposted.id, retitled.id]);
+
+        // Removed listings are no longer candidates
+        db.mark_property_as_removed(reposted.id).await.unwrap();
+        db.mark_property_as_removed(retitled.id).await.unwrap();
+        assert!(db.find_duplicate_candidates().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_districts_are_normalized_on_save() {
+        let db = test_connection().await;
+
+        for (i, district) in ["Capital Federal", "CABA", "Núñez"].into_iter().enumerate() {
+            let mut property = test_property(&format!("test-{}", i));
+            property.district = district.to_string();
+            db.save_property(&mut property).await.unwrap();
+        }
+
+        let districts: Vec<String> = db.get_properties().await.unwrap().into_iter().map(|p| p.district).collect();
+        assert_eq!(districts, vec!["nunez", "capital federal", "capital federal"]);
+
+        let in_caba = db
+            .list_properties(None, None, Some("Ciudad de Buenos Aires"), None, None, None, None, None, None, None, false)
+            .await
+            .unwrap();
+        assert_eq!(in_caba.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_batch_save_updates_existing_listings() {
+        let db = test_connection().await;
+
+        let mut existing = test_property("test-1");
+        db.save_property(&mut existing).await.unwrap();
+
+        let mut changed = test_property("test-1");
+        changed.raw_price = 90000.0;
+        let mut batch = vec![changed, test_property("test-2"), test_property("test-2")];
+        batch[2].raw_price = 95000.0;
+        db.save_properties_batch(&mut batch).await.unwrap();
+
+        assert_eq!(batch[0].id, existing.id);
+        assert_eq!(batch[1].id, batch[2].id);
+        assert_eq!(db.get_properties().await.unwrap().len(), 2);
+
+        let updated = db.get_property(existing.id).await.unwrap().unwrap();
+        assert_eq!(updated.price_usd, 90000.0);
+        let duplicated = db.get_property(batch[1].id).await.unwrap().unwrap();
+        assert_eq!(duplicated.price_usd, 95000.0);
+    }
+
+    #[tokio::test]
+    async fn test_search_properties() {
+        let db = test_connection().await;
+
+        let mut terraza = test_property("test-1");
+        terraza.title = "Departamento con terraza".to_string();
+        terraza.description = Some("Luminoso, con parrilla".to_string());
+        let mut cochera = test_property("test-2");
+        cochera.description = Some("Incluye cochera cubierta".to_string());
+        db.save_property(&mut terraza).await.unwrap();
+        db.save_property(&mut cochera).await.unwrap();
+
+        let results = db.search_properties("terraza").await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, terraza.id);
+
+        let results = db.search_properties("cochera OR parrilla").await.unwrap();
+        assert_eq!(results.len(), 2);
+
+        // Updates are reflected in the index
+        cochera.description = Some("Sin cochera, con terraza".to_string());
+        db.save_property(&mut cochera).await.unwrap();
+        assert_eq!(db.search_properties("terraza").await.unwrap().len(), 2);
+        assert!(db.search_properties("cubierta").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_new_rejects_not_null_sizes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("old.db");
+        {
+            let db = Database::new_without_migrations(&path).await.unwrap();
+            // properties as created by the first release
+            sqlx::query(
+                r#"
+                CREATE TABLE properties (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    external_id TEXT NOT NULL,
+                    source TEXT NOT NULL,
+                    property_type TEXT,
+                    district TEXT NOT NULL,
+                    title TEXT NOT NULL,
+                    description TEXT,
+                    price_usd REAL NOT NULL,
+                    address TEXT NOT NULL,
+                    covered_size REAL NOT NULL,
+                    rooms INTEGER NOT NULL,
+                    antiquity INTEGER,
+                    url TEXT NOT NULL,
+                    created_at DATETIME NOT NULL,
+                    updated_at DATETIME NOT NULL,
+                    UNIQUE(source, external_id)
+                )
+                "#,
+            )
+            .execute(db.pool())
+            .await
+            .unwrap();
+        }
+
+        let error = Database::new(&path).await.unwrap_err();
+        let BreaError::Schema(message) = error else {
+            panic!("expected a schema error, got {}", error);
+        };
+        assert!(message.contains("properties.covered_size, properties.rooms"));
+        assert!(message.contains("database -a up"));
+
+        // Still opens for migrating, and fresh databases pass the check
+        assert!(Database::new_without_migrations(&path).await.is_ok());
+        assert!(Database::new(dir.path().join("new.db")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_migration_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new_without_migrations(dir.path().join("brea.db")).await.unwrap();
+        let latest = migrations::MIGRATIONS.last().unwrap().version();
+
+        let status = db.migration_status().await.unwrap();
+        assert_eq!(status.len(), migrations::MIGRATIONS.len());
+        assert!(status.iter().all(|(_, applied_at, pending)| applied_at.is_none() && *pending));
+
+        let before = Utc::now();
+        apply_migrations(db.pool()).await.unwrap();
+        migrations::rollback_migration(db.pool(), latest).await.unwrap();
+
+        let status = db.migration_status().await.unwrap();
+        let (version, applied_at, pending) = status.last().unwrap();
+        assert_eq!((*version, *applied_at, *pending), (latest, None, true));
+        for (_, applied_at, pending) in &status[..status.len() - 1] {
+            assert!(!pending);
+            assert!(applied_at.unwrap() >= before - chrono::Duration::seconds(1));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_changed_migration_is_reported() {
+        let db = test_connection().await;
+        sqlx::query("UPDATE migrations SET checksum = 'edited' WHERE version = 3")
+            .execute(db.pool())
+            .await
+            .unwrap();
+
+        let error = apply_migrations(db.pool()).await.unwrap_err();
+        let BreaError::Schema(message) = error else {
+            panic!("expected a schema error, got {}", error);
+        };
+        assert!(message.contains("migration 3 was changed"));
+    }
+
+    #[tokio::test]
+    async fn test_migrations_table_without_checksums() {
+        let db = test_connection().await;
+        // As recorded before checksums existed
+        sqlx::query("ALTER TABLE migrations DROP COLUMN checksum")
+            .execute(db.pool())
+            .await
+            .unwrap();
+
+        apply_migrations(db.pool()).await.unwrap();
+
+        let checksums: Vec<(i32, Option<String>)> = sqlx::query_as("SELECT version, checksum FROM migrations ORDER BY version")
+            .fetch_all(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(checksums.len(), migrations::MIGRATIONS.len());
+        for ((version, checksum), migration) in checksums.iter().zip(migrations::MIGRATIONS) {
+            assert_eq!(*version, migration.version());
+            assert_eq!(checksum.as_deref(), Some(migration.checksum().as_str()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_without_fts_table() {
+        let db = test_connection().await;
+        sqlx::query("DROP TABLE properties_fts").execute(db.pool()).await.unwrap();
+
+        let result = db.search_properties("terraza").await;
+        assert!(matches!(result, Err(BreaError::Schema(_))));
+    }
+
+    #[tokio::test]
+    async fn test_price_history_cleanup_keep() {
+        let db = test_connection().await;
+        let mut property = test_property("test-1");
+        db.save_property(&mut property).await.unwrap();
+
+        // 19 older entries on top of the one recorded on insert
+        for i in 1..20 {
+            let observed_at = (Utc::now() - chrono::Duration::minutes(i)).to_rfc3339();
+            let timestamp = DbTimestamp::from_rfc3339(&observed_at).unwrap();
+            db.record_price_history(property.id, 100000.0 + i as f64, timestamp).await.unwrap();
+        }
+        assert_eq!(db.get_price_history(property.id).await.unwrap().len(), 20);
+
+        let removed = db.cleanup_price_history(5).await.unwrap();
+        assert_eq!(removed, 15);
+        let history = db.get_price_history(property.id).await.unwrap();
+        assert_eq!(history.len(), 5);
+        assert_eq!(history[0].0, property.price_usd);
+
+        // keep == 0 still leaves the most recent observation
+        db.cleanup_price_history(0).await.unwrap();
+        let history = db.get_price_history(property.id).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].0, property.price_usd);
+    }
+
+    #[tokio::test]
+    async fn test_set_coordinates_survives_rescrape() {
+        let db = test_connection().await;
+
+        let mut property = test_property("test-1");
+        db.save_property(&mut property).await.unwrap();
+        let missing = db.get_properties_without_coordinates(None).await.unwrap();
+        assert_eq!(missing.len(), 1);
+
+        db.set_coordinates(property.id, -34.5875, -58.4253).await.unwrap();
+        assert!(db.get_properties_without_coordinates(None).await.unwrap().is_empty());
+
+        // A later scrape of the same listing carries no coordinates
+        let mut rescraped = test_property("test-1");
+        rescraped.raw_price = 95000.0;
+        db.save_property(&mut rescraped).await.unwrap();
+
+        let stored = db.get_property(property.id).await.unwrap().unwrap();
+        assert_eq!(stored.latitude, Some(-34.5875));
+        assert_eq!(stored.longitude, Some(-58.4253));
+    }
+
+    async fn set_last_seen_days_ago(db: &Database, property_id: i64, days: i64) {
+        let last_seen_at = (Utc::now() - chrono::Duration::days(days)).to_rfc3339();
+        sqlx::query("UPDATE properties SET last_seen_at = ? WHERE id = ?")
+            .bind(last_seen_at)
+            .bind(property_id)
+            .execute(db.pool())
+            .await
+            .unwrap();
+    }
+
+    async fn status_of(db: &Database, property_id: i64) -> String {
+        db.get_property(property_id).await.unwrap().unwrap().status.as_str().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_listing_status() {
+        let db = test_connection().await;
+
+        let mut seen = test_property("seen");
+        let mut recent = test_property("recent");
+        let mut stale = test_property("stale");
+        let mut gone = test_property("gone");
+        let mut other_source = test_property("other");
+        other_source.source = "other".to_string();
+        let mut elsewhere = test_property("elsewhere");
+        elsewhere.district = "Palermo".to_string();
+        let mut rental = test_property("rental");
+        rental.operation = Operation::Rent;
+        for property in [&mut seen, &mut recent, &mut stale, &mut gone, &mut other_source, &mut elsewhere, &mut rental] {
+            db.save_property(property).await.unwrap();
+        }
+        set_last_seen_days_ago(&db, recent.id, 1).await;
+        set_last_seen_days_ago(&db, stale.id, 5).await;
+        set_last_seen_days_ago(&db, gone.id, 45).await;
+        // Listings the scrape didn't search for, however long unseen
+        for id in [other_source.id, elsewhere.id, rental.id] {
+            set_last_seen_days_ago(&db, id, 45).await;
+        }
+        let reconcile = || {
+            let (district, property_type) = ("Test District", &PropertyType::Apartment);
+            db.reconcile_listing_status(&["seen"], "test", district, property_type, Operation::Sale, 3, 30)
+        };
+
+        let changes = reconcile().await.unwrap();
+        assert_eq!(changes.sold, vec![stale.id]);
+        assert_eq!(changes.removed, vec![gone.id]);
+
+        assert_eq!(status_of(&db, seen.id).await, STATUS_ACTIVE);
+        assert_eq!(status_of(&db, recent.id).await, STATUS_ACTIVE);
+        assert_eq!(status_of(&db, stale.id).await, STATUS_SOLD);
+        assert_eq!(status_of(&db, gone.id).await, STATUS_REMOVED);
+        for id in [other_source.id, elsewhere.id, rental.id] {
+            assert_eq!(status_of(&db, id).await, STATUS_ACTIVE);
+        }
+
+        // A sold listing that stays missing is eventually removed
+        set_last_seen_days_ago(&db, stale.id, 31).await;
+        let changes = reconcile().await.unwrap();
+        assert!(changes.sold.is_empty());
+        assert_eq!(changes.removed, vec![stale.id]);
+        assert_eq!(status_of(&db, stale.id).await, STATUS_REMOVED);
+    }
+
+    #[tokio::test]
+    async fn test_detect_sold_properties_by_source() {
+        let db = test_connection().await;
+        let mut seen = test_property("seen");
+        let mut missing = test_property("missing");
+        let mut other_source = test_property("other");
+        other_source.source = "other".to_string();
+        let mut rental = test_property("rental");
+        rental.operation = Operation::Rent;
+        for property in [&mut seen, &mut missing, &mut other_source, &mut rental] {
+            db.save_property(property).await.unwrap();
+        }
+
+        // Another source's "missing" doesn't count as seeing this one, and a
+        // sale scrape knows nothing about rentals
+        let sold = db
+            .detect_sold_properties(
+                &[seen.key(), PropertyKey::new("other", "missing")],
+                "test",
+                "Test District",
+                &PropertyType::Apartment,
+                Operation::Sale,
+            )
+            .await
+            .unwrap();
+        let sold_ids: Vec<i64> = sold.iter().map(|property| property.id).collect();
+        assert_eq!(sold_ids, vec![missing.id]);
+
+        let sold = db
+            .detect_sold_properties(
+                &[other_source.key()],
+                "other",
+                "Test District",
+                &PropertyType::Apartment,
+                Operation::Sale,
+            )
+            .await
+            .unwrap();
+        assert!(sold.is_empty());
+
+        let sold = db
+            .detect_sold_properties(
+                &[rental.key()],
+                "test",
+                "Test District",
+                &PropertyType::Apartment,
+                Operation::Rent,
+            )
+            .await
+            .unwrap();
+        assert!(sold.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_detect_sold_properties_by_district() {
+        let db = test_connection().await;
+        let mut seen = test_property("seen");
+        let mut missing = test_property("missing");
+        let mut elsewhere = test_property("elsewhere");
+        elsewhere.district = "Palermo".to_string();
+        let mut house = test_property("house");
+        house.property_type = Some(PropertyType::House);
+        for property in [&mut seen, &mut missing, &mut elsewhere, &mut house] {
+            db.save_property(property).await.unwrap();
+        }
+
+        // A scrape of apartments in one district says nothing about another
+        // district, or about houses in the same one
+        let sold = db
+            .detect_sold_properties(
+                &[seen.key()],
+                "test",
+                "Test District",
+                &PropertyType::Apartment,
+                Operation::Sale,
+            )
+            .await
+            .unwrap();
+        let sold_ids: Vec<i64> = sold.iter().map(|property| property.id).collect();
+        assert_eq!(sold_ids, vec![missing.id]);
+
+        let sold = db
+            .detect_sold_properties(
+                &[elsewhere.key()],
+                "test",
+                "Palermo",
+                &PropertyType::Apartment,
+                Operation::Sale,
+            )
+            .await
+            .unwrap();
+        assert!(sold.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_diff_against() {
+        let db = test_connection().await;
+
+        // Yesterday's scrape
+        let mut yesterday = Vec::new();
+        for external_id in ["kept", "repriced", "gone"] {
+            let mut property = test_property(external_id);
+            db.save_property(&mut property).await.unwrap();
+            yesterday.push(property);
+        }
+        let mut house = test_property("house");
+        house.property_type = Some(PropertyType::House);
+        db.save_property(&mut house).await.unwrap();
+        let mut rental = test_property("rental");
+        rental.operation = Operation::Rent;
+        db.save_property(&mut rental).await.unwrap();
+        sqlx::query("UPDATE property_price_history SET observed_at = ?")
+            .bind((Utc::now() - chrono::Duration::days(1)).to_rfc3339())
+            .execute(db.pool())
+            .await
+            .unwrap();
+
+        // Today's, which no longer has "gone"
+        let since = Utc::now();
+        let mut repriced = test_property("repriced");
+        repriced.price_usd = 90000.0;
+        repriced.raw_price = 90000.0;
+        for mut property in [test_property("kept"), repriced, test_property("new")] {
+            db.save_property(&mut property).await.unwrap();
+        }
+
+        let diff = db
+            .diff_against(
+                &["kept", "repriced", "new"],
+                "test",
+                "Test District",
+                &PropertyType::Apartment,
+                Operation::Sale,
+                since,
+            )
+            .await
+            .unwrap();
+        let ids = |properties: &[Property]| properties.iter().map(|p| p.external_id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&diff.added), ["new"]);
+        // Neither the house nor the rental are part of an apartment sale scrape
+        assert_eq!(ids(&diff.removed), ["gone"]);
+        assert_eq!(diff.price_changed.len(), 1);
+        let (property, old_price, new_price) = &diff.price_changed[0];
+        assert_eq!(property.id, yesterday[1].id);
+        assert_eq!((*old_price, *new_price), (100000.0, 90000.0));
+    }
+
+    #[tokio::test]
+    async fn test_get_property_by_key() {
+        let db = test_connection().await;
+        let mut property = test_property("keyed");
+        db.save_property(&mut property).await.unwrap();
+
+        let found = db.get_property_by_key(&property.key(), Operation::Sale).await.unwrap().unwrap();
+        assert_eq!(found.id, property.id);
+        assert!(db.get_property_by_key(&property.key(), Operation::Rent).await.unwrap().is_none());
+        assert!(db
+            .get_property_by_key(&PropertyKey::new("other", "keyed"), Operation::Sale)
+            .await
+            .unwrap()
+            .is_none());
+
+        // The same id listed for rent is a listing of its own
+        let mut rental = test_property("keyed");
+        rental.operation = Operation::Rent;
+        db.save_property(&mut rental).await.unwrap();
+        assert_ne!(rental.id, property.id);
+        let found = db.get_property_by_key(&property.key(), Operation::Rent).await.unwrap().unwrap();
+        assert_eq!(found.id, rental.id);
+        let found = db.get_property_by_key(&property.key(), Operation::Sale).await.unwrap().unwrap();
+        assert_eq!(found.id, property.id);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_rejects_inverted_thresholds() {
+        let db = test_connection().await;
+        let result = db
+            .reconcile_listing_status(&[], "test", "Test District", &PropertyType::Apartment, Operation::Sale, 30, 3)
+            .await;
+        assert!(matches!(result, Err(BreaError::InvalidArgument(_))));
+    }
+
+    #[tokio::test]
+    async fn test_last_seen_at_advances_on_rescrape() {
+        let db = test_connection().await;
+
+        let mut property = test_property("test-1");
+        db.save_property(&mut property).await.unwrap();
+        let first_seen = *db.get_property(property.id).await.unwrap().unwrap().last_seen_at.inner();
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        let cutoff = Utc::now();
+        assert_eq!(db.get_properties_not_seen_since(cutoff).await.unwrap().len(), 1);
+
+        let mut rescraped = test_property("test-1");
+        db.save_property(&mut rescraped).await.unwrap();
+        let last_seen = *db.get_property(property.id).await.unwrap().unwrap().last_seen_at.inner();
+
+        assert!(last_seen > first_seen);
+        assert!(db.get_properties_not_seen_since(cutoff).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ars_prices_are_converted_on_save() {
+        let db = test_connection().await;
+
+        let mut property = test_property("test-1");
+        property.raw_price = 85_000_000.0;
+        property.currency = Currency::Ars;
+        db.save_property(&mut property).await.unwrap();
+
+        let stored = db.get_property(property.id).await.unwrap().unwrap();
+        assert_eq!(stored.currency, Currency::Ars);
+        assert_eq!(stored.raw_price, 85_000_000.0);
+        assert_eq!(stored.price_usd, 85_000.0);
+
+        // Without a configured rate ARS listings are refused, not stored at 0
+        let db = db.with_rate_provider(Arc::new(StaticRateProvider::default()));
+        let mut unpriced = test_property("test-2");
+        unpriced.currency = Currency::Ars;
+        assert!(matches!(
+            db.save_property(&mut unpriced).await,
+            Err(crate::BreaError::Currency(_))
+        ));
+        // Or skipped, when saving them as part of a scrape
+        assert_eq!(db.save_property_or_skip(&mut unpriced).await.unwrap(), None);
+        assert!(db.get_property_by_key(&unpriced.key(), Operation::Sale).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_batch_skips_ars_listings_without_a_rate() {
+        let db = test_connection().await.with_rate_provider(Arc::new(StaticRateProvider::default()));
+        let mut rental = test_property("ars");
+        rental.operation = Operation::Rent;
+        rental.raw_price = 900_000.0;
+        rental.currency = Currency::Ars;
+        let mut batch = [test_property("usd-1"), rental, test_property("usd-2")];
+
+        db.save_properties_batch(&mut batch).await.unwrap();
+        assert!(batch[0].id > 0 && batch[2].id > 0);
+        assert_eq!(batch[1].id, 0);
+        let stored: Vec<String> = db.get_properties().await.unwrap().into_iter().map(|p| p.external_id).collect();
+        assert_eq!(stored, ["usd-2", "usd-1"]);
+    }
+
+    #[tokio::test]
+    async fn test_reprocess_prices() {
+        let db = test_connection().await;
+        let mut ars = test_property("ars");
+        ars.raw_price = 85_000_000.0;
+        ars.currency = Currency::Ars;
+        let mut usd = test_property("usd");
+        for property in [&mut ars, &mut usd] {
+            db.save_property(property).await.unwrap();
+        }
+        // Stored by a parser that read "85.000.000" as 85
+        sqlx::query("UPDATE properties SET price_usd = 0.085 WHERE id = ?")
+            .bind(ars.id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let changes = db.reprocess_prices_from_raw().await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].property_id, changes[0].old_price, changes[0].new_price), (ars.id, 0.085, 85_000.0));
+        assert_eq!(db.get_property(ars.id).await.unwrap().unwrap().price_usd, 85_000.0);
+        let prices: Vec<f64> = db.get_price_history(ars.id).await.unwrap().into_iter().map(|(price, _)| price).collect();
+        assert_eq!(prices, [85_000.0, 85_000.0]);
+        assert!(db.reprocess_prices_from_raw().await.unwrap().is_empty());
+
+        // Properties the reprocessor has nothing for stay as they are
+        let changes = db
+            .reprocess_prices(|property| (property.id == usd.id).then_some(95_000.0))
+            .await
+            .unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(db.get_property(usd.id).await.unwrap().unwrap().price_usd, 95_000.0);
+        assert_eq!(db.get_property(ars.id).await.unwrap().unwrap().price_usd, 85_000.0);
+    }
+
+    #[tokio::test]
+    async fn test_property_type_round_trip() {
+        let db = test_connection().await;
+
+        let mut property = test_property("test-1");
+        property.property_type = Some(PropertyType::CountryHouse);
+        db.save_property(&mut property).await.unwrap();
+
+        let stored = db.get_property(property.id).await.unwrap().unwrap();
+        assert_eq!(stored.property_type, Some(PropertyType::CountryHouse));
+        let raw: String = sqlx::query_scalar("SELECT property_type FROM properties WHERE id = ?")
+            .bind(property.id)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(raw, "country house");
+
+        // Rows written by older versions hold the display form
+        sqlx::query("UPDATE properties SET property_type = 'Commercial Premises' WHERE id = ?")
+            .bind(property.id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let stored = db.get_property(property.id).await.unwrap().unwrap();
+        assert_eq!(stored.property_type, Some(PropertyType::CommercialPremises));
+
+        for property_type in PropertyType::all() {
+            let decoded: PropertyType = sqlx::query_scalar("SELECT ?")
+                .bind(property_type.clone())
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+            assert_eq!(&decoded, property_type);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_every_property_type_is_stored_readably() {
+        let db = test_connection().await;
+
+        for property_type in PropertyType::all() {
+            let mut property = test_property(property_type.db_name());
+            property.property_type = Some(property_type.clone());
+            db.save_property(&mut property).await.unwrap();
+
+            let raw: String = sqlx::query_scalar("SELECT property_type FROM properties WHERE id = ?")
+                .bind(property.id)
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+            assert_eq!(raw, property_type.db_name());
+            let stored = db.get_property(property.id).await.unwrap().unwrap();
+            assert_eq!(stored.property_type.as_ref(), Some(property_type));
+            assert_eq!(PropertyType::from_str(&raw).as_ref(), Ok(property_type));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_image_exists_by_hash() {
+        let db = test_connection().await;
+
+        let mut property = test_property("test-1");
+        db.save_property(&mut property).await.unwrap();
+        assert!(!db.image_exists_by_hash(&[1, 2, 3]).await.unwrap());
+
+        let mut image = PropertyImage {
+            id: 0,
+            property_id: property.id,
+            url: "https://example.com/photo.jpg".to_string(),
+            local_path: "images/010203.jpg".to_string(),
+            hash: vec![1, 2, 3],
+            phash: None,
+            is_primary: false,
+            created_at: DbTimestamp::now(),
+            updated_at: DbTimestamp::now(),
+        };
+        db.save_property_image(&mut image).await.unwrap();
+
+        assert!(db.image_exists_by_hash(&[1, 2, 3]).await.unwrap());
+        assert!(!db.image_exists_by_hash(&[3, 2, 1]).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_price_drops() {
+        let db = test_connection().await;
+        let days_ago = |days: i64| DbTimestamp::from_rfc3339(&(Utc::now() - chrono::Duration::days(days)).to_rfc3339()).unwrap();
+
+        let histories: [(&str, &[(i64, f64)]); 5] = [
+            ("big-drop", &[(20, 200000.0), (10, 180000.0), (1, 150000.0)]),
+            ("small-drop", &[(10, 100000.0), (1, 95000.0)]),
+            ("single", &[(5, 100000.0)]),
+            ("dropped-long-ago", &[(60, 300000.0), (40, 250000.0)]),
+            ("rose", &[(10, 100000.0), (1, 120000.0)]),
+        ];
+        let mut ids = HashMap::new();
+        for (external_id, history) in histories {
+            let mut property = test_property(external_id);
+            db.save_property(&mut property).await.unwrap();
+            sqlx::query("DELETE FROM property_price_history WHERE property_id = ?")
+                .bind(property.id)
+                .execute(db.pool())
+                .await
+                .unwrap();
+            for (days, price) in history {
+                db.record_price_history(property.id, *price, days_ago(*days)).await.unwrap();
+            }
+            ids.insert(external_id, property.id);
+        }
+
+        let since = Utc::now() - chrono::Duration::days(30);
+        let drops = db.price_drops(since, 10.0).await.unwrap();
+        assert_eq!(drops.len(), 1);
+        assert_eq!(drops[0].0.id, ids["big-drop"]);
+        assert!((drops[0].1 - 25.0).abs() < 1e-9);
+
+        let drops = db.price_drops(since, 1.0).await.unwrap();
+        let found: Vec<i64> = drops.iter().map(|(property, _)| property.id).collect();
+        assert_eq!(found, vec![ids["big-drop"], ids["small-drop"]]);
+        assert!((drops[1].1 - 5.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_primary_image() {
+        let db = test_connection().await;
+        let mut property = test_property("test-1");
+        db.save_property(&mut property).await.unwrap();
+        assert!(db.get_primary_property_image(property.id).await.unwrap().is_none());
+
+        let mut images = Vec::new();
+        for name in ["front", "kitchen"] {
+            let mut image = PropertyImage {
+                id: 0,
+                property_id: property.id,
+                url: format!("https://example.com/{}.jpg", name),
+                local_path: String::new(),
+                hash: name.as_bytes().to_vec(),
+                phash: None,
+                is_primary: false,
+                created_at: DbTimestamp::now(),
+                updated_at: DbTimestamp::now(),
+            };
+            db.save_property_image(&mut image).await.unwrap();
+            images.push(image);
+        }
+
+        let stored = db.get_property_images(property.id).await.unwrap();
+        assert_eq!(stored.iter().filter(|image| image.is_primary).count(), 1);
+        let primary = db.get_primary_property_image(property.id).await.unwrap().unwrap();
+        assert_eq!(primary.id, images[0].id);
+
+        db.set_primary_image(property.id, images[1].id).await.unwrap();
+        let stored = db.get_property_images(property.id).await.unwrap();
+        assert_eq!(stored.iter().filter(|image| image.is_primary).count(), 1);
+        let primary = db.get_primary_property_image(property.id).await.unwrap().unwrap();
+        assert_eq!(primary.url, "https://example.com/kitchen.jpg");
+
+        // Another property's photo can't be made primary here
+        let mut other = test_property("test-2");
+        db.save_property(&mut other).await.unwrap();
+        assert!(db.set_primary_image(other.id, images[0].id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_find_properties_with_similar_images() {
+        let db = test_connection().await;
+
+        let mut original = test_property("test-1");
+        let mut relisted = test_property("test-2");
+        let mut unrelated = test_property("test-3");
+        for (property, phash) in [
+            (&mut original, 0xF0F0_F0F0_F0F0_F0F0u64),
+            (&mut relisted, 0xF0F0_F0F0_F0F0_F0F1),
+            (&mut unrelated, 0x0F0F_0F0F_0F0F_0F0F),
+        ] {
+            db.save_property(property).await.unwrap();
+            let mut image = PropertyImage {
+                id: 0,
+                property_id: property.id,
+                url: format!("https://example.com/{}.jpg", property.external_id),
+                local_path: String::new(),
+                hash: property.external_id.as_bytes().to_vec(),
+                phash: Some(phash.to_be_bytes().to_vec()),
+                is_primary: false,
+                created_at: DbTimestamp::now(),
+                updated_at: DbTimestamp::now(),
+            };
+            db.save_property_image(&mut image).await.unwrap();
+        }
+
+        let similar = db.find_properties_with_similar_images(0xF0F0_F0F0_F0F0_F0F0, 4).await.unwrap();
+        let mut ids: Vec<_> = similar.iter().map(|p| p.external_id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["test-1", "test-2"]);
+    }
+
+    #[tokio::test]
+    async fn test_compute_stats() {
+        let db = test_connection().await;
+        assert_eq!(db.compute_stats().await.unwrap(), DbStats::default());
+
+        let mut listings = Vec::new();
+        for (i, (district, price, size)) in [
+            ("Palermo", 100000.0, Some(50.0)),
+            ("Palermo", 300000.0, Some(100.0)),
+            ("Belgrano", 200000.0, Some(100.0)),
+            ("Belgrano", 0.0, None),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mut property = test_property(&format!("test-{}", i));
+            property.district = district.to_string();
+            property.raw_price = price;
+            property.covered_size = size;
+            listings.push(property);
+        }
+        listings[3].property_type = None;
+        listings[3].status = DbPropertyStatus::new(STATUS_SOLD);
+        db.save_properties_batch(&mut listings).await.unwrap();
+
+        let stats = db.compute_stats().await.unwrap();
+        assert_eq!(stats.total, 4);
+        assert_eq!(stats.by_status, vec![("active".to_string(), 3), ("sold".to_string(), 1)]);
+        assert_eq!(
+            stats.by_property_type,
+            vec![("apartment".to_string(), 3), ("unknown".to_string(), 1)]
+        );
+        assert_eq!(stats.median_price_usd, 200000.0);
+        assert_eq!(stats.average_price_usd, 200000.0);
+        assert!((stats.average_covered_size - 250.0 / 3.0).abs() < 1e-9);
+        assert_eq!(
+            stats.price_per_m2_by_district,
+            vec![("belgrano".to_string(), 2000.0), ("palermo".to_string(), 2500.0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_district_type_stats() {
+        let db = test_connection().await;
+        let empty = db.district_type_stats("Palermo", &PropertyType::Apartment).await.unwrap();
+        assert_eq!(empty, PriceStats::default());
+
+        let mut listings = Vec::new();
+        for (i, (district, property_type, price, size)) in [
+            ("Palermo", PropertyType::Apartment, 100000.0, Some(50.0)),
+            ("Palermo", PropertyType::Apartment, 300000.0, Some(100.0)),
+            ("Palermo", PropertyType::Apartment, 200000.0, None),
+            ("Palermo", PropertyType::Apartment, 0.0, Some(40.0)),
+            ("Palermo", PropertyType::House, 500000.0, Some(200.0)),
+            ("Belgrano", PropertyType::Apartment, 150000.0, Some(60.0)),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mut property = test_property(&format!("test-{}", i));
+            property.district = district.to_string();
+            property.property_type = Some(property_type);
+            property.raw_price = price;
+            property.covered_size = size;
+            listings.push(property);
+        }
+        db.save_properties_batch(&mut listings).await.unwrap();
+
+        let stats = db.district_type_stats("palermo", &PropertyType::Apartment).await.unwrap();
+        assert_eq!(stats.count, 3);
+        assert_eq!(
+            stats.price_usd,
+            Some(Distribution { min: 100000.0, max: 300000.0, mean: 200000.0, median: 200000.0 })
+        );
+        // Only the two listings with a size count towards USD/m²
+        assert_eq!(
+            stats.price_per_m2,
+            Some(Distribution { min: 2000.0, max: 3000.0, mean: 2500.0, median: 2500.0 })
+        );
+
+        let houses = db.district_type_stats("Palermo", &PropertyType::House).await.unwrap();
+        assert_eq!(houses.count, 1);
+        assert_eq!(houses.price_usd.unwrap().median, 500000.0);
+
+        let none = db.district_type_stats("Belgrano", &PropertyType::House).await.unwrap();
+        assert_eq!(none, PriceStats::default());
+    }
+
+    #[tokio::test]
+    async fn test_price_per_m2_filter() {
+        let db = test_connection().await;
+
+        let mut listings = Vec::new();
+        for (i, size) in [Some(50.0), Some(100.0), Some(0.0), None].into_iter().enumerate() {
+            let mut property = test_property(&format!("test-{}", i));
+            property.covered_size = size;
+            listings.push(property);
+        }
+        db.save_properties_batch(&mut listings).await.unwrap();
+
+        // 100000 USD over 50 and 100 m²: 2000 and 1000 USD/m²
+        let cheap = PropertyQueryBuilder::new()
+            .with_price_per_m2_range(None, Some(1500.0))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(cheap.len(), 1);
+        assert_eq!(cheap[0].external_id, "test-1");
+
+        let pricey = PropertyQueryBuilder::new()
+            .with_price_per_m2_range(Some(1500.0), None)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(pricey.len(), 1);
+        assert_eq!(pricey[0].external_id, "test-0");
+
+        let all = PropertyQueryBuilder::new()
+            .with_price_per_m2_range(None, None)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_rooms_and_antiquity_ranges() {
+        let db = test_connection().await;
+
+        let mut listings = Vec::new();
+        for rooms in 1..=5 {
+            let mut property = test_property(&format!("rooms-{}", rooms));
+            property.rooms = Some(rooms);
+            property.antiquity = Some(rooms * 10);
+            listings.push(property);
+        }
+        let mut unknown = test_property("unknown");
+        unknown.rooms = None;
+        unknown.antiquity = None;
+        listings.push(unknown);
+        db.save_properties_batch(&mut listings).await.unwrap();
+
+        let ids = |properties: Vec<Property>| {
+            let mut ids: Vec<_> = properties.into_iter().map(|p| p.external_id).collect();
+            ids.sort();
+            ids
+        };
+
+        let two_to_four = PropertyQueryBuilder::new()
+            .with_rooms_range(Some(2), Some(4))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(ids(two_to_four), ["rooms-2", "rooms-3", "rooms-4"]);
+
+        let at_least_five = PropertyQueryBuilder::new()
+            .with_rooms_range(Some(5), None)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(ids(at_least_five), ["rooms-5"]);
+
+        let at_most_twenty = PropertyQueryBuilder::new()
+            .with_antiquity_range(None, Some(20))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(ids(at_most_twenty), ["rooms-1", "rooms-2"]);
+
+        let all = PropertyQueryBuilder::new()
+            .with_rooms_range(None, None)
+            .with_antiquity_range(None, None)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_bathrooms_range() {
+        let db = test_connection().await;
+
+        for bathrooms in [None, Some(1), Some(2), Some(3)] {
+            let mut property = test_property(&format!("baths-{:?}", bathrooms));
+            property.bathrooms = bathrooms;
+            db.save_property(&mut property).await.unwrap();
+        }
+
+        let at_least_two = PropertyQueryBuilder::new()
+            .with_bathrooms_range(Some(2), None)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let mut bathrooms: Vec<_> = at_least_two.iter().map(|p| p.bathrooms).collect();
+        bathrooms.sort();
+        assert_eq!(bathrooms, [Some(2), Some(3)]);
+
+        let all = PropertyQueryBuilder::new()
+            .with_bathrooms_range(None, None)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_created_and_updated_range() {
+        let db = test_connection().await;
+        let at = |timestamp: &str| DbTimestamp::from_rfc3339(timestamp).unwrap();
+
+        for (external_id, created_at, updated_at) in [
+            ("march", "2024-03-31T23:59:59Z", "2024-04-02T10:00:00Z"),
+            ("april", "2024-04-01T00:00:00Z", "2024-04-01T00:00:00Z"),
+            ("later", "2024-04-08T12:00:00Z", "2024-04-08T12:00:00Z"),
+        ] {
+            let mut property = test_property(external_id);
+            property.created_at = at(created_at);
+            property.updated_at = at(updated_at);
+            db.save_property(&mut property).await.unwrap();
+        }
+        let ids = |properties: Vec<Property>| {
+            let mut ids: Vec<String> = properties.into_iter().map(|p| p.external_id).collect();
+            ids.sort();
+            ids
+        };
+
+        // Midnight in Buenos Aires is 03:00 UTC
+        let added = PropertyQueryBuilder::new()
+            .with_created_range(Some(at("2024-03-31T21:00:00-03:00")), Some(at("2024-04-07T23:59:59Z")))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(ids(added), ["april"]);
+
+        let before = PropertyQueryBuilder::new()
+            .with_created_range(None, Some(at("2024-03-31T23:59:59Z")))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(ids(before), ["march"]);
+
+        let updated = PropertyQueryBuilder::new()
+            .with_updated_range(Some(at("2024-04-02T00:00:00Z")), None)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(ids(updated), ["later", "march"]);
+    }
+
+    #[tokio::test]
+    async fn test_expenses_range() {
+        let db = test_connection().await;
+
+        for expenses in [None, Some(40000.0), Some(85000.0), Some(150000.0)] {
+            let mut property = test_property(&format!("expenses-{:?}", expenses));
+            property.expenses_ars = expenses;
+            db.save_property(&mut property).await.unwrap();
+        }
+
+        let affordable = PropertyQueryBuilder::new()
+            .with_expenses_range(None, Some(100000.0))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let mut expenses: Vec<_> = affordable.iter().filter_map(|p| p.expenses_ars).collect();
+        expenses.sort_by(f64::total_cmp);
+        assert_eq!(expenses, [40000.0, 85000.0]);
+    }
+
+    #[tokio::test]
+    async fn test_parking_filter() {
+        let db = test_connection().await;
+
+        for has_parking in [None, Some(true), Some(false)] {
+            let mut property = test_property(&format!("parking-{:?}", has_parking));
+            property.has_parking = has_parking;
+            db.save_property(&mut property).await.unwrap();
+        }
+
+        let with_parking = PropertyQueryBuilder::new().with_parking(true).execute(&db.pool).await.unwrap();
+        assert_eq!(with_parking.len(), 1);
+        assert_eq!(with_parking[0].external_id, "parking-Some(true)");
+
+        let without = PropertyQueryBuilder::new().with_parking(false).execute(&db.pool).await.unwrap();
+        assert_eq!(without.len(), 1);
+        assert_eq!(without[0].has_parking, Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_count_matches_rows() {
+        let db = test_connection().await;
+
+        for rooms in 1..=5 {
+            let mut property = test_property(&format!("rooms-{}", rooms));
+            property.rooms = Some(rooms);
+            property.raw_price = 50000.0 * rooms as f64;
+            db.save_property(&mut property).await.unwrap();
+            if rooms == 1 {
+                db.mark_property_as_sold(property.id).await.unwrap();
+            }
+        }
+
+        let filters = || {
+            PropertyQueryBuilder::new()
+                .with_status_typed(PropertyStatus::Active)
+                .with_district("Test District")
+                .with_rooms_range(Some(2), None)
+                .with_price_range(None, Some(200000.0))
+        };
+        let rows = filters().execute(&db.pool).await.unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(db.count_properties(filters()).await.unwrap(), 3);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use chrono::Utc;
+        // Paging applies to the count as it does to the rows
+        let paged = || filters().order_by("price_usd", false).unwrap().with_limit(Some(2)).with_offset(Some(2));
+        assert_eq!(paged().execute(&db.pool).await.unwrap().len(), 1);
+        assert_eq!(paged().count(&db.pool).await.unwrap(), 1);
 
-    async fn test_connection() -> Database {
-        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
-        let db = Database { pool, migrations: Vec::new() };
-        apply_migrations(&db.pool).await.unwrap();
-        db
+        assert_eq!(db.count_properties(PropertyQueryBuilder::new()).await.unwrap(), 5);
+        let none = PropertyQueryBuilder::new().with_district("recoleta");
+        assert_eq!(db.count_properties(none).await.unwrap(), 0);
     }
 
     #[tokio::test]
-    async fn test_database_creation() {
-        let _db = test_connection().await;
+    async fn test_filter_queries_use_indexes() {
+        let db = test_connection().await;
+
+        let pool = db.pool();
+        let plan = |sql: &'static str| async move {
+            let rows = sqlx::query(sql).fetch_all(pool).await.unwrap();
+            rows.iter().map(|row| row.get::<String, _>("detail")).collect::<Vec<_>>().join("\n")
+        };
+
+        // The query list and watch send, type and price range included
+        let filtered = PropertyQueryBuilder::new()
+            .with_district("palermo")
+            .with_property_type(&PropertyType::Apartment)
+            .with_price_range(Some(100000.0), Some(150000.0))
+            .query_plan(pool)
+            .await
+            .unwrap();
+        assert!(
+            filtered.contains("USING INDEX idx_properties_filter (district=? AND <expr>=? AND price_usd>? AND price_usd<?)"),
+            "{}",
+            filtered
+        );
+
+        let history = plan(
+            "EXPLAIN QUERY PLAN SELECT price_usd, observed_at FROM property_price_history \
+             WHERE property_id = 1 ORDER BY observed_at DESC",
+        )
+        .await;
+        assert!(history.contains("USING COVERING INDEX"), "{}", history);
+        assert!(!history.contains("USE TEMP B-TREE"), "{}", history);
     }
 
     #[tokio::test]
-    async fn test_property_crud() {
+    async fn test_same_listing_matched_by_canonical_url() {
         let db = test_connection().await;
-        let now = DbTimestamp::now();
 
-        // Create a property
-        let mut property = Property {
-            id: 0,
-            external_id: "test-123".to_string(),
-            source: "test".to_string(),
-            property_type: Some("apartment".to_string()),
-            district: "Test District".to_string(),
-            title: "Test Property".to_string(),
-            description: Some("Test description".to_string()),
-            price_usd: 100000.0,
-            address: "123 Test St".to_string(),
-            covered_size: Some(100.0),
-            rooms: Some(2),
-            antiquity: Some(5),
-            url: "https://example.com/test".to_string(),
-            status: DbPropertyStatus::new(STATUS_ACTIVE),
-            created_at: now.clone(),
-            updated_at: now,
-        };
+        let mut original = test_property("ap-1");
+        original.url = "https://www.argenprop.com/departamento--1?utm_source=newsletter".to_string();
+        db.save_property(&mut original).await.unwrap();
 
-        // Save property
-        db.save_property(&mut property).await.unwrap();
-        assert!(property.id > 0);
+        // The same page from another source, behind different tracking params
+        let mut relisted = test_property("feed-77");
+        relisted.source = "feed".to_string();
+        relisted.url = "https://argenprop.com/departamento--1?utm_source=feed&utm_medium=rss#fotos".to_string();
+        relisted.raw_price = 95000.0;
+        let outcome = db.save_property(&mut relisted).await.unwrap();
+        assert!(matches!(outcome, SaveOutcome::Updated { price_change: Some(_) }));
+        assert_eq!(relisted.id, original.id);
+        assert_eq!((relisted.source.as_str(), relisted.external_id.as_str()), ("test", "ap-1"));
 
-        // Get property
-        let retrieved = db.get_property(property.id).await.unwrap().unwrap();
-        assert_eq!(retrieved.external_id, "test-123");
-        assert_eq!(retrieved.price_usd, 100000.0);
+        let mut batch = vec![test_property("feed-78")];
+        batch[0].source = "feed".to_string();
+        batch[0].url = "http://www.argenprop.com/departamento--1/".to_string();
+        db.save_properties_batch(&mut batch).await.unwrap();
+        assert_eq!(batch[0].id, original.id);
 
-        // Update property
-        property.price_usd = 150000.0;
-        property.updated_at = DbTimestamp::now();
-        db.update_property(&property).await.unwrap();
+        let stored = db.get_properties().await.unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].external_id, "ap-1");
+        assert_eq!(stored[0].price_usd, 100000.0);
 
-        // Verify update
-        let updated = db.get_property(property.id).await.unwrap().unwrap();
-        assert_eq!(updated.price_usd, 150000.0);
+        // A different listing on the same site is still a new one
+        let mut other = test_property("ap-2");
+        other.url = "https://www.argenprop.com/departamento--2?utm_source=newsletter".to_string();
+        assert_eq!(db.save_property(&mut other).await.unwrap(), SaveOutcome::Inserted);
     }
 
     #[tokio::test]
-    async fn test_property_image_crud() {
+    async fn test_under_construction_round_trip() {
         let db = test_connection().await;
-        let now = DbTimestamp::now();
-
-        // Create a property first
-        let mut property = Property {
-            id: 0,
-            external_id: "test-123".to_string(),
-            source: "test".to_string(),
-            property_type: Some("apartment".to_string()),
-            district: "Test District".to_string(),
-            title: "Test Property".to_string(),
-            description: Some("Test description".to_string()),
-            price_usd: 100000.0,
-            address: "123 Test St".to_string(),
-            covered_size: Some(100.0),
-            rooms: Some(2),
-            antiquity: Some(5),
-            url: "https://example.com/test".to_string(),
-            status: DbPropertyStatus::new(STATUS_ACTIVE),
-            created_at: now.clone(),
-            updated_at: now.clone(),
-        };
-
-        db.save_property(&mut property).await.unwrap();
 
-        // Create an image
-        let mut image = PropertyImage {
-            id: 0,
-            property_id: property.id,
-            url: "https://example.com/image.jpg".to_string(),
-            local_path: "/tmp/images/test.jpg".to_string(),
-            hash: vec![1, 2, 3, 4],
-            created_at: now.clone(),
-            updated_at: now,
-        };
+        let mut presale = test_property("pozo-1");
+        presale.antiquity = None;
+        presale.under_construction = Some(true);
+        db.save_property(&mut presale).await.unwrap();
 
-        // Save image
-        db.save_property_image(&mut image).await.unwrap();
-        assert!(image.id > 0);
+        // Finished once the listing gives the building an age
+        let mut finished = presale.clone();
+        finished.antiquity = Some(0);
+        finished.under_construction = Some(false);
+        assert!(matches!(
+            db.save_property(&mut finished).await.unwrap(),
+            SaveOutcome::Updated { price_change: None }
+        ));
 
-        // Get images
-        let images = db.get_property_images(property.id).await.unwrap();
-        assert_eq!(images.len(), 1);
-        assert_eq!(images[0].url, "https://example.com/image.jpg");
+        let stored = db.get_property(presale.id).await.unwrap().unwrap();
+        assert_eq!((stored.antiquity, stored.under_construction), (Some(0), Some(false)));
     }
 
     #[tokio::test]
-    async fn test_price_history_cleanup() {
+    async fn test_save_property_outcomes() {
         let db = test_connection().await;
-        let now = DbTimestamp::now();
 
-        // Create a property
-        let mut property = Property {
-            id: 0,
-            external_id: "test-123".to_string(),
-            source: "test".to_string(),
-            property_type: Some("apartment".to_string()),
-            district: "Test District".to_string(),
-            title: "Test Property".to_string(),
-            description: Some("Test description".to_string()),
-            price_usd: 100000.0,
-            address: "123 Test St".to_string(),
-            covered_size: Some(100.0),
-            rooms: Some(2),
-            antiquity: Some(5),
-            url: "https://example.com/test".to_string(),
-            status: DbPropertyStatus::new(STATUS_ACTIVE),
-            created_at: now.clone(),
-            updated_at: now.clone(),
-        };
+        let mut property = test_property("test-1");
+        assert_eq!(db.save_property(&mut property).await.unwrap(), SaveOutcome::Inserted);
 
-        db.save_property(&mut property).await.unwrap();
+        let mut same = test_property("test-1");
+        assert_eq!(db.save_property(&mut same).await.unwrap(), SaveOutcome::Unchanged);
 
-        // Record some price history
-        for i in 0..15 {
-            let price = 100000.0 + (i as f64 * 10000.0);
-            let timestamp = DbTimestamp::now();
-            db.record_price_history(property.id, price, timestamp).await.unwrap();
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-        }
+        let mut retitled = test_property("test-1");
+        retitled.title = "Retitled".to_string();
+        assert_eq!(
+            db.save_property(&mut retitled).await.unwrap(),
+            SaveOutcome::Updated { price_change: None }
+        );
 
-        // Cleanup price history
-        let removed = db.cleanup_price_history().await.unwrap();
-        assert!(removed > 0, "Should have removed some price history entries");
+        let mut cheaper = test_property("test-1");
+        cheaper.title = "Retitled".to_string();
+        cheaper.raw_price = 90000.0;
+        match db.save_property(&mut cheaper).await.unwrap() {
+            SaveOutcome::Updated { price_change: Some(change) } => {
+                assert_eq!(change.property_id, property.id);
+                assert_eq!(change.old_price, 100000.0);
+                assert_eq!(change.new_price, 90000.0);
+            }
+            outcome => panic!("expected a price change, got {:?}", outcome),
+        }
+    }
 
-        // Verify cleanup
-        let history = db.get_price_history(property.id).await.unwrap();
-        assert!(history.len() <= 10, "Should have at most 10 price history entries");
-        
-        // Verify the entries are ordered by date descending
-        for i in 1..history.len() {
-            assert!(history[i-1].1 > history[i].1, "Price history should be ordered by date descending");
+    fn saved_search(district: Option<&str>, max_price: Option<f64>) -> SavedSearch {
+        SavedSearch {
+            id: 0,
+            district: district.map(str::to_string),
+            property_type: Some(PropertyType::Apartment),
+            min_price: None,
+            max_price,
+            min_size: None,
+            max_size: None,
+            created_at: DbTimestamp::now(),
+            last_checked_at: None,
         }
     }
 
     #[tokio::test]
-    async fn test_type_safe_query_builder() {
+    async fn test_matching_new_properties() {
         let db = test_connection().await;
-        let now = DbTimestamp::now();
 
-        // Create some properties
-        let mut property1 = Property {
-            id: 0,
-            external_id: "test-1".to_string(),
-            source: "test".to_string(),
-            property_type: Some("apartment".to_string()),
-            district: "Test District".to_string(),
-            title: "Test Property 1".to_string(),
-            description: Some("Test description 1".to_string()),
-            price_usd: 100000.0,
-            address: "123 Test St".to_string(),
-            covered_size: Some(100.0),
-            rooms: Some(2),
-            antiquity: Some(5),
-            url: "https://example.com/test1".to_string(),
-            status: DbPropertyStatus::new(STATUS_ACTIVE),
-            created_at: now.clone(),
-            updated_at: now.clone(),
-        };
+        let an_hour_ago = DbTimestamp::from(Utc::now() - chrono::Duration::hours(1));
+        let mut palermo = saved_search(Some("Palermo"), Some(150000.0));
+        palermo.last_checked_at = Some(an_hour_ago.clone());
+        db.add_saved_search(&mut palermo).await.unwrap();
+        assert!(palermo.id > 0);
+        assert_eq!(db.list_saved_searches().await.unwrap().len(), 1);
 
-        let mut property2 = Property {
-            id: 0,
-            external_id: "test-2".to_string(),
-            source: "test".to_string(),
-            property_type: Some("house".to_string()),
-            district: "Test District".to_string(),
-            title: "Test Property 2".to_string(),
-            description: Some("Test description 2".to_string()),
-            price_usd: 200000.0,
-            address: "456 Test St".to_string(),
-            covered_size: Some(150.0),
-            rooms: Some(3),
-            antiquity: Some(10),
-            url: "https://example.com/test2".to_string(),
-            status: DbPropertyStatus::new(STATUS_SOLD),
-            created_at: now.clone(),
-            updated_at: now.clone(),
+        let palermo_property = |external_id: &str| {
+            let mut property = test_property(external_id);
+            property.district = "Palermo".to_string();
+            property
         };
+        let mut old = palermo_property("test-0");
+        old.created_at = DbTimestamp::from_rfc3339("2020-01-01T00:00:00Z").unwrap();
+        let matching = palermo_property("test-1");
+        let mut too_expensive = palermo_property("test-2");
+        too_expensive.raw_price = 200000.0;
+        let mut elsewhere = test_property("test-3");
+        elsewhere.district = "Recoleta".to_string();
+        let mut sold = palermo_property("test-4");
+        sold.status = DbPropertyStatus::new(STATUS_SOLD);
+        let mut rental = palermo_property("test-5");
+        rental.operation = Operation::Rent;
+        let mut listings = vec![old, matching, too_expensive, elsewhere, sold, rental];
+        for property in listings.iter_mut() {
+            property.property_type = Some(PropertyType::Apartment);
+        }
+        db.save_properties_batch(&mut listings).await.unwrap();
 
-        db.save_property(&mut property1).await.unwrap();
-        db.save_property(&mut property2).await.unwrap();
+        let matches = db.matching_new_properties().await.unwrap();
+        assert_eq!(matches.iter().map(|p| p.id).collect::<Vec<_>>(), vec![listings[1].id]);
 
-        // Test query builder
-        let active_properties = PropertyQueryBuilder::new()
-            .with_status(DbPropertyStatus::new(STATUS_ACTIVE))
-            .execute(&db.pool)
+        // A broader search checked just now has nothing new to report
+        db.add_saved_search(&mut saved_search(None, Some(150000.0))).await.unwrap();
+        let matches = db.matching_new_properties().await.unwrap();
+        assert_eq!(matches.iter().map(|p| p.id).collect::<Vec<_>>(), vec![listings[1].id]);
+
+        // Checked an hour ago, it matches the Recoleta listing too, without duplicates
+        let mut broader = saved_search(None, Some(150000.0));
+        broader.last_checked_at = Some(an_hour_ago);
+        db.add_saved_search(&mut broader).await.unwrap();
+        let matches = db.matching_new_properties().await.unwrap();
+        assert_eq!(matches.len(), 2);
+        assert!(matches.iter().all(|p| p.price_usd <= 150000.0));
+
+        db.mark_saved_searches_checked(DbTimestamp::now()).await.unwrap();
+        assert!(db.matching_new_properties().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_reads_and_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::with_options(dir.path().join("brea.db"), DatabaseOptions::default())
             .await
             .unwrap();
+        migrations::apply_migrations(db.pool()).await.unwrap();
 
-        assert_eq!(active_properties.len(), 1);
-        assert_eq!(active_properties[0].external_id, "test-1");
+        let tasks: Vec<_> = (0..32)
+            .map(|i| {
+                let db = db.clone();
+                tokio::spawn(async move {
+                    let mut property = test_property(&format!("test-{}", i));
+                    db.save_property(&mut property).await.unwrap();
+                    db.get_properties().await.unwrap();
+                    property.raw_price += 1000.0;
+                    db.save_property(&mut property).await.unwrap();
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
 
-        let sold_properties = PropertyQueryBuilder::new()
-            .with_status(DbPropertyStatus::new(STATUS_SOLD))
-            .execute(&db.pool)
+        assert_eq!(db.get_properties().await.unwrap().len(), 32);
+        let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(db.pool())
             .await
             .unwrap();
-
-        assert_eq!(sold_properties.len(), 1);
-        assert_eq!(sold_properties[0].external_id, "test-2");
+        assert_eq!(journal_mode, "wal");
     }
 
     #[tokio::test]
-    async fn test_type_safe_status_transitions() {
-        let db = test_connection().await;
-        let now = DbTimestamp::now();
-
-        // Create a property
-        let mut property = Property {
-            id: 0,
-            external_id: "test-123".to_string(),
-            source: "test".to_string(),
-            property_type: Some("apartment".to_string()),
-            district: "Test District".to_string(),
-            title: "Test Property".to_string(),
-            description: Some("Test description".to_string()),
-            price_usd: 100000.0,
-            address: "123 Test St".to_string(),
-            covered_size: Some(100.0),
-            rooms: Some(2),
-            antiquity: Some(5),
-            url: "https://example.com/test".to_string(),
-            status: DbPropertyStatus::new(STATUS_ACTIVE),
-            created_at: now.clone(),
-            updated_at: now.clone(),
-        };
+    async fn test_in_memory_shared_across_connections() {
+        let db = Database::new_in_memory_shared().await.unwrap();
+        migrations::apply_migrations(db.pool()).await.unwrap();
 
-        db.save_property(&mut property).await.unwrap();
-
-        // Test status transitions
-        db.mark_property_as_sold(property.id).await.unwrap();
-        let sold = db.get_property(property.id).await.unwrap().unwrap();
-        assert_eq!(sold.status.as_str(), STATUS_SOLD);
+        // Hold several connections at once so each is a different one
+        let mut connections = Vec::new();
+        for _ in 0..DatabaseOptions::default().max_connections {
+            connections.push(db.pool().acquire().await.unwrap());
+        }
+        for connection in &mut connections {
+            let tables: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE name = 'properties'")
+                .fetch_one(&mut **connection)
+                .await
+                .unwrap();
+            assert_eq!(tables, 1);
+        }
+        drop(connections);
 
-        db.mark_property_as_removed(property.id).await.unwrap();
-        let removed = db.get_property(property.id).await.unwrap().unwrap();
-        assert_eq!(removed.status.as_str(), STATUS_REMOVED);
+        // Each shared database is its own
+        let other = Database::new_in_memory_shared().await.unwrap();
+        let tables: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master")
+            .fetch_one(other.pool())
+            .await
+            .unwrap();
+        assert_eq!(tables, 0);
     }
 
     #[tokio::test]
-    async fn test_type_safe_timestamp() {
-        let db = test_connection().await;
-        let now = DbTimestamp::now();
+    async fn test_vacuum_after_heavy_churn() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("brea.db");
+        let db = Database::with_options(&path, DatabaseOptions::default()).await.unwrap();
+        migrations::apply_migrations(db.pool()).await.unwrap();
 
-        // Create a property
-        let mut property = Property {
-            id: 0,
-            external_id: "test-123".to_string(),
-            source: "test".to_string(),
-            property_type: Some("apartment".to_string()),
-            district: "Test District".to_string(),
-            title: "Test Property".to_string(),
-            description: Some("Test description".to_string()),
-            price_usd: 100000.0,
-            address: "123 Test St".to_string(),
-            covered_size: Some(100.0),
-            rooms: Some(2),
-            antiquity: Some(5),
-            url: "https://example.com/test".to_string(),
-            status: DbPropertyStatus::new(STATUS_ACTIVE),
-            created_at: now.clone(),
-            updated_at: now.clone(),
-        };
+        let mut batch: Vec<Property> = (0..2000)
+            .map(|i| {
+                let mut property = test_property(&format!("test-{}", i));
+                property.description = Some("x".repeat(500));
+                property
+            })
+            .collect();
+        db.save_properties_batch(&mut batch).await.unwrap();
+        sqlx::query("DELETE FROM properties_fts").execute(db.pool()).await.unwrap();
+        sqlx::query("DELETE FROM property_price_history").execute(db.pool()).await.unwrap();
+        sqlx::query("DELETE FROM properties").execute(db.pool()).await.unwrap();
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(db.pool()).await.unwrap();
+        let before = std::fs::metadata(&path).unwrap().len();
 
-        db.save_property(&mut property).await.unwrap();
+        db.vacuum().await.unwrap();
+        db.analyze().await.unwrap();
 
-        // Test timestamp handling
-        let retrieved = db.get_property(property.id).await.unwrap().unwrap();
-        assert_eq!(retrieved.created_at.to_string(), now.to_string());
-        assert_eq!(retrieved.updated_at.to_string(), now.to_string());
+        assert!(std::fs::metadata(&path).unwrap().len() < before);
+        assert!(db.get_properties().await.unwrap().is_empty());
     }
-} 
\ No newline at end of file
+}
@@ -1,7 +1,24 @@
 use super::types::{DbPropertyStatus, DbTimestamp};
-use crate::{Property, PropertyImage, Result};
+use crate::districts::normalize_district;
+use crate::{BreaError, Operation, Property, PropertyImage, PropertyStatus, PropertyType, Result};
 use chrono::{DateTime, Utc};
-use sqlx::{sqlite::SqlitePool, QueryBuilder, Row, FromRow, sqlite::Sqlite};
+use sqlx::{sqlite::SqlitePool, Execute, QueryBuilder, Row, FromRow, sqlite::Sqlite};
+
+/// Columns that may be used to sort property listings. `ORDER BY` can't take a
+/// bound parameter, so anything else is rejected instead of being spliced
+/// into the query.
+pub const SORTABLE_FIELDS: &[&str] = &[
+    "id",
+    "price_usd",
+    "covered_size",
+    "rooms",
+    "bathrooms",
+    "antiquity",
+    "district",
+    "title",
+    "created_at",
+    "updated_at",
+];
 
 pub struct PropertyQueryBuilder<'a> {
     builder: QueryBuilder<'a, Sqlite>,
@@ -19,12 +36,44 @@ impl<'a> PropertyQueryBuilder<'a> {
         self
     }
 
+    /// Matches on the canonical encoding. Rows written by older versions hold
+    /// the display form ("Commercial Premises"), which only differs in case.
+    pub fn with_property_type(mut self, property_type: &PropertyType) -> Self {
+        self.builder.push(" AND LOWER(property_type) = ");
+        self.builder.push_bind(property_type.clone());
+        self
+    }
+
+    /// Listings for sale, for rent or for temporary rent.
+    pub fn with_operation(mut self, operation: Operation) -> Self {
+        self.builder.push(" AND operation = ");
+        self.builder.push_bind(operation);
+        self
+    }
+
+    /// Matches `district` under any of its spellings, see
+    /// [`normalize_district`].
+    pub fn with_district(mut self, district: &'a str) -> Self {
+        self.builder.push(" AND district = ");
+        self.builder.push_bind(normalize_district(district));
+        self
+    }
+
+    /// Matches the raw status string as stored. Nothing checks it is a known
+    /// status, so a typo silently matches no rows; prefer
+    /// [`Self::with_status_typed`].
     pub fn with_status(mut self, status: DbPropertyStatus) -> Self {
         self.builder.push(" AND status = ");
         self.builder.push_bind(status);
         self
     }
 
+    pub fn with_status_typed(mut self, status: PropertyStatus) -> Self {
+        self.builder.push(" AND status = ");
+        self.builder.push_bind(status);
+        self
+    }
+
     pub fn with_price_range(mut self, min: Option<f64>, max: Option<f64>) -> Self {
         if let Some(min_price) = min {
             self.builder.push(" AND price_usd >= ");
@@ -49,6 +98,128 @@ impl<'a> PropertyQueryBuilder<'a> {
         self
     }
 
+    /// Inclusive bounds on the number of rooms; listings with no room count
+    /// never match once a bound is set.
+    pub fn with_rooms_range(mut self, min: Option<i32>, max: Option<i32>) -> Self {
+        if let Some(min) = min {
+            self.builder.push(" AND rooms >= ");
+            self.builder.push_bind(min);
+        }
+        if let Some(max) = max {
+            self.builder.push(" AND rooms <= ");
+            self.builder.push_bind(max);
+        }
+        self
+    }
+
+    /// Inclusive bounds on the number of bathrooms, toilettes not counted;
+    /// listings with no bathroom count never match once a bound is set.
+    pub fn with_bathrooms_range(mut self, min: Option<i32>, max: Option<i32>) -> Self {
+        if let Some(min) = min {
+            self.builder.push(" AND bathrooms >= ");
+            self.builder.push_bind(min);
+        }
+        if let Some(max) = max {
+            self.builder.push(" AND bathrooms <= ");
+            self.builder.push_bind(max);
+        }
+        self
+    }
+
+    /// Inclusive bounds on the monthly expensas in pesos; listings that
+    /// don't publish them never match once a bound is set.
+    pub fn with_expenses_range(mut self, min: Option<f64>, max: Option<f64>) -> Self {
+        if let Some(min) = min {
+            self.builder.push(" AND expenses_ars >= ");
+            self.builder.push_bind(min);
+        }
+        if let Some(max) = max {
+            self.builder.push(" AND expenses_ars <= ");
+            self.builder.push_bind(max);
+        }
+        self
+    }
+
+    /// Listings that say whether they come with parking and match `has_parking`.
+    pub fn with_parking(mut self, has_parking: bool) -> Self {
+        self.builder.push(" AND has_parking = ");
+        self.builder.push_bind(has_parking);
+        self
+    }
+
+    /// Inclusive bounds on the building age in years; listings with unknown
+    /// antiquity never match once a bound is set.
+    pub fn with_antiquity_range(mut self, min: Option<i32>, max: Option<i32>) -> Self {
+        if let Some(min) = min {
+            self.builder.push(" AND antiquity >= ");
+            self.builder.push_bind(min);
+        }
+        if let Some(max) = max {
+            self.builder.push(" AND antiquity <= ");
+            self.builder.push_bind(max);
+        }
+        self
+    }
+
+    /// Filters on `price_usd / covered_size`; listings without a covered
+    /// size never match a price per m² range.
+    pub fn with_price_per_m2_range(mut self, min: Option<f64>, max: Option<f64>) -> Self {
+        if min.is_none() && max.is_none() {
+            return self;
+        }
+        self.builder.push(" AND covered_size > 0");
+        if let Some(min) = min {
+            self.builder.push(" AND price_usd / covered_size >= ");
+            self.builder.push_bind(min);
+        }
+        if let Some(max) = max {
+            self.builder.push(" AND price_usd / covered_size <= ");
+            self.builder.push_bind(max);
+        }
+        self
+    }
+
+    /// Properties carrying `tag`, see [`crate::Database::add_tag`].
+    pub fn with_tag(mut self, tag: &'a str) -> Self {
+        self.builder.push(" AND id IN (SELECT property_id FROM property_tags WHERE tag = ");
+        self.builder.push_bind(tag.trim());
+        self.builder.push(")");
+        self
+    }
+
+    pub fn with_created_after(mut self, since: DateTime<Utc>) -> Self {
+        self.builder.push(" AND julianday(created_at) > julianday(");
+        self.builder.push_bind(since.to_rfc3339());
+        self.builder.push(")");
+        self
+    }
+
+    /// Inclusive bounds on when listings were first stored.
+    pub fn with_created_range(self, from: Option<DbTimestamp>, to: Option<DbTimestamp>) -> Self {
+        self.with_timestamp_range("created_at", from, to)
+    }
+
+    /// Inclusive bounds on when listings last changed.
+    pub fn with_updated_range(self, from: Option<DbTimestamp>, to: Option<DbTimestamp>) -> Self {
+        self.with_timestamp_range("updated_at", from, to)
+    }
+
+    /// Timestamps are compared through `julianday`, so rows written with
+    /// another offset or precision still compare as instants.
+    fn with_timestamp_range(mut self, column: &str, from: Option<DbTimestamp>, to: Option<DbTimestamp>) -> Self {
+        if let Some(from) = from {
+            self.builder.push(format!(" AND julianday({}) >= julianday(", column));
+            self.builder.push_bind(from);
+            self.builder.push(")");
+        }
+        if let Some(to) = to {
+            self.builder.push(format!(" AND julianday({}) <= julianday(", column));
+            self.builder.push_bind(to);
+            self.builder.push(")");
+        }
+        self
+    }
+
     pub fn with_limit(mut self, limit: Option<i64>) -> Self {
         if let Some(limit) = limit {
             self.builder.push(" LIMIT ");
@@ -65,13 +236,25 @@ impl<'a> PropertyQueryBuilder<'a> {
         self
     }
 
-    pub fn order_by(mut self, field: &str, desc: bool) -> Self {
+    pub fn order_by(mut self, field: &str, desc: bool) -> Result<Self> {
+        if !SORTABLE_FIELDS.contains(&field) {
+            return Err(BreaError::InvalidArgument(format!(
+                "cannot sort by '{}', expected one of: {}",
+                field,
+                SORTABLE_FIELDS.join(", ")
+            )));
+        }
         self.builder.push(" ORDER BY ");
         self.builder.push(field);
         if desc {
             self.builder.push(" DESC");
         }
-        self
+        // Ties fall back to insertion order, so pages taken with a limit and
+        // offset neither repeat nor skip listings with equal values
+        if field != "id" {
+            self.builder.push(", id");
+        }
+        Ok(self)
     }
 
     pub fn with_external_ids_not_in(mut self, ids: &'a [&'a str]) -> Self {
@@ -99,6 +282,26 @@ impl<'a> PropertyQueryBuilder<'a> {
         let rows = query.fetch_all(pool).await?;
         Ok(rows)
     }
+
+    /// Number of rows [`Self::execute`] would return, counted by SQLite
+    /// instead of fetching them.
+    pub async fn count(mut self, pool: &SqlitePool) -> Result<i64> {
+        // Placeholders are positional, so the query can be wrapped as is and
+        // keep its bound arguments
+        let sql = format!("SELECT COUNT(*) FROM ({})", self.builder.sql());
+        let arguments = self.builder.build().take_arguments().unwrap_or_default();
+        let count = sqlx::query_scalar_with(&sql, arguments).fetch_one(pool).await?;
+        Ok(count)
+    }
+
+    /// SQLite's plan for the query, one step per line.
+    #[cfg(test)]
+    pub(crate) async fn query_plan(mut self, pool: &SqlitePool) -> Result<String> {
+        let sql = format!("EXPLAIN QUERY PLAN {}", self.builder.sql());
+        let arguments = self.builder.build().take_arguments().unwrap_or_default();
+        let rows = sqlx::query_with(&sql, arguments).fetch_all(pool).await?;
+        Ok(rows.iter().map(|row| row.get::<String, _>("detail")).collect::<Vec<_>>().join("\n"))
+    }
 }
 
 pub struct PropertyImageQueryBuilder<'a> {
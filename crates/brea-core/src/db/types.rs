@@ -2,7 +2,8 @@ use chrono::{DateTime, Utc};
 use std::fmt;
 use thiserror::Error;
 use sqlx::{Type, Encode, sqlite::{Sqlite, SqliteArgumentValue}, Database, Decode};
-use serde::{Serialize, Deserialize};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Error, Debug)]
 pub enum TimestampError {
@@ -69,7 +70,11 @@ pub const STATUS_REMOVED: &str = "removed";
 
 pub static VALID_STATUSES: &[&str] = &[STATUS_ACTIVE, STATUS_SOLD, STATUS_REMOVED];
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A UTC timestamp, stored and serialized as RFC 3339.
+///
+/// Deserializing also accepts Unix epoch seconds, so records exported with
+/// epoch timestamps read back the same as those written as RFC 3339.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct DbTimestamp(DateTime<Utc>);
 
 impl DbTimestamp {
@@ -86,6 +91,48 @@ impl DbTimestamp {
     pub fn inner(&self) -> &DateTime<Utc> {
         &self.0
     }
+
+    /// Seconds since the Unix epoch, dropping any fraction of a second.
+    pub fn as_unix(&self) -> i64 {
+        self.0.timestamp()
+    }
+}
+
+impl<'de> Deserialize<'de> for DbTimestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TimestampVisitor;
+
+        impl Visitor<'_> for TimestampVisitor {
+            type Value = DbTimestamp;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an RFC 3339 timestamp or Unix epoch seconds")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<DbTimestamp, E> {
+                DbTimestamp::from_rfc3339(s).map_err(E::custom)
+            }
+
+            fn visit_i64<E: de::Error>(self, seconds: i64) -> Result<DbTimestamp, E> {
+                DateTime::from_timestamp(seconds, 0)
+                    .map(DbTimestamp)
+                    .ok_or_else(|| E::custom(format!("epoch seconds out of range: {}", seconds)))
+            }
+
+            fn visit_u64<E: de::Error>(self, seconds: u64) -> Result<DbTimestamp, E> {
+                let seconds = i64::try_from(seconds).map_err(|_| E::custom(format!("epoch seconds out of range: {}", seconds)))?;
+                self.visit_i64(seconds)
+            }
+        }
+
+        deserializer.deserialize_any(TimestampVisitor)
+    }
+}
+
+impl From<DateTime<Utc>> for DbTimestamp {
+    fn from(dt: DateTime<Utc>) -> Self {
+        Self(dt)
+    }
 }
 
 impl fmt::Display for DbTimestamp {
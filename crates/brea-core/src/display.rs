@@ -1,16 +1,14 @@
 use chrono::{DateTime, Utc};
 use colored::Colorize;
+use crate::PriceHistory;
 use std::fmt;
 
 pub struct PropertyDisplay {
     pub property: super::Property,
     pub price_history: Vec<(f64, DateTime<Utc>)>,
-}
-
-impl fmt::Display for PropertyDisplay {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_string())
-    }
+    graph_width: usize,
+    graph_height: usize,
+    smoothing: usize,
 }
 
 impl PropertyDisplay {
@@ -18,62 +16,77 @@ impl PropertyDisplay {
         Self {
             property,
             price_history,
+            graph_width: 40,
+            graph_height: 10,
+            smoothing: 0,
         }
     }
 
-    fn create_ascii_graph(data: &[(f64, DateTime<Utc>)], width: usize, height: usize) -> String {
-        if data.is_empty() {
-            return String::new();
-        }
-
-        let mut graph = vec![vec![' '; width]; height];
-        let max_price = data.iter().map(|(p, _)| *p).fold(f64::NEG_INFINITY, f64::max);
-        let min_price = data.iter().map(|(p, _)| *p).fold(f64::INFINITY, f64::min);
-        let price_range = max_price - min_price;
-
-        for (i, (price, _)) in data.iter().enumerate() {
-            if i >= width {
-                break;
-            }
-            let normalized_height = if price_range > 0.0 {
-                ((price - min_price) / price_range * (height as f64 - 1.0)) as usize
-            } else {
-                height / 2
-            };
-            for y in 0..=normalized_height {
-                graph[y][i] = '█';
-            }
-        }
+    /// Size of the price history graph in characters and lines.
+    pub fn with_graph_dimensions(mut self, width: usize, height: usize) -> Self {
+        self.graph_width = width.max(1);
+        self.graph_height = height.max(1);
+        self
+    }
 
-        graph.into_iter()
-            .rev()
-            .map(|row| row.into_iter().collect::<String>())
-            .collect::<Vec<_>>()
-            .join("\n")
+    /// Graphs the moving median over `window` prices instead of the raw
+    /// ones, see [`PriceHistory::smoothed`].
+    pub fn with_smoothing(mut self, window: usize) -> Self {
+        self.smoothing = window;
+        self
     }
+}
 
-    pub fn to_string(&self) -> String {
-        let graph = Self::create_ascii_graph(&self.price_history, 40, 10);
+impl fmt::Display for PropertyDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let graph = if self.price_history.is_empty() {
+            String::new()
+        } else {
+            PriceHistory::new(self.price_history.clone())
+                .smoothed(self.smoothing)
+                .to_ascii_graph(self.graph_width, self.graph_height)
+        };
         
         let mut details = Vec::new();
         
         if let Some(size) = self.property.covered_size {
             details.push(format!("{:.1} m²", size));
         }
+
+        if let Some(price_per_m2) = self.property.price_per_m2() {
+            details.push(format!("${:.0}/m²", price_per_m2));
+        }
         
         if let Some(rooms) = self.property.rooms {
             details.push(format!("{} rooms", rooms));
         }
+
+        if let Some(bathrooms) = self.property.bathrooms {
+            details.push(format!("{} baths", bathrooms));
+        }
+
+        match self.property.has_parking {
+            Some(true) => details.push("parking".to_string()),
+            Some(false) => details.push("no parking".to_string()),
+            None => {}
+        }
+
+        if let Some(expenses) = self.property.expenses_ars {
+            details.push(format!("ARS {:.0}/month expensas", expenses));
+        }
         
-        if let Some(antiquity) = self.property.antiquity {
+        if self.property.under_construction == Some(true) {
+            details.push("under construction".to_string());
+        } else if let Some(antiquity) = self.property.antiquity {
             details.push(format!("{} years old", antiquity));
         }
         
         let details_str = details.join(" | ");
         let price_str = format!("${:.2}", self.property.price_usd);
         
-        format!(
-            "{}\n{}\n{}\n{}\n{}\n{}",
+        write!(
+            f,
+            "{}\n{}\n{}\n{}\nAddress: {} - {}{}\n{}",
             self.property.title.bright_white().bold(),
             self.property.description.as_deref().unwrap_or(""),
             format!("Price: {}", price_str).green(),
@@ -82,9 +95,14 @@ impl PropertyDisplay {
             } else {
                 String::new()
             },
-            format!("Address: {} - {}", self.property.address, self.property.district),
+            self.property.address,
+            self.property.district,
+            match &self.property.notes {
+                Some(notes) => format!("\nNotes: {}", notes),
+                None => String::new(),
+            },
             if !graph.is_empty() {
-                format!("\nPrice History:\n{}", graph)
+                format!("\nPrice History (kUSD):\n{}", graph)
             } else {
                 String::new()
             }
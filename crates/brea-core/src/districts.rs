@@ -0,0 +1,231 @@
+use crate::{BreaError, Result};
+
+/// Districts of the City of Buenos Aires (CABA) and Greater Buenos Aires
+/// (GBA) listings are published under, with the slug their results pages
+/// use on ArgenProp.
+pub const KNOWN_DISTRICTS: &[(&str, &str)] = &[
+    ("Capital Federal", "capital-federal"),
+    // CABA
+    ("Agronomía", "agronomia"),
+    ("Almagro", "almagro"),
+    ("Balvanera", "balvanera"),
+    ("Barracas", "barracas"),
+    ("Belgrano", "belgrano"),
+    ("Boedo", "boedo"),
+    ("Caballito", "caballito"),
+    ("Chacarita", "chacarita"),
+    ("Coghlan", "coghlan"),
+    ("Colegiales", "colegiales"),
+    ("Constitución", "constitucion"),
+    ("Flores", "flores"),
+    ("Floresta", "floresta"),
+    ("La Boca", "boca"),
+    ("La Paternal", "paternal"),
+    ("Liniers", "liniers"),
+    ("Mataderos", "mataderos"),
+    ("Monserrat", "monserrat"),
+    ("Monte Castro", "monte-castro"),
+    ("Nueva Pompeya", "nueva-pompeya"),
+    ("Núñez", "nunez"),
+    ("Palermo", "palermo"),
+    ("Parque Avellaneda", "parque-avellaneda"),
+    ("Parque Chacabuco", "parque-chacabuco"),
+    ("Parque Chas", "parque-chas"),
+    ("Parque Patricios", "parque-patricios"),
+    ("Puerto Madero", "puerto-madero"),
+    ("Recoleta", "recoleta"),
+    ("Retiro", "retiro"),
+    ("Saavedra", "saavedra"),
+    ("San Cristóbal", "san-cristobal"),
+    ("San Nicolás", "san-nicolas"),
+    ("San Telmo", "san-telmo"),
+    ("Vélez Sarsfield", "velez-sarsfield"),
+    ("Versalles", "versalles"),
+    ("Villa Crespo", "villa-crespo"),
+    ("Villa del Parque", "villa-del-parque"),
+    ("Villa Devoto", "villa-devoto"),
+    ("Villa General Mitre", "villa-general-mitre"),
+    ("Villa Lugano", "villa-lugano"),
+    ("Villa Luro", "villa-luro"),
+    ("Villa Ortúzar", "villa-ortuzar"),
+    ("Villa Pueyrredón", "villa-pueyrredon"),
+    ("Villa Real", "villa-real"),
+    ("Villa Riachuelo", "villa-riachuelo"),
+    ("Villa Santa Rita", "villa-santa-rita"),
+    ("Villa Soldati", "villa-soldati"),
+    ("Villa Urquiza", "villa-urquiza"),
+    // GBA
+    ("Almirante Brown", "almirante-brown"),
+    ("Avellaneda", "avellaneda"),
+    ("Berazategui", "berazategui"),
+    ("Escobar", "escobar"),
+    ("Esteban Echeverría", "esteban-echeverria"),
+    ("Ezeiza", "ezeiza"),
+    ("Florencio Varela", "florencio-varela"),
+    ("General San Martín", "general-san-martin"),
+    ("Hurlingham", "hurlingham"),
+    ("Ituzaingó", "ituzaingo"),
+    ("José C. Paz", "jose-c-paz"),
+    ("La Matanza", "la-matanza"),
+    ("La Plata", "la-plata"),
+    ("Lanús", "lanus"),
+    ("Lomas de Zamora", "lomas-de-zamora"),
+    ("Malvinas Argentinas", "malvinas-argentinas"),
+    ("Merlo", "merlo"),
+    ("Moreno", "moreno"),
+    ("Morón", "moron"),
+    ("Pilar", "pilar"),
+    ("Quilmes", "quilmes"),
+    ("San Fernando", "san-fernando"),
+    ("San Isidro", "san-isidro"),
+    ("San Miguel", "san-miguel"),
+    ("Tigre", "tigre"),
+    ("Tres de Febrero", "tres-de-febrero"),
+    ("Vicente López", "vicente-lopez"),
+];
+
+/// Most edits between an unknown district and a known one for the latter
+/// to be suggested
+const MAX_SUGGESTION_DISTANCE: usize = 3;
+
+/// Other names in use for a district, and the name they are stored under.
+/// Both sides are already normalized.
+const ALIASES: &[(&str, &str)] = &[
+    ("caba", "capital federal"),
+    ("ciudad de buenos aires", "capital federal"),
+    ("ciudad autonoma de buenos aires", "capital federal"),
+];
+
+/// Canonical form of a district name: lowercase, without accents, with
+/// single spaces between words, and with known aliases resolved.
+///
+/// "Núñez" becomes "nunez", and "CABA" and "Ciudad de Buenos Aires" both
+/// become "capital federal".
+pub fn normalize_district(district: &str) -> String {
+    let normalized = district
+        .chars()
+        .map(strip_accent)
+        .collect::<String>()
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == normalized)
+        .map(|(_, canonical)| canonical.to_string())
+        .unwrap_or(normalized)
+}
+
+/// Slug of `district` in [`KNOWN_DISTRICTS`], matched on its name, any
+/// alias [`normalize_district`] knows or the slug itself, so "Núñez",
+/// "La Boca", "boca" and "CABA" are all found.
+///
+/// Unknown districts are an [`BreaError::InvalidArgument`], which names the
+/// closest known district when one is only a few typos away.
+pub fn validate_district(district: &str) -> Result<String> {
+    let normalized = normalize_district(district);
+    let matches = |name: &str, slug: &str| normalize_district(name) == normalized || slug.replace('-', " ") == normalized;
+    if let Some((_, slug)) = KNOWN_DISTRICTS.iter().find(|(name, slug)| matches(name, slug)) {
+        return Ok(slug.to_string());
+    }
+
+    let closest = KNOWN_DISTRICTS
+        .iter()
+        .map(|(name, _)| (levenshtein(&normalize_district(name), &normalized), *name))
+        .min_by_key(|(distance, _)| *distance)
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE);
+    Err(BreaError::InvalidArgument(match closest {
+        Some((_, name)) => format!("unknown district '{}', did you mean '{}'?", district, name),
+        None => format!("unknown district '{}'", district),
+    }))
+}
+
+/// Number of single character insertions, deletions and substitutions
+/// turning `a` into `b`.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn strip_accent(c: char) -> char {
+    match c {
+        'á' | 'à' | 'ä' | 'â' => 'a',
+        'é' | 'è' | 'ë' | 'ê' => 'e',
+        'í' | 'ì' | 'ï' | 'î' => 'i',
+        'ó' | 'ò' | 'ö' | 'ô' => 'o',
+        'ú' | 'ù' | 'ü' | 'û' => 'u',
+        'ñ' => 'n',
+        'Á' | 'À' | 'Ä' | 'Â' => 'A',
+        'É' | 'È' | 'Ë' | 'Ê' => 'E',
+        'Í' | 'Ì' | 'Ï' | 'Î' => 'I',
+        'Ó' | 'Ò' | 'Ö' | 'Ô' => 'O',
+        'Ú' | 'Ù' | 'Ü' | 'Û' => 'U',
+        'Ñ' => 'N',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strips_accents() {
+        assert_eq!(normalize_district("Núñez"), "nunez");
+        assert_eq!(normalize_district("San Martín"), "san martin");
+        assert_eq!(normalize_district("  Villa   Pueyrredón "), "villa pueyrredon");
+    }
+
+    #[test]
+    fn test_resolves_aliases() {
+        for name in ["CABA", "Capital Federal", "capital-federal", "Ciudad de Buenos Aires", "Ciudad Autónoma de Buenos Aires"] {
+            assert_eq!(normalize_district(name), "capital federal", "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_validate_known_district() {
+        assert_eq!(validate_district("Palermo").unwrap(), "palermo");
+        assert_eq!(validate_district("villa crespo").unwrap(), "villa-crespo");
+        assert_eq!(validate_district("villa-crespo").unwrap(), "villa-crespo");
+        assert_eq!(validate_district("Vicente López").unwrap(), "vicente-lopez");
+    }
+
+    #[test]
+    fn test_validate_district_alias() {
+        assert_eq!(validate_district("Núñez").unwrap(), "nunez");
+        assert_eq!(validate_district("La Boca").unwrap(), "boca");
+        assert_eq!(validate_district("Paternal").unwrap(), "paternal");
+        assert_eq!(validate_district("CABA").unwrap(), "capital-federal");
+    }
+
+    #[test]
+    fn test_validate_district_suggests_closest() {
+        let error = validate_district("Palremo").unwrap_err().to_string();
+        assert!(error.contains("did you mean 'Palermo'?"), "{}", error);
+        let error = validate_district("belgrno").unwrap_err().to_string();
+        assert!(error.contains("did you mean 'Belgrano'?"), "{}", error);
+
+        let error = validate_district("Springfield").unwrap_err().to_string();
+        assert!(!error.contains("did you mean"), "{}", error);
+    }
+
+    #[test]
+    fn test_keeps_articles() {
+        // Argenprop drops some leading articles in its URLs, but the name
+        // itself keeps them
+        assert_eq!(normalize_district("La Boca"), "la boca");
+    }
+}
@@ -1,18 +0,0 @@
-use std::io;
-use thiserror::Error;
-
-#[derive(Debug, Error)]
-pub enum BreaError {
-    #[error("Database error: {0}")]
-    Database(#[from] sqlx::Error),
-    #[error("IO error: {0}")]
-    Io(#[from] io::Error),
-    #[error("URL parse error: {0}")]
-    UrlParse(#[from] url::ParseError),
-    #[error("Request error: {0}")]
-    Request(#[from] reqwest::Error),
-    #[error("Custom error: {0}")]
-    Custom(String),
-}
-
-pub type Result<T> = std::result::Result<T, BreaError>; 
\ No newline at end of file
@@ -0,0 +1,206 @@
+use crate::db::types::DbTimestamp;
+use crate::{PriceObservation, Property};
+use chrono::SecondsFormat;
+use serde::Serialize;
+
+/// How exports write `created_at`, `updated_at` and `last_seen_at`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// RFC 3339, e.g. `2024-03-20T00:00:00+00:00`
+    #[default]
+    Rfc3339,
+    /// Whole seconds since the Unix epoch, e.g. `1710892800`
+    Unix,
+}
+
+impl TimestampFormat {
+    pub fn format(self, timestamp: &DbTimestamp) -> String {
+        match self {
+            TimestampFormat::Rfc3339 => timestamp.to_string(),
+            TimestampFormat::Unix => timestamp.as_unix().to_string(),
+        }
+    }
+}
+
+/// `property` as one JSON Lines record: the property's own serialization,
+/// with its timestamps as epoch numbers when `timestamps` is
+/// [`TimestampFormat::Unix`].
+pub fn json_record(property: &Property, timestamps: TimestampFormat) -> serde_json::Result<serde_json::Value> {
+    let mut record = serde_json::to_value(property)?;
+    if timestamps == TimestampFormat::Unix {
+        record["created_at"] = property.created_at.as_unix().into();
+        record["updated_at"] = property.updated_at.as_unix().into();
+        record["last_seen_at"] = property.last_seen_at.as_unix().into();
+    }
+    Ok(record)
+}
+
+/// A price history, oldest price first, as the `price_history` array of a
+/// JSON Lines record, with `observed_at` written as `timestamps` says.
+pub fn price_history_record(history: &[PriceObservation], timestamps: TimestampFormat) -> serde_json::Value {
+    history
+        .iter()
+        .map(|observation| {
+            let observed_at: serde_json::Value = match timestamps {
+                TimestampFormat::Rfc3339 => observation.observed_at.to_rfc3339_opts(SecondsFormat::AutoSi, true).into(),
+                TimestampFormat::Unix => observation.observed_at.timestamp().into(),
+            };
+            serde_json::json!({ "price_usd": observation.price_usd, "observed_at": observed_at })
+        })
+        .collect()
+}
+
+/// One row of a CSV export.
+///
+/// Columns come out in field order and are named after the fields, so this
+/// struct is the file format: add new columns at the end and don't rename or
+/// reorder the existing ones. Enums are written as their display strings and
+/// the internal `id` is left out, since it means nothing outside the database.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PropertyExport {
+    pub source: String,
+    pub external_id: String,
+    pub operation: String,
+    pub property_type: String,
+    pub status: String,
+    pub title: String,
+    pub district: String,
+    pub address: String,
+    pub price_usd: f64,
+    pub raw_price: f64,
+    pub currency: String,
+    pub covered_size: Option<f64>,
+    pub rooms: Option<i32>,
+    pub bathrooms: Option<i32>,
+    pub antiquity: Option<i32>,
+    pub under_construction: Option<bool>,
+    pub has_parking: Option<bool>,
+    pub expenses_ars: Option<f64>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub url: String,
+    pub description: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub last_seen_at: String,
+}
+
+impl From<&Property> for PropertyExport {
+    fn from(property: &Property) -> Self {
+        Self::new(property, TimestampFormat::default())
+    }
+}
+
+impl PropertyExport {
+    pub fn new(property: &Property, timestamps: TimestampFormat) -> Self {
+        Self {
+            source: property.source.clone(),
+            external_id: property.external_id.clone(),
+            operation: property.operation.to_string(),
+            property_type: property.property_type.as_ref().map(ToString::to_string).unwrap_or_default(),
+            status: property.status.to_string(),
+            title: property.title.clone(),
+            district: property.district.clone(),
+            address: property.address.clone(),
+            price_usd: property.price_usd,
+            raw_price: property.raw_price,
+            currency: property.currency.to_string(),
+            covered_size: property.covered_size,
+            rooms: property.rooms,
+            bathrooms: property.bathrooms,
+            antiquity: property.antiquity,
+            under_construction: property.under_construction,
+            has_parking: property.has_parking,
+            expenses_ars: property.expenses_ars,
+            latitude: property.latitude,
+            longitude: property.longitude,
+            url: property.url.clone(),
+            description: property.description.clone().unwrap_or_default(),
+            created_at: timestamps.format(&property.created_at),
+            updated_at: timestamps.format(&property.updated_at),
+            last_seen_at: timestamps.format(&property.last_seen_at),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::types::DbPropertyStatus;
+    use crate::{Currency, Operation, PropertyType};
+
+    fn exported_property() -> Property {
+        let timestamp = DbTimestamp::from_rfc3339("2024-03-20T00:00:00Z").unwrap();
+        Property {
+            id: 42,
+            external_id: "test-123".to_string(),
+            source: "argenprop".to_string(),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::CommercialPremises),
+            district: "palermo".to_string(),
+            title: "Local en Palermo".to_string(),
+            description: None,
+            price_usd: 100000.0,
+            raw_price: 100000.0,
+            currency: Currency::Usd,
+            address: "Honduras 4800".to_string(),
+            latitude: None,
+            longitude: None,
+            covered_size: Some(80.0),
+            rooms: None,
+            antiquity: Some(30),
+            under_construction: Some(false),
+            bathrooms: Some(1),
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
+            url: "https://www.argenprop.com/local--123".to_string(),
+            status: DbPropertyStatus::new("active"),
+            created_at: timestamp.clone(),
+            updated_at: timestamp.clone(),
+            last_seen_at: timestamp,
+        }
+    }
+
+    #[test]
+    fn test_csv_columns() {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.serialize(PropertyExport::from(&exported_property())).unwrap();
+        let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "source,external_id,operation,property_type,status,title,district,address,price_usd,raw_price,currency,\
+             covered_size,rooms,bathrooms,antiquity,under_construction,has_parking,expenses_ars,latitude,longitude,\
+             url,description,created_at,updated_at,last_seen_at"
+        );
+        let row = lines.next().unwrap();
+        assert!(row.starts_with("argenprop,test-123,"));
+        assert!(row.contains(",Commercial Premises,active,"));
+        assert!(!row.contains("42"));
+    }
+
+    #[test]
+    fn test_timestamps_round_trip_in_both_formats() {
+        let property = exported_property();
+        assert_eq!(property.created_at.as_unix(), 1710892800);
+
+        let export = PropertyExport::new(&property, TimestampFormat::Unix);
+        assert_eq!(export.created_at, "1710892800");
+        assert_eq!(PropertyExport::from(&property).created_at, "2024-03-20T00:00:00+00:00");
+
+        for timestamps in [TimestampFormat::Rfc3339, TimestampFormat::Unix] {
+            let record = json_record(&property, timestamps).unwrap();
+            assert_eq!(record["last_seen_at"].is_number(), timestamps == TimestampFormat::Unix);
+            let read: Property = serde_json::from_value(record).unwrap();
+            assert_eq!(read.created_at, property.created_at);
+            assert_eq!(read.last_seen_at, property.last_seen_at);
+        }
+
+        // Records written before epoch timestamps existed still read back.
+        let old: DbTimestamp = serde_json::from_str("\"2024-03-20T00:00:00Z\"").unwrap();
+        assert_eq!(old, property.created_at);
+        assert!(serde_json::from_str::<DbTimestamp>("\"yesterday\"").is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use crate::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::warn;
+
+/// Base URL of the public OpenStreetMap Nominatim instance
+pub const NOMINATIM_URL: &str = "https://nominatim.openstreetmap.org";
+
+/// Resolves free-form addresses to coordinates.
+#[async_trait]
+pub trait Geocoder: Send + Sync {
+    /// Returns `(latitude, longitude)` for the address, or `None` when it
+    /// can't be located.
+    async fn geocode(&self, address: &str) -> Result<Option<(f64, f64)>>;
+}
+
+/// Geocoder backed by the Nominatim search API.
+///
+/// The public instance asks for at most one request per second and an
+/// identifying User-Agent, so requests made through the same geocoder are
+/// spaced by `min_interval` (one second unless changed).
+#[derive(Debug)]
+pub struct NominatimGeocoder {
+    client: reqwest::Client,
+    base_url: String,
+    min_interval: Duration,
+    last_request: Mutex<Option<Instant>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NominatimPlace {
+    lat: String,
+    lon: String,
+}
+
+impl NominatimGeocoder {
+    pub fn new() -> Self {
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("brea/", env!("CARGO_PKG_VERSION"), " (https://github.com/xaiki/brea)"))
+            .build()
+            .expect("failed to build HTTP client");
+
+        Self {
+            client,
+            base_url: NOMINATIM_URL.to_string(),
+            min_interval: Duration::from_secs(1),
+            last_request: Mutex::new(None),
+        }
+    }
+
+    /// Points the geocoder at another Nominatim instance, e.g. a self-hosted one.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_min_interval(mut self, min_interval: Duration) -> Self {
+        self.min_interval = min_interval;
+        self
+    }
+
+    async fn wait_for_turn(&self) {
+        let mut last_request = self.last_request.lock().await;
+        if let Some(last) = *last_request {
+            tokio::time::sleep_until(last + self.min_interval).await;
+        }
+        *last_request = Some(Instant::now());
+    }
+}
+
+impl Default for NominatimGeocoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Geocoder for NominatimGeocoder {
+    async fn geocode(&self, address: &str) -> Result<Option<(f64, f64)>> {
+        self.wait_for_turn().await;
+
+        let places: Vec<NominatimPlace> = self.client
+            .get(format!("{}/search", self.base_url))
+            .query(&[("q", address), ("format", "json"), ("limit", "1")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let Some(place) = places.into_iter().next() else {
+            return Ok(None);
+        };
+        match (place.lat.parse::<f64>(), place.lon.parse::<f64>()) {
+            (Ok(latitude), Ok(longitude)) => Ok(Some((latitude, longitude))),
+            _ => {
+                warn!("Nominatim returned unparseable coordinates for '{}': {:?}", address, place);
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn geocoder(server: &MockServer) -> NominatimGeocoder {
+        NominatimGeocoder::new()
+            .with_base_url(server.uri())
+            .with_min_interval(Duration::ZERO)
+    }
+
+    #[tokio::test]
+    async fn test_nominatim_geocode() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/search"))
+            .and(query_param("q", "Av. Santa Fe 3200, Palermo"))
+            .and(query_param("format", "json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"[{"lat": "-34.5875", "lon": "-58.4107", "display_name": "Avenida Santa Fe"}]"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let coordinates = geocoder(&server).geocode("Av. Santa Fe 3200, Palermo").await.unwrap();
+        assert_eq!(coordinates, Some((-34.5875, -58.4107)));
+    }
+
+    #[tokio::test]
+    async fn test_nominatim_no_match() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+            .mount(&server)
+            .await;
+
+        let coordinates = geocoder(&server).geocode("nowhere").await.unwrap();
+        assert_eq!(coordinates, None);
+    }
+
+    #[tokio::test]
+    async fn test_nominatim_spaces_requests() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+            .mount(&server)
+            .await;
+
+        let geocoder = geocoder(&server).with_min_interval(Duration::from_millis(100));
+        let start = std::time::Instant::now();
+        for _ in 0..3 {
+            geocoder.geocode("somewhere").await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
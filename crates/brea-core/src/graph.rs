@@ -1,7 +1,38 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Days, Months, NaiveTime, Utc};
 use serde::{Serialize, Deserialize};
 use rasciigraph::{plot, Config};
 
+/// Bucket size for [`PriceHistory::resample`]. Buckets are aligned on UTC
+/// midnights, Mondays and the first of the month respectively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResampleInterval {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl ResampleInterval {
+    /// Start of the bucket `at` falls in.
+    fn bucket_start(self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let date = at.date_naive();
+        let start = match self {
+            ResampleInterval::Daily => date,
+            ResampleInterval::Weekly => date - Days::new(date.weekday().num_days_from_monday().into()),
+            ResampleInterval::Monthly => date - Days::new((date.day() - 1).into()),
+        };
+        start.and_time(NaiveTime::MIN).and_utc()
+    }
+
+    /// Start of the bucket after the one starting at `start`.
+    fn next_bucket(self, start: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            ResampleInterval::Daily => start + Days::new(1),
+            ResampleInterval::Weekly => start + Days::new(7),
+            ResampleInterval::Monthly => start + Months::new(1),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PriceHistory {
     pub prices: Vec<(f64, DateTime<Utc>)>,
@@ -14,24 +45,89 @@ impl PriceHistory {
         }
     }
 
+    /// Replaces each price by the median of the `window` prices around it,
+    /// so a single mis-parsed price doesn't spike the graph. Windows of 0
+    /// or 1 leave the prices as they are, and windows longer than the series
+    /// are clamped to it.
+    pub fn smoothed(&self, window: usize) -> PriceHistory {
+        let window = window.min(self.prices.len());
+        if window <= 1 {
+            return self.clone();
+        }
+
+        // Windows are shifted inwards at both ends so they stay full
+        let last_start = self.prices.len() - window;
+        let prices = (0..self.prices.len())
+            .map(|i| {
+                let start = i.saturating_sub((window - 1) / 2).min(last_start);
+                let mut neighbours: Vec<f64> = self.prices[start..start + window].iter().map(|(price, _)| *price).collect();
+                neighbours.sort_by(f64::total_cmp);
+                let middle = neighbours.len() / 2;
+                let median = if neighbours.len().is_multiple_of(2) {
+                    (neighbours[middle - 1] + neighbours[middle]) / 2.0
+                } else {
+                    neighbours[middle]
+                };
+                (median, self.prices[i].1)
+            })
+            .collect();
+
+        PriceHistory { prices }
+    }
+
+    /// One price per `interval`, dated at the start of its bucket, from the
+    /// bucket of the first observation to that of the last. Each bucket
+    /// holds the last price observed by its end, so buckets without an
+    /// observation carry the previous price forward.
+    pub fn resample(&self, interval: ResampleInterval) -> PriceHistory {
+        let mut observations = self.prices.clone();
+        observations.sort_by_key(|(_, observed_at)| *observed_at);
+        let (Some(&(mut price, first)), Some(&(_, last))) = (observations.first(), observations.last()) else {
+            return PriceHistory { prices: Vec::new() };
+        };
+
+        let last_bucket = interval.bucket_start(last);
+        let mut bucket = interval.bucket_start(first);
+        let mut observations = observations.into_iter().peekable();
+        let mut prices = Vec::new();
+        loop {
+            let next_bucket = interval.next_bucket(bucket);
+            while let Some((observed, _)) = observations.next_if(|(_, observed_at)| *observed_at < next_bucket) {
+                price = observed;
+            }
+            prices.push((price, bucket));
+            if bucket >= last_bucket {
+                break;
+            }
+            bucket = next_bucket;
+        }
+
+        PriceHistory { prices }
+    }
+
+    /// Plots the prices in kUSD, `width` columns wide (not counting the
+    /// axis labels) and `height` lines tall. A series that never changes is
+    /// drawn as a single line.
     pub fn to_ascii_graph(&self, width: usize, height: usize) -> String {
         if self.prices.is_empty() {
             return "No price history available".to_string();
         }
 
         // Extract prices and convert to kUSD, rounding to whole numbers
-        let prices: Vec<f64> = self.prices.iter()
+        let mut prices: Vec<f64> = self.prices.iter()
             .map(|(price, _)| (*price / 1000.0).round())
             .collect();
 
-        if prices.is_empty() {
-            return "No valid prices in history".to_string();
+        // rasciigraph needs two points to interpolate between
+        if prices.len() == 1 {
+            prices.push(prices[0]);
         }
 
-        // Create the graph using rasciigraph
+        // rasciigraph draws one more line than the height it is given, and
+        // picks its own height when given 0
         let config = Config::default()
-            .with_width(width as u32)
-            .with_height(height as u32);
+            .with_width(width.max(2) as u32)
+            .with_height((height.max(2) - 1) as u32);
         let graph = plot(prices, config);
 
         // Ensure each line is exactly the specified width
@@ -40,4 +136,117 @@ impl PriceHistory {
             .collect::<Vec<_>>()
             .join("\n")
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_point_history() {
+        let history = PriceHistory::new(vec![(90000.0, Utc::now()), (120000.0, Utc::now())]);
+        let graph = history.to_ascii_graph(20, 5);
+
+        assert_eq!(graph.lines().count(), 5);
+        // Axis labels run from the highest price down to the lowest
+        assert!(graph.lines().next().unwrap().contains("120"));
+        assert!(graph.lines().last().unwrap().contains("90"));
+    }
+
+    #[test]
+    fn test_single_observation() {
+        let history = PriceHistory::new(vec![(100000.0, Utc::now())]);
+        let graph = history.to_ascii_graph(20, 5);
+
+        assert_eq!(graph.lines().count(), 1);
+        assert!(graph.contains("100"));
+    }
+
+    /// Chronological prices, as `PriceHistory::new` expects them newest first
+    fn history(prices: &[f64]) -> PriceHistory {
+        PriceHistory::new(prices.iter().rev().map(|price| (*price, Utc::now())).collect())
+    }
+
+    fn prices(history: &PriceHistory) -> Vec<f64> {
+        history.prices.iter().map(|(price, _)| *price).collect()
+    }
+
+    #[test]
+    fn test_smoothed_removes_spikes() {
+        let raw = history(&[100.0, 101.0, 1000.0, 102.0, 103.0, 5.0, 104.0]);
+        let smoothed = raw.smoothed(3);
+
+        assert_eq!(prices(&raw), vec![100.0, 101.0, 1000.0, 102.0, 103.0, 5.0, 104.0]);
+        assert_eq!(prices(&smoothed), vec![101.0, 101.0, 102.0, 103.0, 102.0, 103.0, 103.0]);
+        let spread = |prices: Vec<f64>| {
+            prices.iter().cloned().fold(f64::NEG_INFINITY, f64::max) - prices.iter().cloned().fold(f64::INFINITY, f64::min)
+        };
+        assert!(spread(prices(&smoothed)) < spread(prices(&raw)));
+        // Dates are kept
+        assert_eq!(smoothed.prices[2].1, raw.prices[2].1);
+    }
+
+    #[test]
+    fn test_smoothed_edge_cases() {
+        assert!(PriceHistory::new(Vec::new()).smoothed(3).prices.is_empty());
+
+        let raw = history(&[100.0, 500.0, 110.0]);
+        assert_eq!(prices(&raw.smoothed(1)), prices(&raw));
+        // A window longer than the series is clamped to its length
+        assert_eq!(prices(&raw.smoothed(10)), prices(&raw.smoothed(3)));
+        assert_eq!(prices(&raw.smoothed(3)), vec![110.0, 110.0, 110.0]);
+    }
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(timestamp).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_resample_weekly() {
+        // Two weeks from Wednesday 2024-03-06, with nothing in the week of
+        // the 18th, newest first as PriceHistory::new expects
+        let history = PriceHistory::new(vec![
+            (90000.0, at("2024-03-27T10:00:00Z")),
+            (95000.0, at("2024-03-14T10:00:00Z")),
+            (98000.0, at("2024-03-11T00:00:00Z")),
+            (100000.0, at("2024-03-08T10:00:00Z")),
+            (105000.0, at("2024-03-06T10:00:00Z")),
+        ]);
+
+        let weekly = history.resample(ResampleInterval::Weekly);
+        assert_eq!(
+            weekly.prices,
+            vec![
+                (100000.0, at("2024-03-04T00:00:00Z")),
+                (95000.0, at("2024-03-11T00:00:00Z")),
+                (95000.0, at("2024-03-18T00:00:00Z")),
+                (90000.0, at("2024-03-25T00:00:00Z")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resample_daily_and_monthly() {
+        assert!(PriceHistory::new(Vec::new()).resample(ResampleInterval::Daily).prices.is_empty());
+
+        let history = PriceHistory::new(vec![
+            (90000.0, at("2024-03-03T23:00:00Z")),
+            (100000.0, at("2024-01-31T12:00:00Z")),
+        ]);
+        let daily = history.resample(ResampleInterval::Daily);
+        assert_eq!(daily.prices.len(), 33);
+        assert_eq!(daily.prices[0], (100000.0, at("2024-01-31T00:00:00Z")));
+        assert_eq!(daily.prices[31], (100000.0, at("2024-03-02T00:00:00Z")));
+        assert_eq!(daily.prices[32], (90000.0, at("2024-03-03T00:00:00Z")));
+
+        let monthly = history.resample(ResampleInterval::Monthly);
+        assert_eq!(prices(&monthly), vec![100000.0, 100000.0, 90000.0]);
+        assert_eq!(monthly.prices[1].1, at("2024-02-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_empty_history() {
+        let history = PriceHistory::new(Vec::new());
+        assert_eq!(history.to_ascii_graph(20, 5), "No price history available");
+    }
+}
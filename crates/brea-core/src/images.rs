@@ -0,0 +1,422 @@
+use crate::{BreaError, Database, PropertyImage, Result};
+use image::imageops::FilterType;
+use sha2::{Digest, Sha256};
+use futures::stream::{self, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Which photos of each listing a scrape downloads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImageMode {
+    #[default]
+    None,
+    /// Only one photo per listing, the first one that downloads, which
+    /// becomes its primary photo. Listings with a photo stored get none.
+    Primary,
+    /// Every photo not stored yet
+    All,
+}
+
+/// Images [`download_images`] fetches at once by default
+pub const DEFAULT_IMAGE_CONCURRENCY: usize = 4;
+
+/// Downloads an image into `dir`, named after the SHA-256 of its bytes.
+///
+/// Returns the path written and the hash. Since files are content
+/// addressed, a photo shared by several listings is stored only once.
+pub async fn download_image(client: &reqwest::Client, url: &str, dir: &Path) -> Result<(PathBuf, Vec<u8>)> {
+    let bytes = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+
+    let hash = Sha256::digest(&bytes).to_vec();
+    let path = dir.join(format!("{}.jpg", hex::encode(&hash)));
+
+    if !tokio::fs::try_exists(&path).await? {
+        tokio::fs::create_dir_all(dir).await?;
+        tokio::fs::write(&path, &bytes).await?;
+    }
+
+    Ok((path, hash))
+}
+
+/// Downloads `urls` into `dir` like [`download_image`], fetching up to
+/// `concurrency` of them at once. Results come back in the order of `urls`;
+/// a URL listed more than once is only fetched once and shares its result.
+pub async fn download_images(
+    client: &reqwest::Client,
+    urls: &[String],
+    dir: &Path,
+    concurrency: usize,
+) -> Vec<Result<(PathBuf, Vec<u8>)>> {
+    let mut unique: Vec<&str> = Vec::new();
+    let mut slots: HashMap<&str, usize> = HashMap::new();
+    for url in urls {
+        slots.entry(url.as_str()).or_insert_with(|| {
+            unique.push(url.as_str());
+            unique.len() - 1
+        });
+    }
+
+    let mut fetched: Vec<_> = stream::iter(unique.iter().enumerate())
+        .map(|(slot, url)| async move { (slot, download_image(client, url, dir).await) })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+    fetched.sort_by_key(|(slot, _)| *slot);
+    let mut fetched: Vec<_> = fetched.into_iter().map(|(_, result)| result).collect();
+
+    // The first mention of a URL takes its result, later ones get a copy
+    urls.iter()
+        .map(|url| {
+            let slot = &mut fetched[slots[url.as_str()]];
+            let copy = match &*slot {
+                Ok(downloaded) => Ok(downloaded.clone()),
+                Err(e) => Err(BreaError::Scraping(e.to_string())),
+            };
+            std::mem::replace(slot, copy)
+        })
+        .collect()
+}
+
+/// Downloads the photos `mode` asks for of the saved property `property_id`
+/// into `dir` and stores them, skipping those already stored. Returns the
+/// number downloaded.
+///
+/// With [`ImageMode::All`], up to `concurrency` photos are fetched at once.
+/// Failed downloads are logged and skipped, so with [`ImageMode::Primary`]
+/// the next photo is tried until one comes through.
+pub async fn download_property_images(
+    db: &Database,
+    client: &reqwest::Client,
+    property_id: i64,
+    images: &mut [PropertyImage],
+    dir: &Path,
+    mode: ImageMode,
+    concurrency: usize,
+) -> Result<usize> {
+    let known: HashSet<String> = db
+        .get_property_images(property_id)
+        .await?
+        .into_iter()
+        .map(|image| image.url)
+        .collect();
+    let pending: Vec<&mut PropertyImage> = images.iter_mut().filter(|image| !known.contains(&image.url)).collect();
+
+    let mut downloaded = 0;
+    match mode {
+        ImageMode::None => {}
+        ImageMode::Primary if !known.is_empty() => {}
+        ImageMode::Primary => {
+            for image in pending {
+                match download_image(client, &image.url, dir).await {
+                    Ok((local_path, hash)) => {
+                        store_image(db, property_id, image, local_path, hash).await?;
+                        downloaded += 1;
+                        break;
+                    }
+                    Err(e) => warn!("Failed to download {}: {}", image.url, e),
+                }
+            }
+        }
+        ImageMode::All => {
+            let urls: Vec<String> = pending.iter().map(|image| image.url.clone()).collect();
+            let results = download_images(client, &urls, dir, concurrency).await;
+            for (image, result) in pending.into_iter().zip(results) {
+                match result {
+                    Ok((local_path, hash)) => {
+                        store_image(db, property_id, image, local_path, hash).await?;
+                        downloaded += 1;
+                    }
+                    Err(e) => warn!("Failed to download {}: {}", image.url, e),
+                }
+            }
+        }
+    }
+    Ok(downloaded)
+}
+
+/// Stores a downloaded photo of `property_id`, with its perceptual hash
+/// when the file can be decoded. A photo other listings share gets a row of
+/// its own pointing at the same file, see [`download_image`].
+async fn store_image(
+    db: &Database,
+    property_id: i64,
+    image: &mut PropertyImage,
+    local_path: PathBuf,
+    hash: Vec<u8>,
+) -> Result<()> {
+    let phash = tokio::fs::read(&local_path)
+        .await
+        .map_err(BreaError::from)
+        .and_then(|bytes| perceptual_hash(&bytes));
+    image.phash = match phash {
+        Ok(phash) => Some(phash.to_be_bytes().to_vec()),
+        Err(e) => {
+            warn!("Failed to compute perceptual hash of {}: {}", image.url, e);
+            None
+        }
+    };
+    image.property_id = property_id;
+    image.local_path = local_path.to_string_lossy().to_string();
+    image.hash = hash;
+    db.save_property_image(image).await
+}
+
+/// Difference hash (dHash) of an encoded image.
+///
+/// The picture is shrunk to 9x8 grayscale and each bit records whether a
+/// pixel is brighter than its right neighbour, so resizing, recompression
+/// or small color tweaks barely change the result.
+pub fn perceptual_hash(bytes: &[u8]) -> Result<u64> {
+    let pixels = image::load_from_memory(bytes)?
+        .resize_exact(9, 8, FilterType::Triangle)
+        .into_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = pixels.get_pixel(x, y)[0] > pixels.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(brighter);
+        }
+    }
+    Ok(hash)
+}
+
+/// Number of differing bits between two perceptual hashes.
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::apply_migrations;
+    use crate::db::types::{DbPropertyStatus, DbTimestamp, STATUS_ACTIVE};
+    use crate::{Currency, Operation, Property, PropertyType};
+    use image::{ImageOutputFormat, RgbImage};
+    use std::io::Cursor;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn encode_png(image: RgbImage) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, ImageOutputFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    /// A diagonal gradient with a dark square, the kind of shape a photo has
+    fn photo(width: u32, height: u32, brightness: u8) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| {
+            let in_square = x > width / 4 && x < width / 2 && y > height / 3 && y < height * 2 / 3;
+            let value = if in_square {
+                20
+            } else {
+                ((x * 200 / width + y * 55 / height) as u8).saturating_add(brightness)
+            };
+            image::Rgb([value, value, value])
+        })
+    }
+
+    #[tokio::test]
+    async fn test_download_image() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/photo.jpg"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"not really a jpeg".to_vec()))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let images_dir = dir.path().join("images");
+        let client = reqwest::Client::new();
+        let url = format!("{}/photo.jpg", server.uri());
+
+        let (local_path, hash) = download_image(&client, &url, &images_dir).await.unwrap();
+        assert_eq!(hash, Sha256::digest(b"not really a jpeg").to_vec());
+        assert_eq!(local_path, images_dir.join(format!("{}.jpg", hex::encode(&hash))));
+        assert_eq!(std::fs::read(&local_path).unwrap(), b"not really a jpeg");
+
+        // The same bytes land on the same file
+        let (again, _) = download_image(&client, &url, &images_dir).await.unwrap();
+        assert_eq!(again, local_path);
+        assert_eq!(std::fs::read_dir(&images_dir).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_download_image_not_found() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("{}/missing.jpg", server.uri());
+        assert!(download_image(&reqwest::Client::new(), &url, dir.path()).await.is_err());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_download_images_keeps_order() {
+        let server = MockServer::start().await;
+        for n in 0..5u8 {
+            Mock::given(method("GET"))
+                .and(path(format!("/{}.jpg", n)))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_bytes(vec![n; 16])
+                        // Later photos answer first
+                        .set_delay(std::time::Duration::from_millis(10 * (5 - n as u64))),
+                )
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path("/missing.jpg"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut urls: Vec<String> = (0..5).map(|n| format!("{}/{}.jpg", server.uri(), n)).collect();
+        urls.insert(2, format!("{}/missing.jpg", server.uri()));
+        urls.push(urls[0].clone());
+
+        let results = download_images(&reqwest::Client::new(), &urls, dir.path(), 3).await;
+        assert_eq!(results.len(), urls.len());
+        assert!(results[2].is_err());
+        let hashes: Vec<Vec<u8>> = results
+            .iter()
+            .filter_map(|result| result.as_ref().ok())
+            .map(|(local_path, hash)| {
+                assert_eq!(Sha256::digest(std::fs::read(local_path).unwrap()).to_vec(), *hash);
+                hash.clone()
+            })
+            .collect();
+        let expected: Vec<Vec<u8>> = [0u8, 1, 2, 3, 4, 0].iter().map(|&n| Sha256::digest([n; 16]).to_vec()).collect();
+        assert_eq!(hashes, expected);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 5);
+    }
+
+    fn listing(external_id: &str) -> Property {
+        let now = DbTimestamp::now();
+        Property {
+            id: 0,
+            external_id: external_id.to_string(),
+            source: "test".to_string(),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::Apartment),
+            district: "palermo".to_string(),
+            title: "Test Property".to_string(),
+            description: None,
+            price_usd: 100000.0,
+            raw_price: 100000.0,
+            currency: Currency::Usd,
+            address: "Test Address".to_string(),
+            latitude: None,
+            longitude: None,
+            covered_size: Some(50.0),
+            rooms: Some(2),
+            antiquity: None,
+            under_construction: None,
+            bathrooms: None,
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
+            url: format!("https://example.com/{}", external_id),
+            status: DbPropertyStatus::new(STATUS_ACTIVE),
+            created_at: now.clone(),
+            updated_at: now.clone(),
+            last_seen_at: now,
+        }
+    }
+
+    fn scraped_image(url: String) -> PropertyImage {
+        let now = DbTimestamp::now();
+        PropertyImage {
+            id: 0,
+            property_id: 0,
+            url,
+            local_path: String::new(),
+            hash: Vec::new(),
+            phash: None,
+            is_primary: false,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_primary_mode_downloads_one_image_per_property() {
+        let server = MockServer::start().await;
+        // Each photo has its own bytes, so each gets a file of its own
+        for n in 0..6u8 {
+            Mock::given(method("GET"))
+                .and(path(format!("/{}.png", n)))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(encode_png(photo(32, 24, n * 10))))
+                .mount(&server)
+                .await;
+        }
+        let db = Database::new_in_memory_shared().await.unwrap();
+        apply_migrations(db.pool()).await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let client = reqwest::Client::new();
+
+        let mut listings = Vec::new();
+        for (external_id, photos) in [("a", 0..3u8), ("b", 3..6u8)] {
+            let mut property = listing(external_id);
+            db.save_property(&mut property).await.unwrap();
+            let images: Vec<PropertyImage> =
+                photos.map(|n| scraped_image(format!("{}/{}.png", server.uri(), n))).collect();
+            listings.push((property, images));
+        }
+
+        for (property, images) in &mut listings {
+            let downloaded = download_property_images(&db, &client, property.id, images, dir.path(), ImageMode::Primary, DEFAULT_IMAGE_CONCURRENCY)
+                .await
+                .unwrap();
+            assert_eq!(downloaded, 1);
+            let stored = db.get_property_images(property.id).await.unwrap();
+            assert_eq!(stored.len(), 1);
+            assert!(stored[0].is_primary);
+            assert_eq!(stored[0].url, images[0].url);
+
+            // A later scrape leaves a listing that has its photo alone
+            let again = download_property_images(&db, &client, property.id, images, dir.path(), ImageMode::Primary, DEFAULT_IMAGE_CONCURRENCY)
+                .await
+                .unwrap();
+            assert_eq!(again, 0);
+        }
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        let (property, images) = &mut listings[0];
+        let none = download_property_images(&db, &client, property.id, images, dir.path(), ImageMode::None, DEFAULT_IMAGE_CONCURRENCY).await.unwrap();
+        assert_eq!(none, 0);
+        let all = download_property_images(&db, &client, property.id, images, dir.path(), ImageMode::All, DEFAULT_IMAGE_CONCURRENCY).await.unwrap();
+        assert_eq!(all, 2);
+    }
+
+    #[test]
+    fn test_perceptual_hash_similar_images() {
+        let original = perceptual_hash(&encode_png(photo(640, 480, 0))).unwrap();
+        // Relisted copy: downscaled and slightly brightened
+        let relisted = perceptual_hash(&encode_png(photo(320, 240, 10))).unwrap();
+        // Unrelated picture: the mirrored gradient
+        let other = perceptual_hash(&encode_png(image::imageops::flip_horizontal(&photo(640, 480, 0)))).unwrap();
+
+        assert!(hamming_distance(original, relisted) <= 4);
+        assert!(hamming_distance(original, other) > 16);
+    }
+
+    #[test]
+    fn test_perceptual_hash_rejects_non_images() {
+        assert!(perceptual_hash(b"not really a jpeg").is_err());
+    }
+}
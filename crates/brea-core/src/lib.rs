@@ -6,13 +6,22 @@ use url::Url;
 use std::str::FromStr;
 use colored::Colorize;
 use crate::db::types::{DbPropertyStatus, DbTimestamp, STATUS_ACTIVE, STATUS_SOLD, STATUS_REMOVED};
-use std::fmt;
 
+pub mod api;
+pub mod currency;
 pub mod db;
+pub mod districts;
+pub mod export;
+pub mod geocode;
+pub mod images;
+pub mod notify;
+pub mod sink;
 mod graph;
 mod display;
 pub use db::Database;
-pub use graph::PriceHistory;
+pub use display::PropertyDisplay;
+pub use sink::ListingSink;
+pub use graph::{PriceHistory, ResampleInterval};
 
 pub type Result<T> = std::result::Result<T, BreaError>;
 
@@ -22,10 +31,22 @@ pub enum BreaError {
     Database(#[from] sqlx::Error),
     #[error("Scraping error: {0}")]
     Scraping(String),
+    #[error("Scraping error: {0}")]
+    Scrape(#[from] ScrapeError),
     #[error("Invalid property type: {0}")]
     InvalidPropertyType(String),
     #[error("Invalid URL: {0}")]
     InvalidUrl(String),
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("Invalid property: {0}")]
+    InvalidProperty(String),
+    #[error("Schema error: {0}")]
+    Schema(String),
+    #[error("Currency conversion error: {0}")]
+    Currency(String),
+    #[error("Image error: {0}")]
+    Image(#[from] image::ImageError),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     #[error("CSV error: {0}")]
@@ -38,6 +59,38 @@ pub enum BreaError {
     Url(#[from] url::ParseError),
 }
 
+/// Why a page couldn't be scraped.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ScrapeError {
+    /// The request failed or timed out before a response came back
+    #[error("{0}")]
+    Network(String),
+    /// The site answered with an error status
+    #[error("{url} returned HTTP {status}")]
+    Http { url: String, status: u16 },
+    /// The page came back but didn't hold what was expected
+    #[error("{0}")]
+    Parse(String),
+    /// The page came back empty
+    #[error("empty response")]
+    EmptyResponse,
+    /// A CSS selector the parser uses doesn't compile
+    #[error("invalid selector {0}")]
+    SelectorFailed(String),
+}
+
+impl ScrapeError {
+    /// Whether trying again may succeed: network failures, server errors and
+    /// rate limiting are transient, the rest will fail the same way.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ScrapeError::Network(_) => true,
+            ScrapeError::Http { status, .. } => *status >= 500 || *status == 429,
+            ScrapeError::Parse(_) | ScrapeError::EmptyResponse | ScrapeError::SelectorFailed(_) => false,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum PropertyType {
     House,
@@ -55,37 +108,31 @@ pub enum PropertyType {
     CountryHouse,
 }
 
-impl sqlx::Type<sqlx::Sqlite> for PropertyType {
-    fn type_info() -> sqlx::sqlite::SqliteTypeInfo {
-        <String as sqlx::Type<sqlx::Sqlite>>::type_info()
-    }
-}
-
-impl<'r> sqlx::Decode<'r, sqlx::Sqlite> for PropertyType {
-    fn decode(value: sqlx::sqlite::SqliteValueRef<'r>) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let text = <&str as sqlx::Decode<sqlx::Sqlite>>::decode(value)?;
-        match text.to_lowercase().as_str() {
-            "house" | "houses" | "casa" | "casas" => Ok(PropertyType::House),
-            "apartment" | "apartments" | "departamento" | "departamentos" => Ok(PropertyType::Apartment),
-            "land" | "lands" | "terreno" | "terrenos" => Ok(PropertyType::Land),
-            "ph" => Ok(PropertyType::Ph),
-            "local" | "locales" => Ok(PropertyType::Local),
-            "field" | "fields" | "campo" | "campos" => Ok(PropertyType::Field),
-            "garage" | "garages" | "cochera" | "cocheras" => Ok(PropertyType::Garage),
-            "commercial" | "commercial-premises" | "fondo-comercio" | "commercial premises" => Ok(PropertyType::CommercialPremises),
-            "warehouse" | "warehouses" | "galpon" | "galpones" => Ok(PropertyType::Warehouse),
-            "hotel" | "hotels" => Ok(PropertyType::Hotel),
-            "special-business" | "special-businesses" | "negocio-especial" | "special business" => Ok(PropertyType::SpecialBusiness),
-            "office" | "offices" | "oficina" | "oficinas" => Ok(PropertyType::Office),
-            "country-house" | "country-houses" | "quinta" | "quintas" => Ok(PropertyType::CountryHouse),
-            _ => Err(format!("Unknown property type: {}", text).into()),
-        }
+impl PropertyType {
+    /// Every property type, in declaration order
+    pub fn all() -> &'static [PropertyType] {
+        &[
+            PropertyType::House,
+            PropertyType::Apartment,
+            PropertyType::Land,
+            PropertyType::Ph,
+            PropertyType::Local,
+            PropertyType::Field,
+            PropertyType::Garage,
+            PropertyType::CommercialPremises,
+            PropertyType::Warehouse,
+            PropertyType::Hotel,
+            PropertyType::SpecialBusiness,
+            PropertyType::Office,
+            PropertyType::CountryHouse,
+        ]
     }
-}
 
-impl sqlx::Encode<'_, sqlx::Sqlite> for PropertyType {
-    fn encode_by_ref(&self, args: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'_>>) -> sqlx::encode::IsNull {
-        let text = match self {
+    /// How the type is stored in the database. Only ever changes along with
+    /// a migration rewriting the stored values, and `FromStr` must keep
+    /// accepting it. Scrapers build their URLs with their own slugs instead.
+    pub fn db_name(&self) -> &'static str {
+        match self {
             PropertyType::House => "house",
             PropertyType::Apartment => "apartment",
             PropertyType::Land => "land",
@@ -99,8 +146,32 @@ impl sqlx::Encode<'_, sqlx::Sqlite> for PropertyType {
             PropertyType::SpecialBusiness => "special business",
             PropertyType::Office => "office",
             PropertyType::CountryHouse => "country house",
-        };
-        args.push(sqlx::sqlite::SqliteArgumentValue::Text(text.into()));
+        }
+    }
+}
+
+impl sqlx::Type<sqlx::Sqlite> for PropertyType {
+    fn type_info() -> sqlx::sqlite::SqliteTypeInfo {
+        <String as sqlx::Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+/// Decodes both the canonical lowercase form written by `Encode` and the
+/// legacy values older versions stored, such as "Apartment" or
+/// "Commercial Premises".
+impl<'r> sqlx::Decode<'r, sqlx::Sqlite> for PropertyType {
+    fn decode(value: sqlx::sqlite::SqliteValueRef<'r>) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let text = <&str as sqlx::Decode<sqlx::Sqlite>>::decode(value)?;
+        match Self::all().iter().find(|property_type| property_type.db_name() == text) {
+            Some(property_type) => Ok(property_type.clone()),
+            None => Self::from_str(text).map_err(|e| e.into()),
+        }
+    }
+}
+
+impl sqlx::Encode<'_, sqlx::Sqlite> for PropertyType {
+    fn encode_by_ref(&self, args: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'_>>) -> sqlx::encode::IsNull {
+        args.push(sqlx::sqlite::SqliteArgumentValue::Text(self.db_name().into()));
         sqlx::encode::IsNull::No
     }
 }
@@ -142,7 +213,7 @@ impl FromStr for PropertyType {
             "hotel" | "hotels" => Ok(PropertyType::Hotel),
             "special-business" | "special-businesses" | "negocio-especial" | "special business" => Ok(PropertyType::SpecialBusiness),
             "office" | "offices" | "oficina" | "oficinas" => Ok(PropertyType::Office),
-            "country-house" | "country-houses" | "quinta" | "quintas" => Ok(PropertyType::CountryHouse),
+            "country-house" | "country-houses" | "country house" | "quinta" | "quintas" => Ok(PropertyType::CountryHouse),
             _ => Err(format!(
                 "Invalid property type: {}. Valid options are: house/casa, apartment/departamento, land/terreno, ph, local, field/campo, garage/cochera, commercial/fondo-comercio, warehouse/galpon, hotel, special-business/negocio-especial, office/oficina, country-house/quinta",
                 s
@@ -151,6 +222,130 @@ impl FromStr for PropertyType {
     }
 }
 
+/// Kind of listing a property was published under.
+///
+/// Rentals are often quoted as a monthly amount, sometimes in ARS; regardless
+/// of the operation, `Property::price_usd` stays normalized to USD.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Operation {
+    #[default]
+    Sale,
+    Rent,
+    TemporaryRent,
+}
+
+impl Operation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Sale => "sale",
+            Operation::Rent => "rent",
+            Operation::TemporaryRent => "temporary-rent",
+        }
+    }
+}
+
+impl sqlx::Type<sqlx::Sqlite> for Operation {
+    fn type_info() -> sqlx::sqlite::SqliteTypeInfo {
+        <String as sqlx::Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> sqlx::Decode<'r, sqlx::Sqlite> for Operation {
+    fn decode(value: sqlx::sqlite::SqliteValueRef<'r>) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let text = <&str as sqlx::Decode<sqlx::Sqlite>>::decode(value)?;
+        Self::from_str(text).map_err(|e| e.into())
+    }
+}
+
+impl sqlx::Encode<'_, sqlx::Sqlite> for Operation {
+    fn encode_by_ref(&self, args: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'_>>) -> sqlx::encode::IsNull {
+        args.push(sqlx::sqlite::SqliteArgumentValue::Text(self.as_str().into()));
+        sqlx::encode::IsNull::No
+    }
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operation::Sale => write!(f, "Sale"),
+            Operation::Rent => write!(f, "Rent"),
+            Operation::TemporaryRent => write!(f, "Temporary Rent"),
+        }
+    }
+}
+
+impl FromStr for Operation {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sale" | "venta" => Ok(Operation::Sale),
+            "rent" | "alquiler" => Ok(Operation::Rent),
+            "temporary-rent" | "temporary rent" | "alquiler-temporal" => Ok(Operation::TemporaryRent),
+            _ => Err(format!(
+                "Invalid operation: {}. Valid options are: sale/venta, rent/alquiler, temporary-rent/alquiler-temporal",
+                s
+            )),
+        }
+    }
+}
+
+/// Currency a listing is priced in. ArgenProp mixes USD (`U$S`/`USD`) and
+/// ARS (`$`) prices.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Currency {
+    #[default]
+    Usd,
+    Ars,
+}
+
+impl Currency {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Currency::Usd => "USD",
+            Currency::Ars => "ARS",
+        }
+    }
+}
+
+impl sqlx::Type<sqlx::Sqlite> for Currency {
+    fn type_info() -> sqlx::sqlite::SqliteTypeInfo {
+        <String as sqlx::Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> sqlx::Decode<'r, sqlx::Sqlite> for Currency {
+    fn decode(value: sqlx::sqlite::SqliteValueRef<'r>) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let text = <&str as sqlx::Decode<sqlx::Sqlite>>::decode(value)?;
+        Self::from_str(text).map_err(|e| e.into())
+    }
+}
+
+impl sqlx::Encode<'_, sqlx::Sqlite> for Currency {
+    fn encode_by_ref(&self, args: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'_>>) -> sqlx::encode::IsNull {
+        args.push(sqlx::sqlite::SqliteArgumentValue::Text(self.as_str().into()));
+        sqlx::encode::IsNull::No
+    }
+}
+
+impl std::fmt::Display for Currency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_uppercase().as_str() {
+            "USD" | "U$S" | "US$" => Ok(Currency::Usd),
+            "ARS" | "$" => Ok(Currency::Ars),
+            _ => Err(format!("Invalid currency: {}. Valid options are: USD, ARS", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum PropertyStatus {
     Active,
@@ -218,24 +413,168 @@ impl std::fmt::Display for PropertyStatus {
 }
 
 // Property with SQLx support
+/// A listing as scraped and stored.
+///
+/// # Field ownership
+///
+/// Saving a listing that is already stored overwrites only what the scrape
+/// owns: the listing details (type, district, title, description, prices,
+/// address, sizes, rooms, age, bathrooms, parking, expensas, URL) along with
+/// `status`, `updated_at` and `last_seen_at`. Everything else belongs to the
+/// user or the database and survives re-scrapes:
+///
+/// - `notes`, set with [`Database::set_notes`], and tags, which live in
+///   their own table
+/// - `latitude` and `longitude` once set, by a scrape, the geocoder or
+///   [`Database::set_coordinates`]; a scrape only fills in missing ones
+/// - a `removed` status, set by `dedupe --apply` or by hand, which a scrape
+///   seeing the listing again doesn't revive. Sold listings showing up again
+///   do become active.
+/// - `id`, `source`, `external_id` and `created_at`, fixed when the listing
+///   is first stored
+///
+/// See [`Property::keep_user_fields`].
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Property {
     pub id: i64,
     pub external_id: String,
     pub source: String,
-    pub property_type: Option<String>,
+    pub operation: Operation,
+    pub property_type: Option<PropertyType>,
     pub district: String,
     pub title: String,
     pub description: Option<String>,
+    /// Price normalized to USD, computed from `raw_price` when saved
     pub price_usd: f64,
+    /// Price as listed, in `currency`
+    pub raw_price: f64,
+    pub currency: Currency,
     pub address: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
     pub covered_size: Option<f64>,
     pub rooms: Option<i32>,
     pub antiquity: Option<i32>,
+    /// Whether the building is still being built ("en pozo", "en
+    /// construcción"), in which case `antiquity` is unknown. `None` when the
+    /// listing doesn't say.
+    #[serde(default)]
+    pub under_construction: Option<bool>,
+    /// Full bathrooms; toilettes aren't counted
+    #[serde(default)]
+    pub bathrooms: Option<i32>,
+    /// Whether a parking space comes with the listing. `None` when it doesn't
+    /// say, and for garages, which are the parking space themselves.
+    #[serde(default)]
+    pub has_parking: Option<bool>,
+    /// Monthly expensas (building fees) in pesos, as listed
+    #[serde(default)]
+    pub expenses_ars: Option<f64>,
+    /// Free text the user wrote about the listing, see
+    /// [`Database::set_notes`]. Scrapers leave it `None` and saving a
+    /// scraped listing keeps the stored notes.
+    #[serde(default)]
+    pub notes: Option<String>,
     pub url: String,
     pub status: DbPropertyStatus,
     pub created_at: DbTimestamp,
     pub updated_at: DbTimestamp,
+    /// When a scrape last saw this listing
+    pub last_seen_at: DbTimestamp,
+}
+
+impl Property {
+    /// What identifies this listing across scrapes.
+    pub fn key(&self) -> PropertyKey {
+        PropertyKey::from(self)
+    }
+
+    /// Takes the fields a scrape doesn't own from `stored`, the row this
+    /// scraped listing is about to be saved over.
+    pub fn keep_user_fields(&mut self, stored: &Property) {
+        self.id = stored.id;
+        self.source = stored.source.clone();
+        self.external_id = stored.external_id.clone();
+        self.created_at = stored.created_at.clone();
+        self.notes = stored.notes.clone();
+        if stored.latitude.is_some() && stored.longitude.is_some() {
+            self.latitude = stored.latitude;
+            self.longitude = stored.longitude;
+        }
+        if stored.status.as_str() == STATUS_REMOVED {
+            self.status = stored.status.clone();
+        }
+    }
+
+    /// USD per covered square meter, if the covered size is known.
+    pub fn price_per_m2(&self) -> Option<f64> {
+        self.covered_size
+            .filter(|size| *size > 0.0)
+            .map(|size| self.price_usd / size)
+    }
+
+    /// Checks the listing is worth storing: it has a title, a positive
+    /// price and a URL that parses. Returns every problem found. The price
+    /// checked is the listed one, as `price_usd` is only set on save.
+    pub fn validate(&self) -> std::result::Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        if self.title.trim().is_empty() {
+            problems.push("empty title".to_string());
+        }
+        if self.raw_price <= 0.0 || self.raw_price.is_nan() {
+            problems.push(format!("price {} is not positive", self.raw_price));
+        }
+        if let Err(e) = Url::parse(&self.url) {
+            problems.push(format!("invalid url '{}': {}", self.url, e));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// The listing URL without its query string or fragment, over https,
+    /// with a lowercase host and no `www.` prefix, so links to the same
+    /// listing from different places compare equal.
+    ///
+    /// A relative URL has no host to normalize and only loses its query
+    /// string and fragment.
+    pub fn canonical_url(&self) -> String {
+        let Ok(url) = Url::parse(&self.url) else {
+            let end = self.url.find(['?', '#']).unwrap_or(self.url.len());
+            return self.url[..end].trim_end_matches('/').to_string();
+        };
+        let host = url.host_str().unwrap_or_default();
+        let host = host.strip_prefix("www.").unwrap_or(host);
+        let port = url.port().map(|port| format!(":{}", port)).unwrap_or_default();
+        format!("https://{}{}{}", host, port, url.path().trim_end_matches('/'))
+    }
+}
+
+/// A listing's identity: the site it comes from and the id the site gives it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PropertyKey {
+    pub source: String,
+    pub external_id: String,
+}
+
+impl PropertyKey {
+    pub fn new(source: impl Into<String>, external_id: impl Into<String>) -> Self {
+        Self { source: source.into(), external_id: external_id.into() }
+    }
+}
+
+impl From<&Property> for PropertyKey {
+    fn from(property: &Property) -> Self {
+        Self::new(&property.source, &property.external_id)
+    }
+}
+
+impl std::fmt::Display for PropertyKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.source, self.external_id)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -245,6 +584,12 @@ pub struct PropertyImage {
     pub url: String,
     pub local_path: String,
     pub hash: Vec<u8>,
+    /// Perceptual hash of the picture, close for visually similar photos
+    pub phash: Option<Vec<u8>>,
+    /// Whether this is the photo shown first for the property; at most one
+    /// image per property has it set
+    #[serde(default)]
+    pub is_primary: bool,
     pub created_at: DbTimestamp,
     pub updated_at: DbTimestamp,
 }
@@ -257,6 +602,38 @@ pub struct PropertyPriceHistory {
     pub observed_at: DbTimestamp,
 }
 
+/// A price a property was seen listed at.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceObservation {
+    pub price_usd: f64,
+    pub observed_at: DateTime<Utc>,
+}
+
+/// A property with its price history, for JSON consumers that want both in
+/// one go. Serializes as the property's own fields plus a `price_history`
+/// array, oldest price first.
+#[derive(Debug, Clone, Serialize)]
+pub struct PropertyWithHistory {
+    #[serde(flatten)]
+    pub property: Property,
+    pub price_history: Vec<PriceObservation>,
+}
+
+/// Criteria a user wants to be alerted about. Unset fields match anything.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub district: Option<String>,
+    pub property_type: Option<PropertyType>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    pub min_size: Option<f64>,
+    pub max_size: Option<f64>,
+    pub created_at: DbTimestamp,
+    /// When `watch` last reported matches for this search
+    pub last_checked_at: Option<DbTimestamp>,
+}
+
 // Custom serialization for PathBuf
 mod path_buf_serde {
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -279,47 +656,10 @@ mod path_buf_serde {
     }
 }
 
-pub struct PropertyDisplay {
-    pub property: Property,
-    pub price_history: Option<Vec<(f64, DateTime<Utc>)>>,
-}
-
-impl PropertyDisplay {
-    pub fn new(property: Property, price_history: Vec<(f64, DateTime<Utc>)>) -> Self {
-        Self {
-            property,
-            price_history: Some(price_history),
-        }
-    }
-
-    pub fn format(&self) -> String {
-        let mut output = String::new();
-        output.push_str(&format!("Property: {} - {}\n", self.property.title, self.property.district));
-        output.push_str(&format!("Address: {}\n", self.property.address));
-        output.push_str(&format!("Price: ${:.2}\n", self.property.price_usd));
-        if let Some(size) = self.property.covered_size {
-            output.push_str(&format!("Size: {:.1} m²\n", size));
-        }
-        if let Some(rooms) = self.property.rooms {
-            output.push_str(&format!("Rooms: {}\n", rooms));
-        }
-        if let Some(antiquity) = self.property.antiquity {
-            output.push_str(&format!("Antiquity: {} years\n", antiquity));
-        }
-        output.push_str(&format!("Status: {}\n", self.property.status));
-        output
-    }
-}
-
-impl fmt::Display for PropertyDisplay {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_string())
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::Utc;
 
     #[test]
     fn test_property_serialization() {
@@ -327,19 +667,30 @@ mod tests {
             id: 1,
             external_id: "test-123".to_string(),
             source: "test".to_string(),
-            property_type: Some("apartment".to_string()),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::Apartment),
             district: "Test District".to_string(),
             title: "Test Property".to_string(),
             description: Some("Test description".to_string()),
             price_usd: 100000.0,
+            raw_price: 100000.0,
+            currency: Currency::Usd,
             address: "123 Test St".to_string(),
+            latitude: None,
+            longitude: None,
             covered_size: Some(100.0),
             rooms: Some(2),
             antiquity: Some(5),
+            under_construction: None,
+            bathrooms: None,
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
             url: "https://example.com/test".to_string(),
             status: DbPropertyStatus::new("active"),
             created_at: DbTimestamp::from_rfc3339("2024-03-20T00:00:00Z").unwrap(),
             updated_at: DbTimestamp::from_rfc3339("2024-03-20T00:00:00Z").unwrap(),
+            last_seen_at: DbTimestamp::from_rfc3339("2024-03-20T00:00:00Z").unwrap(),
         };
 
         let json = serde_json::to_string(&property).unwrap();
@@ -347,6 +698,58 @@ mod tests {
         assert_eq!(deserialized.external_id, "test-123");
     }
 
+    #[test]
+    fn test_property_with_history_serialization() {
+        let timestamp = DbTimestamp::from_rfc3339("2024-03-20T00:00:00Z").unwrap();
+        let property = Property {
+            id: 1,
+            external_id: "test-123".to_string(),
+            source: "test".to_string(),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::Apartment),
+            district: "Test District".to_string(),
+            title: "Test Property".to_string(),
+            description: None,
+            price_usd: 95000.0,
+            raw_price: 95000.0,
+            currency: Currency::Usd,
+            address: "123 Test St".to_string(),
+            latitude: None,
+            longitude: None,
+            covered_size: None,
+            rooms: None,
+            antiquity: None,
+            under_construction: None,
+            bathrooms: None,
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
+            url: "https://example.com/test".to_string(),
+            status: DbPropertyStatus::new("active"),
+            created_at: timestamp.clone(),
+            updated_at: timestamp.clone(),
+            last_seen_at: timestamp,
+        };
+        let observed_at = |rfc3339: &str| DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc);
+        let with_history = PropertyWithHistory {
+            property: property.clone(),
+            price_history: vec![
+                PriceObservation { price_usd: 100000.0, observed_at: observed_at("2024-03-20T00:00:00Z") },
+                PriceObservation { price_usd: 95000.0, observed_at: observed_at("2024-04-01T12:00:00Z") },
+            ],
+        };
+
+        let json = serde_json::to_value(&with_history).unwrap();
+        assert_eq!(json["external_id"], "test-123");
+        assert_eq!(json["price_usd"], 95000.0);
+        assert_eq!(json["price_history"], serde_json::json!([
+            { "price_usd": 100000.0, "observed_at": "2024-03-20T00:00:00Z" },
+            { "price_usd": 95000.0, "observed_at": "2024-04-01T12:00:00Z" },
+        ]));
+        // A plain property still serializes without it
+        assert!(serde_json::to_value(&property).unwrap().get("price_history").is_none());
+    }
+
     #[test]
     fn test_property_image_serialization() {
         let image = PropertyImage {
@@ -355,6 +758,8 @@ mod tests {
             url: "https://example.com/image.jpg".to_string(),
             local_path: "/tmp/images/test.jpg".to_string(),
             hash: vec![1, 2, 3, 4],
+            phash: None,
+            is_primary: false,
             created_at: DbTimestamp::from_rfc3339("2024-03-20T00:00:00Z").unwrap(),
             updated_at: DbTimestamp::from_rfc3339("2024-03-20T00:00:00Z").unwrap(),
         };
@@ -364,6 +769,208 @@ mod tests {
         assert_eq!(deserialized.url, "https://example.com/image.jpg");
     }
 
+    #[test]
+    fn test_price_per_m2() {
+        let mut property = Property {
+            id: 1,
+            external_id: "test-123".to_string(),
+            source: "test".to_string(),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::Apartment),
+            district: "Test District".to_string(),
+            title: "Test Property".to_string(),
+            description: Some("Test description".to_string()),
+            price_usd: 150000.0,
+            raw_price: 150000.0,
+            currency: Currency::Usd,
+            address: "123 Test St".to_string(),
+            latitude: None,
+            longitude: None,
+            covered_size: Some(75.0),
+            rooms: Some(2),
+            antiquity: Some(5),
+            under_construction: None,
+            bathrooms: None,
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
+            url: "https://example.com/test".to_string(),
+            status: DbPropertyStatus::new("active"),
+            created_at: DbTimestamp::from_rfc3339("2024-03-20T00:00:00Z").unwrap(),
+            updated_at: DbTimestamp::from_rfc3339("2024-03-20T00:00:00Z").unwrap(),
+            last_seen_at: DbTimestamp::from_rfc3339("2024-03-20T00:00:00Z").unwrap(),
+        };
+        assert_eq!(property.price_per_m2(), Some(2000.0));
+
+        property.covered_size = Some(0.0);
+        assert_eq!(property.price_per_m2(), None);
+        property.covered_size = None;
+        assert_eq!(property.price_per_m2(), None);
+    }
+
+    fn display_property() -> Property {
+        Property {
+            id: 1,
+            external_id: "test-123".to_string(),
+            source: "test".to_string(),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::Apartment),
+            district: "Test District".to_string(),
+            title: "Departamento en Palermo".to_string(),
+            description: Some("Test description".to_string()),
+            price_usd: 100000.0,
+            raw_price: 100000.0,
+            currency: Currency::Usd,
+            address: "123 Test St".to_string(),
+            latitude: None,
+            longitude: None,
+            covered_size: Some(100.0),
+            rooms: Some(2),
+            antiquity: Some(5),
+            under_construction: None,
+            bathrooms: None,
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
+            url: "https://example.com/test".to_string(),
+            status: DbPropertyStatus::new("active"),
+            created_at: DbTimestamp::from_rfc3339("2024-03-20T00:00:00Z").unwrap(),
+            updated_at: DbTimestamp::from_rfc3339("2024-03-20T00:00:00Z").unwrap(),
+            last_seen_at: DbTimestamp::from_rfc3339("2024-03-20T00:00:00Z").unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_property_display() {
+        let property = display_property();
+
+        let rendered = PropertyDisplay::new(property, vec![(100000.0, Utc::now())]).to_string();
+        assert!(rendered.contains("Departamento en Palermo"));
+        assert!(rendered.contains("$1000/m²"));
+        assert!(rendered.contains("Price History"));
+    }
+
+    #[test]
+    fn test_property_display_graph_dimensions() {
+        let history = vec![(120000.0, Utc::now()), (110000.0, Utc::now()), (100000.0, Utc::now())];
+        let rendered = PropertyDisplay::new(display_property(), history)
+            .with_graph_dimensions(20, 5)
+            .to_string();
+
+        let graph: Vec<_> = rendered.split("Price History (kUSD):\n").nth(1).unwrap().lines().collect();
+        assert_eq!(graph.len(), 5);
+        assert!(graph[0].contains("120"));
+    }
+
+    #[test]
+    fn test_property_display_expenses() {
+        let mut property = display_property();
+        assert!(!PropertyDisplay::new(property.clone(), Vec::new()).to_string().contains("expensas"));
+
+        property.expenses_ars = Some(85000.0);
+        let rendered = PropertyDisplay::new(property, Vec::new()).to_string();
+        assert!(rendered.contains("ARS 85000/month expensas"));
+    }
+
+    #[test]
+    fn test_property_display_notes() {
+        let mut property = display_property();
+        assert!(!PropertyDisplay::new(property.clone(), Vec::new()).to_string().contains("Notes:"));
+
+        property.notes = Some("Visit on Saturday".to_string());
+        let rendered = PropertyDisplay::new(property, Vec::new()).to_string();
+        assert!(rendered.contains("Address: 123 Test St - Test District\nNotes: Visit on Saturday"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(display_property().validate(), Ok(()));
+
+        let mut untitled = display_property();
+        untitled.title = "  ".to_string();
+        assert_eq!(untitled.validate(), Err(vec!["empty title".to_string()]));
+
+        let mut free = display_property();
+        free.raw_price = 0.0;
+        assert_eq!(free.validate(), Err(vec!["price 0 is not positive".to_string()]));
+
+        let mut relative = display_property();
+        relative.url = "/departamento-en-venta-en-palermo--123".to_string();
+        let problems = relative.validate().unwrap_err();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("invalid url"));
+
+        // Every problem is reported at once
+        let mut empty = display_property();
+        empty.title = String::new();
+        empty.raw_price = -1.0;
+        empty.url = String::new();
+        assert_eq!(empty.validate().unwrap_err().len(), 3);
+    }
+
+    #[test]
+    fn test_property_type_all() {
+        // Stops compiling when a variant is added, until it's added here and
+        // to `all()`
+        let position = |property_type: &PropertyType| match property_type {
+            PropertyType::House => 0,
+            PropertyType::Apartment => 1,
+            PropertyType::Land => 2,
+            PropertyType::Ph => 3,
+            PropertyType::Local => 4,
+            PropertyType::Field => 5,
+            PropertyType::Garage => 6,
+            PropertyType::CommercialPremises => 7,
+            PropertyType::Warehouse => 8,
+            PropertyType::Hotel => 9,
+            PropertyType::SpecialBusiness => 10,
+            PropertyType::Office => 11,
+            PropertyType::CountryHouse => 12,
+        };
+        assert_eq!(PropertyType::all().len(), 13);
+        for (index, property_type) in PropertyType::all().iter().enumerate() {
+            assert_eq!(position(property_type), index);
+            assert_eq!(PropertyType::from_str(&property_type.to_string()).as_ref(), Ok(property_type));
+        }
+    }
+
+    #[test]
+    fn test_property_key() {
+        use std::collections::HashSet;
+
+        // Same listing scraped twice, with a new price and title
+        let first = display_property();
+        let mut again = display_property();
+        again.id = 7;
+        again.price_usd = 95000.0;
+        again.title = "Departamento en Palermo, rebajado".to_string();
+        assert_eq!(first.key(), again.key());
+        assert_eq!(HashSet::from([first.key(), again.key()]).len(), 1);
+        assert_eq!(first.key().to_string(), "test test-123");
+
+        let mut other_source = display_property();
+        other_source.source = "other".to_string();
+        assert_ne!(first.key(), other_source.key());
+        assert_eq!(other_source.key(), PropertyKey::new("other", "test-123"));
+    }
+
+    #[test]
+    fn test_canonical_url() {
+        let mut property = display_property();
+
+        property.url = "https://www.argenprop.com/departamento-en-venta-en-palermo--123?utm_source=mail&utm_medium=alert".to_string();
+        assert_eq!(property.canonical_url(), "https://argenprop.com/departamento-en-venta-en-palermo--123");
+
+        property.url = "http://WWW.Argenprop.com/departamento-en-venta-en-palermo--123/#fotos".to_string();
+        assert_eq!(property.canonical_url(), "https://argenprop.com/departamento-en-venta-en-palermo--123");
+
+        property.url = "https://argenprop.com:8443/departamento-en-venta-en-palermo--123?utm_campaign=x".to_string();
+        assert_eq!(property.canonical_url(), "https://argenprop.com:8443/departamento-en-venta-en-palermo--123");
+
+        property.url = "/departamento-en-venta-en-palermo--123?utm_source=mail#fotos".to_string();
+        assert_eq!(property.canonical_url(), "/departamento-en-venta-en-palermo--123");
+    }
+
     #[test]
     fn test_error_display() {
         let err = BreaError::InvalidPropertyType("invalid".to_string());
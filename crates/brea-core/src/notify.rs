@@ -0,0 +1,164 @@
+use crate::db::{PriceChange, SaveOutcome};
+use crate::{Property, Result};
+use async_trait::async_trait;
+use serde::Serialize;
+
+/// A listing event worth telling the user about.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Notification {
+    NewProperty { property: Property },
+    PriceDrop { property: Property, change: PriceChange },
+}
+
+impl Notification {
+    /// The notification for a saved listing, if any: new listings and price
+    /// drops are reported, everything else is not.
+    pub fn from_outcome(property: &Property, outcome: &SaveOutcome) -> Option<Self> {
+        match outcome {
+            SaveOutcome::Inserted => Some(Notification::NewProperty { property: property.clone() }),
+            SaveOutcome::Updated { price_change: Some(change) } if change.new_price < change.old_price => {
+                Some(Notification::PriceDrop { property: property.clone(), change: change.clone() })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Delivers notifications somewhere outside brea.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, notification: &Notification) -> Result<()>;
+}
+
+/// Notifier POSTing each notification as JSON to a URL.
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        self.client
+            .post(&self.url)
+            .json(notification)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::types::{DbPropertyStatus, DbTimestamp, STATUS_ACTIVE};
+    use crate::{Currency, Operation, PropertyType};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn test_property() -> Property {
+        Property {
+            id: 7,
+            external_id: "test-123".to_string(),
+            source: "test".to_string(),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::Apartment),
+            district: "Palermo".to_string(),
+            title: "Test Property".to_string(),
+            description: None,
+            price_usd: 90000.0,
+            raw_price: 90000.0,
+            currency: Currency::Usd,
+            address: "Test Address".to_string(),
+            latitude: None,
+            longitude: None,
+            covered_size: Some(50.0),
+            rooms: Some(2),
+            antiquity: None,
+            under_construction: None,
+            bathrooms: None,
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
+            url: "https://example.com/test".to_string(),
+            status: DbPropertyStatus::new(STATUS_ACTIVE),
+            created_at: DbTimestamp::now(),
+            updated_at: DbTimestamp::now(),
+            last_seen_at: DbTimestamp::now(),
+        }
+    }
+
+    fn price_change(old_price: f64, new_price: f64) -> SaveOutcome {
+        SaveOutcome::Updated {
+            price_change: Some(PriceChange {
+                property_id: 7,
+                old_price,
+                new_price,
+                observed_at: DbTimestamp::now(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_from_outcome() {
+        let property = test_property();
+        assert!(matches!(
+            Notification::from_outcome(&property, &SaveOutcome::Inserted),
+            Some(Notification::NewProperty { .. })
+        ));
+        assert!(matches!(
+            Notification::from_outcome(&property, &price_change(100000.0, 90000.0)),
+            Some(Notification::PriceDrop { .. })
+        ));
+        assert!(Notification::from_outcome(&property, &price_change(90000.0, 100000.0)).is_none());
+        assert!(Notification::from_outcome(&property, &SaveOutcome::Updated { price_change: None }).is_none());
+        assert!(Notification::from_outcome(&property, &SaveOutcome::Unchanged).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_webhook_posts_payload() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let notifier = WebhookNotifier::new(format!("{}/hook", server.uri()));
+        let notification = Notification::from_outcome(&test_property(), &price_change(100000.0, 90000.0)).unwrap();
+        notifier.notify(&notification).await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let payload: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(payload["event"], "price_drop");
+        assert_eq!(payload["property"]["external_id"], "test-123");
+        assert_eq!(payload["change"]["old_price"], 100000.0);
+        assert_eq!(payload["change"]["new_price"], 90000.0);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_reports_http_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let notifier = WebhookNotifier::new(server.uri());
+        let notification = Notification::NewProperty { property: test_property() };
+        assert!(notifier.notify(&notification).await.is_err());
+    }
+}
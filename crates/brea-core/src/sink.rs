@@ -0,0 +1,39 @@
+use crate::{Database, Operation, Property, PropertyKey, PropertyStatus, PropertyType, Result};
+use async_trait::async_trait;
+
+/// Where a scrape reconciles what it saw with what is already stored, so
+/// scrapers can mark listings sold without knowing about [`Database`].
+#[async_trait]
+pub trait ListingSink: Send + Sync + std::fmt::Debug {
+    /// Active `source` listings of `property_type` for `operation` in
+    /// `district` missing from `seen`.
+    async fn detect_sold_properties(
+        &self,
+        seen: &[PropertyKey],
+        source: &str,
+        district: &str,
+        property_type: &PropertyType,
+        operation: Operation,
+    ) -> Result<Vec<Property>>;
+
+    /// Sets the status of every property in `ids`.
+    async fn mark_properties_status(&self, ids: &[i64], status: PropertyStatus) -> Result<()>;
+}
+
+#[async_trait]
+impl ListingSink for Database {
+    async fn detect_sold_properties(
+        &self,
+        seen: &[PropertyKey],
+        source: &str,
+        district: &str,
+        property_type: &PropertyType,
+        operation: Operation,
+    ) -> Result<Vec<Property>> {
+        Database::detect_sold_properties(self, seen, source, district, property_type, operation).await
+    }
+
+    async fn mark_properties_status(&self, ids: &[i64], status: PropertyStatus) -> Result<()> {
+        Database::mark_properties_status(self, ids, status).await
+    }
+}
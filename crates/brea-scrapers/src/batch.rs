@@ -0,0 +1,286 @@
+use crate::{ScrapeQuery, Scraper};
+use brea_core::{BreaError, Database, PropertyType, Result};
+use futures::stream::{self, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::Mutex;
+use tracing::info;
+
+/// Progress of a single district and property type.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TargetProgress {
+    pub district: String,
+    pub property_type: PropertyType,
+    /// Last page whose listings were saved
+    pub pages_done: u32,
+    /// Whether the last page of results was reached
+    pub finished: bool,
+}
+
+/// What a `scrape_districts` run has completed so far, persisted as JSON so
+/// an interrupted run picks up where it stopped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ScrapeCheckpoint {
+    pub targets: Vec<TargetProgress>,
+}
+
+impl ScrapeCheckpoint {
+    /// Reads a checkpoint, starting from scratch if the file doesn't exist.
+    pub async fn load(path: &Path) -> Result<Self> {
+        if !tokio::fs::try_exists(path).await? {
+            return Ok(Self::default());
+        }
+        let contents = tokio::fs::read(path).await?;
+        Ok(serde_json::from_slice(&contents)?)
+    }
+
+    /// Writes the checkpoint through a temporary file, so a crash mid-write
+    /// leaves the previous checkpoint intact.
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec_pretty(self)?).await?;
+        tokio::fs::rename(&tmp, path).await?;
+        Ok(())
+    }
+
+    pub fn progress(&self, district: &str, property_type: &PropertyType) -> Option<&TargetProgress> {
+        self.targets
+            .iter()
+            .find(|target| target.district == district && &target.property_type == property_type)
+    }
+
+    fn record(&mut self, district: &str, property_type: &PropertyType, page: u32, finished: bool) {
+        match self
+            .targets
+            .iter_mut()
+            .find(|target| target.district == district && &target.property_type == property_type)
+        {
+            Some(target) => {
+                target.pages_done = page;
+                target.finished = finished;
+            }
+            None => self.targets.push(TargetProgress {
+                district: district.to_string(),
+                property_type: property_type.clone(),
+                pages_done: page,
+                finished,
+            }),
+        }
+    }
+}
+
+/// Scrapes every district for every property type, saving listings page by
+/// page and recording each saved page in the `checkpoint` file.
+///
+/// Up to `concurrency` (district, type) pairs are scraped at once, each up to
+/// page `max_pages`. Rerunning with the same checkpoint skips finished pairs
+/// and resumes the others after their last saved page. Returns the number of
+/// listings saved by this run.
+pub async fn scrape_districts(
+    scraper: &dyn Scraper,
+    db: &Database,
+    districts: &[String],
+    property_types: &[PropertyType],
+    checkpoint: &Path,
+    max_pages: u32,
+    concurrency: usize,
+) -> Result<usize> {
+    let progress = Mutex::new(ScrapeCheckpoint::load(checkpoint).await?);
+    let saved = AtomicUsize::new(0);
+
+    let mut targets = Vec::new();
+    {
+        let progress = progress.lock().await;
+        for district in districts {
+            for property_type in property_types {
+                let pages_done = match progress.progress(district, property_type) {
+                    Some(target) if target.finished => continue,
+                    Some(target) => target.pages_done,
+                    None => 0,
+                };
+                targets.push((district, property_type, pages_done));
+            }
+        }
+    }
+
+    stream::iter(targets.into_iter().map(Ok::<_, BreaError>))
+        .try_for_each_concurrent(concurrency.max(1), |(district, property_type, pages_done)| {
+            let (progress, saved) = (&progress, &saved);
+            async move {
+                let mut query = ScrapeQuery::new(district.clone(), property_type.clone(), None, None, None, None, None)
+                    .with_page(pages_done + 1);
+
+                while query.page <= max_pages {
+                    let (mut results, has_next) = scraper.scrape_page(&query).await?;
+                    let mut page_saved = 0;
+                    for (property, _images) in results.iter_mut() {
+                        if db.save_property_or_skip(property).await?.is_some() {
+                            page_saved += 1;
+                        }
+                    }
+                    saved.fetch_add(page_saved, Ordering::Relaxed);
+
+                    let mut progress = progress.lock().await;
+                    progress.record(district, property_type, query.page, !has_next);
+                    progress.save(checkpoint).await?;
+                    info!("{} {}: saved page {} ({} listings)", district, property_type, query.page, page_saved);
+
+                    if !has_next {
+                        break;
+                    }
+                    query.next_page();
+                }
+                Ok(())
+            }
+        })
+        .await?;
+
+    Ok(saved.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PropertyTypeTranslator;
+    use async_trait::async_trait;
+    use brea_core::db::migrations::apply_migrations;
+    use brea_core::db::types::DbTimestamp;
+    use brea_core::db::{DbPropertyStatus, STATUS_ACTIVE};
+    use brea_core::{Currency, Operation, Property, PropertyImage, ScrapeError};
+    use std::sync::Mutex as StdMutex;
+
+    /// Serves `total_pages` pages with one listing each per district, failing
+    /// once on `fail_on` to simulate a crash.
+    struct FlakyScraper {
+        total_pages: u32,
+        fail_on: StdMutex<Option<(String, u32)>>,
+        requested: StdMutex<Vec<(String, u32)>>,
+    }
+
+    impl FlakyScraper {
+        fn new(total_pages: u32, fail_on: Option<(&str, u32)>) -> Self {
+            Self {
+                total_pages,
+                fail_on: StdMutex::new(fail_on.map(|(district, page)| (district.to_string(), page))),
+                requested: StdMutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl PropertyTypeTranslator for FlakyScraper {
+        fn property_type_to_str(&self, _property_type: &PropertyType) -> &'static str {
+            "departamentos"
+        }
+    }
+
+    #[async_trait]
+    impl Scraper for FlakyScraper {
+        fn source_name(&self) -> &str {
+            "test"
+        }
+
+        async fn scrape_page(&self, query: &ScrapeQuery) -> Result<(Vec<(Property, Vec<PropertyImage>)>, bool)> {
+            let target = (query.district.clone(), query.page);
+            if self.fail_on.lock().unwrap().take_if(|fail_on| *fail_on == target).is_some() {
+                return Err(ScrapeError::Network("connection reset".to_string()).into());
+            }
+            self.requested.lock().unwrap().push(target);
+
+            let now = DbTimestamp::now();
+            let property = Property {
+                id: 0,
+                external_id: format!("{}-{}", query.district, query.page),
+                source: self.source_name().to_string(),
+                operation: Operation::Sale,
+                property_type: Some(query.property_type.clone()),
+                district: query.district.clone(),
+                title: format!("Page {}", query.page),
+                description: None,
+                price_usd: 100000.0,
+                raw_price: 100000.0,
+                currency: Currency::Usd,
+                address: "Test Address".to_string(),
+                latitude: None,
+                longitude: None,
+                covered_size: None,
+                rooms: None,
+                antiquity: None,
+                under_construction: None,
+                bathrooms: None,
+                has_parking: None,
+                expenses_ars: None,
+                notes: None,
+                url: format!("https://example.com/{}/{}", query.district, query.page),
+                status: DbPropertyStatus::new(STATUS_ACTIVE),
+                created_at: now.clone(),
+                updated_at: now.clone(),
+                last_seen_at: now,
+            };
+            Ok((vec![(property, Vec::new())], query.page < self.total_pages))
+        }
+
+        fn supported_property_types(&self) -> Vec<PropertyType> {
+            vec![PropertyType::Apartment]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scrape_districts_resumes_from_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().join("brea.db")).await.unwrap();
+        apply_migrations(db.pool()).await.unwrap();
+        let checkpoint = dir.path().join("checkpoint.json");
+        let districts = vec!["palermo".to_string(), "belgrano".to_string()];
+        let types = [PropertyType::Apartment];
+
+        // Crashes after saving the first two pages of Palermo
+        let scraper = FlakyScraper::new(3, Some(("palermo", 3)));
+        let result = scrape_districts(&scraper, &db, &districts, &types, &checkpoint, 10, 1).await;
+        assert!(result.is_err());
+
+        let saved = ScrapeCheckpoint::load(&checkpoint).await.unwrap();
+        let palermo = saved.progress("palermo", &PropertyType::Apartment).unwrap();
+        assert_eq!((palermo.pages_done, palermo.finished), (2, false));
+        assert!(saved.progress("belgrano", &PropertyType::Apartment).is_none());
+
+        let scraper = FlakyScraper::new(3, None);
+        let count = scrape_districts(&scraper, &db, &districts, &types, &checkpoint, 10, 2).await.unwrap();
+        assert_eq!(count, 4);
+
+        // Palermo picked up at page 3 rather than starting over
+        let requested = scraper.requested.lock().unwrap().clone();
+        assert!(requested.contains(&("palermo".to_string(), 3)));
+        assert!(!requested.iter().any(|(district, page)| district == "palermo" && *page < 3));
+        assert_eq!(db.get_properties().await.unwrap().len(), 6);
+
+        // Everything is finished, so a third run does nothing
+        let scraper = FlakyScraper::new(3, None);
+        let count = scrape_districts(&scraper, &db, &districts, &types, &checkpoint, 10, 2).await.unwrap();
+        assert_eq!(count, 0);
+        assert!(scraper.requested.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scrape_districts_respects_max_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().join("brea.db")).await.unwrap();
+        apply_migrations(db.pool()).await.unwrap();
+        let checkpoint = dir.path().join("checkpoint.json");
+        let districts = vec!["palermo".to_string()];
+
+        let scraper = FlakyScraper::new(5, None);
+        let count = scrape_districts(&scraper, &db, &districts, &[PropertyType::Apartment], &checkpoint, 2, 1)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+
+        // A later run with a higher limit continues instead of stopping
+        let count = scrape_districts(&scraper, &db, &districts, &[PropertyType::Apartment], &checkpoint, 5, 1)
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+        let saved = ScrapeCheckpoint::load(&checkpoint).await.unwrap();
+        assert!(saved.progress("palermo", &PropertyType::Apartment).unwrap().finished);
+    }
+}
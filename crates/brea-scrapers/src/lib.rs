@@ -1,10 +1,26 @@
 pub mod argenprop;
+pub mod batch;
 
-use brea_core::{Database, Property, PropertyImage, PropertyType, Result};
+use brea_core::{
+    BreaError, ListingSink, Operation, Property, PropertyImage, PropertyKey, PropertyStatus, PropertyType, Result,
+};
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use async_trait::async_trait;
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+use url::Url;
 
-pub use argenprop::ArgenPropScraper;
+pub use argenprop::{ArgenPropScraper, RetryPolicy};
+use argenprop::{DEFAULT_PRICE_BOUNDS, DEFAULT_REQUESTS_PER_SECOND, DEFAULT_REQUEST_TIMEOUT, DEFAULT_USER_AGENT};
+pub use batch::{scrape_districts, ScrapeCheckpoint};
+
+/// Default [`ScrapeQuery::page_cap`]
+pub const DEFAULT_PAGE_CAP: u32 = 50;
 
 /// Enum representing different property listing sources
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -14,17 +30,56 @@ pub enum ScraperType {
     // Add more scrapers here as we implement them
 }
 
+impl ScraperType {
+    /// Every available scraper
+    pub fn all() -> &'static [ScraperType] {
+        &[ScraperType::Argenprop]
+    }
+
+    /// Name used to pick the scraper on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScraperType::Argenprop => argenprop::DEFAULT_SOURCE,
+        }
+    }
+}
+
+impl FromStr for ScraperType {
+    type Err = BreaError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::all()
+            .iter()
+            .find(|scraper_type| scraper_type.name().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| BreaError::InvalidArgument(format!(
+                "unknown scraper '{}', expected one of: {}",
+                s,
+                Self::all().iter().map(ScraperType::name).collect::<Vec<_>>().join(", ")
+            )))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ScrapeQuery {
     pub district: String,
     pub property_type: PropertyType,
+    pub operation: Operation,
     pub min_price: Option<f64>,
     pub max_price: Option<f64>,
     pub min_size: Option<f64>,
     pub max_size: Option<f64>,
     pub page: u32,
-    #[allow(dead_code)]
-    pub db: Option<Arc<Database>>,
+    /// Last page to scrape, inclusive. When set, the page count passed to
+    /// `scrape_listing` is ignored.
+    pub end_page: Option<u32>,
+    /// Most pages a single scrape fetches, whatever `max_pages` or
+    /// `end_page` ask for. Guards against a `has_next` that never turns false.
+    pub page_cap: u32,
+    /// Where listings missing from a complete scrape are marked sold
+    pub sink: Option<Arc<dyn ListingSink>>,
+    /// Stops a multi-page scrape once the page being fetched is done
+    pub cancel: CancellationToken,
 }
 
 impl ScrapeQuery {
@@ -35,28 +90,155 @@ impl ScrapeQuery {
         max_price: Option<f64>,
         min_size: Option<f64>,
         max_size: Option<f64>,
-        db: Option<Arc<Database>>,
+        sink: Option<Arc<dyn ListingSink>>,
     ) -> Self {
         Self {
             district,
             property_type,
+            operation: Operation::default(),
             min_price,
             max_price,
             min_size,
             max_size,
             page: 1,
-            db,
+            end_page: None,
+            page_cap: DEFAULT_PAGE_CAP,
+            sink,
+            cancel: CancellationToken::new(),
         }
     }
 
+    pub fn with_operation(mut self, operation: Operation) -> Self {
+        self.operation = operation;
+        self
+    }
+
     pub fn with_page(mut self, page: u32) -> Self {
         self.page = page;
         self
     }
 
+    /// Scrape pages `start` through `end`, both included.
+    pub fn with_page_range(mut self, start: u32, end: u32) -> Result<Self> {
+        if start == 0 || start > end {
+            return Err(BreaError::InvalidArgument(format!(
+                "invalid page range {}-{}, pages start at 1 and the range can't be reversed",
+                start, end
+            )));
+        }
+        self.page = start;
+        self.end_page = Some(end);
+        Ok(self)
+    }
+
+    pub fn with_page_cap(mut self, page_cap: u32) -> Self {
+        self.page_cap = page_cap;
+        self
+    }
+
+    /// Stop scraping further pages once `cancel` is triggered.
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Whether a price or size filter narrows the search, so that its
+    /// results are only part of the listings of the district.
+    pub fn is_filtered(&self) -> bool {
+        self.min_price.is_some() || self.max_price.is_some() || self.min_size.is_some() || self.max_size.is_some()
+    }
+
     pub fn next_page(&mut self) {
         self.page += 1;
     }
+
+    /// Number of pages to scrape from `page` on: up to `end_page` when it is
+    /// set, `max_pages` otherwise.
+    fn page_count(&self, max_pages: u32) -> Result<u32> {
+        match self.end_page {
+            Some(end) if end < self.page => Err(BreaError::InvalidArgument(format!(
+                "end page {} comes before start page {}",
+                end, self.page
+            ))),
+            Some(end) => Ok(end - self.page + 1),
+            None => Ok(max_pages),
+        }
+    }
+}
+
+/// Listings of one results page and whether another page follows it.
+pub type ScrapePage = (Vec<(Property, Vec<PropertyImage>)>, bool);
+
+/// Listings gathered by [`Scraper::scrape_listing`].
+#[derive(Debug, Clone, Default)]
+pub struct ListingScrape {
+    pub listings: Vec<(Property, Vec<PropertyImage>)>,
+    /// Whether every listing of the search was seen: the scrape started on
+    /// the first page, reached the last one and no page failed. Only then
+    /// can a listing missing from `listings` be taken as gone from the site.
+    pub complete: bool,
+    /// Stored listings this scrape marked sold
+    pub marked_sold: Vec<i64>,
+}
+
+/// Outcome of [`Scraper::self_test`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SelfTestReport {
+    /// Results page that was scraped
+    pub url: String,
+    /// Selectors that found at least one element on the page
+    pub matched_selectors: Vec<String>,
+    /// Selectors that found nothing, the usual sign of a markup change
+    pub empty_selectors: Vec<String>,
+    /// Listings parsed from the page
+    pub listings: usize,
+    /// Listings with a title and a non-zero price
+    pub complete_listings: usize,
+    /// Why the page couldn't be fetched. The markup wasn't checked, so a
+    /// retry may well pass.
+    pub network_error: Option<String>,
+}
+
+impl SelfTestReport {
+    /// Whether the page was fetched and at least one listing came out whole.
+    pub fn passed(&self) -> bool {
+        self.network_error.is_none() && self.complete_listings > 0
+    }
+}
+
+/// Fields of a scraped listing that came out empty, usually because a
+/// selector stopped matching. Parsers default what they can't find to empty
+/// text and a zero price, so this is what tells such listings apart.
+pub fn extraction_warnings(property: &Property) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if property.external_id.is_empty() {
+        warnings.push("no external id".to_string());
+    }
+    if property.title.is_empty() {
+        warnings.push("no title".to_string());
+    }
+    if property.raw_price <= 0.0 {
+        warnings.push("no price".to_string());
+    }
+    if property.address.is_empty() {
+        warnings.push("no address".to_string());
+    }
+    if property.url.is_empty() {
+        warnings.push("no url".to_string());
+    }
+    warnings
+}
+
+/// Drops listings that fail [`Property::validate`], logging why, so cards
+/// the parser couldn't make sense of aren't stored.
+pub fn skip_invalid(listings: &mut Vec<(Property, Vec<PropertyImage>)>) {
+    listings.retain(|(property, _)| match property.validate() {
+        Ok(()) => true,
+        Err(problems) => {
+            warn!("Skipping listing {} ({}): {}", property.external_id, property.url, problems.join(", "));
+            false
+        }
+    });
 }
 
 /// Trait for translating PropertyType to scraper-specific strings
@@ -71,30 +253,174 @@ pub trait Scraper: Send + Sync + PropertyTypeTranslator {
     /// Scrape a single page of property listings
     async fn scrape_page(&self, query: &ScrapeQuery) -> Result<(Vec<(Property, Vec<PropertyImage>)>, bool)>;
 
+    /// Source the scraped listings are tagged with
+    fn source_name(&self) -> &str;
+
     /// Get all property types supported by this scraper
     fn supported_property_types(&self) -> Vec<PropertyType>;
 
-    /// Scrape multiple pages of property listings
-    async fn scrape_listing(&self, mut query: ScrapeQuery, max_pages: u32) -> Result<Vec<(Property, Vec<PropertyImage>)>> {
+    /// Scrape a single listing from its detail page, which has fields the
+    /// results pages leave out (full description, all photos, coordinates)
+    async fn scrape_detail(&self, url: &Url) -> Result<(Property, Vec<PropertyImage>)> {
+        Err(BreaError::Scraping(format!("Detail pages are not supported for {}", url)))
+    }
+
+    /// Scrapes one known results page to check the site's markup still
+    /// matches what the parser expects
+    async fn self_test(&self) -> Result<SelfTestReport> {
+        Err(BreaError::Scraping("Self test is not supported by this scraper".to_string()))
+    }
+
+    /// Fills in a listing scraped from a results page with what its detail
+    /// page adds: the full description, coordinates, features the card left
+    /// out and the complete photo set. Identity fields are left as scraped.
+    async fn enrich_with_detail(&self, property: &mut Property, images: &mut Vec<PropertyImage>) -> Result<()> {
+        let url = Url::parse(&property.url)?;
+        let (detail, detail_images) = self.scrape_detail(&url).await?;
+
+        if detail.description.as_deref().is_some_and(|description| !description.is_empty()) {
+            property.description = detail.description;
+        }
+        property.latitude = detail.latitude.or(property.latitude);
+        property.longitude = detail.longitude.or(property.longitude);
+        property.covered_size = detail.covered_size.or(property.covered_size);
+        property.rooms = detail.rooms.or(property.rooms);
+        property.antiquity = detail.antiquity.or(property.antiquity);
+        if detail_images.len() > images.len() {
+            *images = detail_images;
+        }
+        Ok(())
+    }
+
+    /// Scrape multiple pages of property listings, from `query.page` up to
+    /// `query.end_page` or for `max_pages` pages.
+    ///
+    /// A page failing after the first one ends the scrape with the listings
+    /// gathered so far, marked incomplete, and so does reaching
+    /// `query.page_cap`, or `query.cancel` being triggered, which lets the
+    /// page being fetched finish. When the scrape is complete, unfiltered and
+    /// `query.sink` is set, active listings of the same source, district,
+    /// property type and operation that didn't show up are marked sold.
+    async fn scrape_listing(&self, mut query: ScrapeQuery, max_pages: u32) -> Result<ListingScrape> {
+        let requested_pages = query.page_count(max_pages)?;
+        let max_pages = requested_pages.min(query.page_cap);
+        let first_page = query.page;
         let mut all_properties = Vec::new();
         let mut pages_scraped = 0;
+        let mut complete = false;
 
         while pages_scraped < max_pages {
-            let (properties, has_next) = self.scrape_page(&query).await?;
+            if query.cancel.is_cancelled() {
+                info!("Scrape of {} cancelled, keeping the {} pages fetched", query.district, pages_scraped);
+                break;
+            }
+            let (properties, has_next) = match self.scrape_page(&query).await {
+                Ok(page) => page,
+                Err(e) if query.page > first_page => {
+                    warn!("Stopping at page {} of {}: {}", query.page, query.district, e);
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
             all_properties.extend(properties);
 
             if !has_next {
+                complete = first_page == 1;
                 break;
             }
 
             query.next_page();
             pages_scraped += 1;
         }
+        if pages_scraped == max_pages && max_pages < requested_pages {
+            warn!(
+                "Stopped scraping {} after {} pages, the page cap, with the site still reporting more",
+                query.district, max_pages
+            );
+        }
+
+        let mut marked_sold = Vec::new();
+        if let Some(sink) = &query.sink {
+            if complete && query.is_filtered() {
+                debug!("Price or size filters hide listings of {}, skipping sold detection", query.district);
+            } else if complete {
+                marked_sold = mark_missing_as_sold(sink.as_ref(), &query, &all_properties).await?;
+            } else {
+                debug!("Not all listings of {} were seen, skipping sold detection", query.district);
+            }
+        }
+
+        Ok(ListingScrape { listings: all_properties, complete, marked_sold })
+    }
+
+    /// Scrape multiple pages of property listings, fetching up to
+    /// `concurrency` pages at a time and no more than `query.page_cap`.
+    ///
+    /// The first page is fetched on its own to find out whether there is more
+    /// than one. Results come back in page order, and nothing past the first
+    /// page reporting no next page is kept or requested. Once `query.cancel`
+    /// is triggered no more pages are requested, and the ones in flight are
+    /// awaited and kept.
+    async fn scrape_listing_concurrent(
+        &self,
+        query: ScrapeQuery,
+        max_pages: u32,
+        concurrency: usize,
+    ) -> Result<Vec<(Property, Vec<PropertyImage>)>> {
+        let requested_pages = query.page_count(max_pages)?;
+        let max_pages = requested_pages.min(query.page_cap);
+        if max_pages == 0 || query.cancel.is_cancelled() {
+            return Ok(Vec::new());
+        }
+
+        let (first_page, has_next) = self.scrape_page(&query).await?;
+        let mut pages = BTreeMap::new();
+        pages.insert(0, first_page);
+
+        // Offset (from the starting page) of the last page with results
+        let mut last_page = if has_next { None } else { Some(0) };
+        let mut next_offset = 1;
+        let mut in_flight = FuturesUnordered::new();
+
+        loop {
+            while last_page.is_none()
+                && !query.cancel.is_cancelled()
+                && next_offset < max_pages
+                && in_flight.len() < concurrency.max(1)
+            {
+                let offset = next_offset;
+                let page_query = query.clone().with_page(query.page + offset);
+                in_flight.push(async move { (offset, self.scrape_page(&page_query).await) });
+                next_offset += 1;
+            }
+
+            let Some((offset, result)) = in_flight.next().await else {
+                break;
+            };
+            let (properties, has_next) = result?;
+            if !has_next && last_page.is_none_or(|last| offset < last) {
+                last_page = Some(offset);
+            }
+            pages.insert(offset, properties);
+        }
+        if last_page.is_none() && max_pages < requested_pages {
+            warn!(
+                "Stopped scraping {} after {} pages, the page cap, with the site still reporting more",
+                query.district, max_pages
+            );
+        }
 
-        Ok(all_properties)
+        Ok(pages
+            .into_iter()
+            .take_while(|(offset, _)| last_page.is_none_or(|last| *offset <= last))
+            .flat_map(|(_, properties)| properties)
+            .collect())
     }
 
-    /// Scrape all property types for a given district
+    /// Scrape all property types for a given district.
+    ///
+    /// A property type failing doesn't stop the others: each one comes back
+    /// with its own result, and the failures are summed up in a warning.
     async fn scrape_all_types(
         &self,
         district: &str,
@@ -103,14 +429,14 @@ pub trait Scraper: Send + Sync + PropertyTypeTranslator {
         min_size: Option<f64>,
         max_size: Option<f64>,
         max_pages: u32,
-    ) -> Result<Vec<(Property, Vec<PropertyImage>)>> {
-        let mut all_properties = Vec::new();
+    ) -> Vec<(PropertyType, Result<ListingScrape>)> {
+        let mut results = Vec::new();
         let supported_types = self.supported_property_types();
 
         for property_type in supported_types {
             let query = ScrapeQuery::new(
                 district.to_string(),
-                property_type,
+                property_type.clone(),
                 min_price,
                 max_price,
                 min_size,
@@ -118,11 +444,76 @@ pub trait Scraper: Send + Sync + PropertyTypeTranslator {
                 None,
             );
 
-            let properties = self.scrape_listing(query, max_pages).await?;
-            all_properties.extend(properties);
+            let result = self.scrape_listing(query, max_pages).await;
+            results.push((property_type, result));
+        }
+
+        let failures: Vec<String> = results
+            .iter()
+            .filter_map(|(property_type, result)| result.as_ref().err().map(|e| format!("{} ({})", property_type, e)))
+            .collect();
+        if !failures.is_empty() {
+            warn!(
+                "Scraping {} failed for {} of {} property types: {}",
+                district,
+                failures.len(),
+                results.len(),
+                failures.join(", ")
+            );
         }
+        results
+    }
+}
+
+/// Marks sold the active listings `query` searched for that are missing from
+/// `listings`, returning their ids. An empty scrape leaves everything alone,
+/// as it is more likely a blocked or broken page than a sold-out site.
+async fn mark_missing_as_sold(
+    sink: &dyn ListingSink,
+    query: &ScrapeQuery,
+    listings: &[(Property, Vec<PropertyImage>)],
+) -> Result<Vec<i64>> {
+    let Some((first, _)) = listings.first() else {
+        return Ok(Vec::new());
+    };
+    let seen: Vec<PropertyKey> = listings.iter().map(|(property, _)| property.key()).collect();
+    // Listings carry the district the site files them under, which needn't
+    // be how it was asked for ("La Boca" is stored as "boca")
+    let sold_ids: Vec<i64> = sink
+        .detect_sold_properties(&seen, &first.source, &first.district, &query.property_type, query.operation)
+        .await?
+        .iter()
+        .map(|property| property.id)
+        .collect();
+    sink.mark_properties_status(&sold_ids, PropertyStatus::Sold).await?;
+    Ok(sold_ids)
+}
+
+/// How scrapers made by [`ScraperFactory`] send their requests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScraperConfig {
+    pub user_agent: String,
+    /// Longest wait for a whole response
+    pub timeout: Duration,
+    /// Requests per second; 0 or less disables the limit
+    pub requests_per_second: f64,
+    /// HTTP or HTTPS proxy every request goes through
+    pub proxy: Option<String>,
+    pub retry_policy: RetryPolicy,
+    /// Sale prices in USD taken as real, see [`ArgenPropScraper::with_price_bounds`]
+    pub price_bounds: RangeInclusive<f64>,
+}
 
-        Ok(all_properties)
+impl Default for ScraperConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            requests_per_second: DEFAULT_REQUESTS_PER_SECOND,
+            proxy: None,
+            retry_policy: RetryPolicy::default(),
+            price_bounds: DEFAULT_PRICE_BOUNDS,
+        }
     }
 }
 
@@ -132,10 +523,17 @@ pub struct ScraperFactory;
 impl ScraperFactory {
     /// Create a new scraper instance based on the specified type
     pub fn create_scraper(scraper_type: ScraperType) -> Arc<dyn Scraper> {
-        match scraper_type {
-            ScraperType::Argenprop => Arc::new(ArgenPropScraper::new()),
+        Self::create_scraper_with_config(scraper_type, &ScraperConfig::default())
+            .expect("the default scraper config is valid")
+    }
+
+    /// Create a scraper set up with `config`. Fails with
+    /// [`BreaError::InvalidUrl`] if the configured proxy URL isn't one.
+    pub fn create_scraper_with_config(scraper_type: ScraperType, config: &ScraperConfig) -> Result<Arc<dyn Scraper>> {
+        Ok(match scraper_type {
+            ScraperType::Argenprop => Arc::new(ArgenPropScraper::with_config(config)?),
             // Add more cases here as we implement more scrapers
-        }
+        })
     }
 }
 
@@ -150,6 +548,8 @@ pub fn property_type_to_str(scraper_type: ScraperType, property_type: &PropertyT
 #[cfg(test)]
 mod tests {
     use super::*;
+    use brea_core::db::PropertyQueryBuilder;
+    use brea_core::{Currency, Database, ScrapeError};
 
     #[test]
     fn test_scrape_query() {
@@ -165,6 +565,7 @@ mod tests {
 
         assert_eq!(query.district, "test");
         assert_eq!(query.property_type, PropertyType::House);
+        assert_eq!(query.operation, Operation::Sale);
         assert_eq!(query.min_price, Some(100_000.0));
         assert_eq!(query.max_price, Some(200_000.0));
         assert_eq!(query.min_size, Some(50.0));
@@ -188,4 +589,392 @@ mod tests {
         query.next_page();
         assert_eq!(query.page, 2);
     }
-} 
\ No newline at end of file
+
+    /// Serves `total_pages` pages with one listing each, later pages
+    /// answering faster so concurrent fetches complete out of order.
+    struct PagedScraper {
+        total_pages: u32,
+        failing_page: Option<u32>,
+        failing_type: Option<PropertyType>,
+        cancel_after: Option<(u32, CancellationToken)>,
+        listed_district: Option<String>,
+        requested: std::sync::Mutex<Vec<u32>>,
+    }
+
+    impl PagedScraper {
+        fn new(total_pages: u32) -> Self {
+            Self {
+                total_pages,
+                failing_page: None,
+                failing_type: None,
+                cancel_after: None,
+                listed_district: None,
+                requested: std::sync::Mutex::new(Vec::new()),
+            }
+        }
+
+        /// Files every listing under `district`, whatever the query asked
+        /// for, as ArgenProp does with districts starting with an article.
+        fn listing_under(mut self, district: &str) -> Self {
+            self.listed_district = Some(district.to_string());
+            self
+        }
+
+        fn failing_on(mut self, page: u32) -> Self {
+            self.failing_page = Some(page);
+            self
+        }
+
+        fn failing_for(mut self, property_type: PropertyType) -> Self {
+            self.failing_type = Some(property_type);
+            self
+        }
+
+        /// Triggers `cancel` while serving `page`, as Ctrl-C would.
+        fn cancelling_after(mut self, page: u32, cancel: CancellationToken) -> Self {
+            self.cancel_after = Some((page, cancel));
+            self
+        }
+    }
+
+    impl PropertyTypeTranslator for PagedScraper {
+        fn property_type_to_str(&self, _property_type: &PropertyType) -> &'static str {
+            "departamentos"
+        }
+    }
+
+    #[async_trait]
+    impl Scraper for PagedScraper {
+        fn source_name(&self) -> &str {
+            "test"
+        }
+
+        async fn scrape_page(&self, query: &ScrapeQuery) -> Result<(Vec<(Property, Vec<PropertyImage>)>, bool)> {
+            self.requested.lock().unwrap().push(query.page);
+            if self.failing_page == Some(query.page) {
+                return Err(ScrapeError::Network(format!("page {} timed out", query.page)).into());
+            }
+            if self.failing_type.as_ref() == Some(&query.property_type) {
+                let url = format!("https://example.com/{}/{}", query.property_type, query.page);
+                return Err(ScrapeError::Http { url, status: 404 }.into());
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(5 * (20 - query.page.min(20)) as u64)).await;
+            if let Some((page, cancel)) = &self.cancel_after {
+                if *page == query.page {
+                    cancel.cancel();
+                }
+            }
+
+            let now = brea_core::db::types::DbTimestamp::now();
+            let property = Property {
+                id: 0,
+                external_id: query.page.to_string(),
+                source: self.source_name().to_string(),
+                operation: query.operation,
+                property_type: Some(query.property_type.clone()),
+                district: self.listed_district.clone().unwrap_or_else(|| query.district.clone()),
+                title: format!("Page {}", query.page),
+                description: None,
+                price_usd: 100000.0,
+                raw_price: 100000.0,
+                currency: Currency::Usd,
+                address: "Test Address".to_string(),
+                latitude: None,
+                longitude: None,
+                covered_size: None,
+                rooms: None,
+                antiquity: None,
+                under_construction: None,
+                bathrooms: None,
+                has_parking: None,
+                expenses_ars: None,
+                notes: None,
+                url: format!("https://example.com/{}", query.page),
+                status: brea_core::db::DbPropertyStatus::new(brea_core::db::STATUS_ACTIVE),
+                created_at: now.clone(),
+                updated_at: now.clone(),
+                last_seen_at: now,
+            };
+            Ok((vec![(property, Vec::new())], query.page < self.total_pages))
+        }
+
+        fn supported_property_types(&self) -> Vec<PropertyType> {
+            vec![PropertyType::Apartment, PropertyType::House, PropertyType::Land]
+        }
+    }
+
+    fn page_ids(results: &[(Property, Vec<PropertyImage>)]) -> Vec<String> {
+        results.iter().map(|(property, _)| property.external_id.clone()).collect()
+    }
+
+    fn paged_query() -> ScrapeQuery {
+        ScrapeQuery::new("test".to_string(), PropertyType::Apartment, None, None, None, None, None)
+    }
+
+    #[tokio::test]
+    async fn test_scrape_listing_page_range() {
+        let scraper = PagedScraper::new(8);
+
+        // The page range wins over the page count
+        let query = paged_query().with_page_range(2, 3).unwrap();
+        let results = scraper.scrape_listing(query, 10).await.unwrap().listings;
+        assert_eq!(page_ids(&results), vec!["2", "3"]);
+        assert_eq!(*scraper.requested.lock().unwrap(), vec![2, 3]);
+
+        let query = paged_query().with_page_range(7, 12).unwrap();
+        let results = scraper.scrape_listing_concurrent(query, 1, 3).await.unwrap();
+        assert_eq!(page_ids(&results), vec!["7", "8"]);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_scrape_keeps_fetched_pages() {
+        let cancel = CancellationToken::new();
+        let scraper = PagedScraper::new(5).cancelling_after(1, cancel.clone());
+        let scrape = scraper.scrape_listing(paged_query().with_cancellation(cancel), 10).await.unwrap();
+        assert_eq!(page_ids(&scrape.listings), vec!["1"]);
+        assert!(!scrape.complete);
+        assert_eq!(*scraper.requested.lock().unwrap(), vec![1]);
+
+        let cancel = CancellationToken::new();
+        let scraper = PagedScraper::new(5).cancelling_after(1, cancel.clone());
+        let results = scraper.scrape_listing_concurrent(paged_query().with_cancellation(cancel), 10, 3).await.unwrap();
+        assert_eq!(page_ids(&results), vec!["1"]);
+        assert_eq!(*scraper.requested.lock().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn test_invalid_page_range() {
+        assert!(matches!(paged_query().with_page_range(3, 2), Err(BreaError::InvalidArgument(_))));
+        assert!(matches!(paged_query().with_page_range(0, 2), Err(BreaError::InvalidArgument(_))));
+        assert_eq!(paged_query().with_page_range(4, 4).unwrap().page_count(10).unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_partial_scrape_marks_nothing_sold() {
+        use brea_core::db::migrations::apply_migrations;
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Database::new(dir.path().join("brea.db")).await.unwrap());
+        apply_migrations(db.pool()).await.unwrap();
+        // Listings 1 to 4 were seen before; 4 is no longer on the site
+        let previous = PagedScraper::new(4).scrape_listing(paged_query(), 10).await.unwrap();
+        for (mut property, _images) in previous.listings {
+            db.save_property(&mut property).await.unwrap();
+        }
+        let active = || async {
+            PropertyQueryBuilder::new()
+                .with_status_typed(PropertyStatus::Active)
+                .execute(db.pool())
+                .await
+                .unwrap()
+                .len()
+        };
+
+        // Page 2 failing keeps page 1 but nothing is marked sold
+        let query = ScrapeQuery { sink: Some(db.clone()), ..paged_query() };
+        let partial = PagedScraper::new(3).failing_on(2).scrape_listing(query.clone(), 10).await.unwrap();
+        assert_eq!(page_ids(&partial.listings), vec!["1"]);
+        assert!(!partial.complete);
+        assert!(partial.marked_sold.is_empty());
+        assert_eq!(active().await, 4);
+
+        // Neither does a scrape that stops before the last page
+        let truncated = PagedScraper::new(3).scrape_listing(query.clone(), 2).await.unwrap();
+        assert!(!truncated.complete);
+        assert_eq!(active().await, 4);
+
+        // Nor a price filter, which hides listings that are still for sale
+        let filtered = ScrapeQuery { min_price: Some(50000.0), ..query.clone() };
+        let filtered = PagedScraper::new(3).scrape_listing(filtered, 10).await.unwrap();
+        assert!(filtered.complete);
+        assert!(filtered.marked_sold.is_empty());
+        assert_eq!(active().await, 4);
+
+        // Nor a complete scrape of another district
+        let elsewhere = ScrapeQuery { district: "elsewhere".to_string(), ..query.clone() };
+        let elsewhere = PagedScraper::new(1).scrape_listing(elsewhere, 10).await.unwrap();
+        assert!(elsewhere.complete);
+        assert!(elsewhere.marked_sold.is_empty());
+        assert_eq!(active().await, 4);
+
+        let full = PagedScraper::new(3).scrape_listing(query.clone(), 10).await.unwrap();
+        assert!(full.complete);
+        assert_eq!(full.marked_sold.len(), 1);
+        assert_eq!(active().await, 3);
+
+        // A failing first page is an error, not an empty scrape
+        assert!(PagedScraper::new(3).failing_on(1).scrape_listing(query, 10).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sold_detection_uses_the_listed_district() {
+        use brea_core::db::migrations::apply_migrations;
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Database::new(dir.path().join("brea.db")).await.unwrap());
+        apply_migrations(db.pool()).await.unwrap();
+        let query = ScrapeQuery { district: "La Boca".to_string(), ..paged_query() };
+        let previous = PagedScraper::new(4).listing_under("boca").scrape_listing(query.clone(), 10).await.unwrap();
+        for (mut property, _images) in previous.listings {
+            db.save_property(&mut property).await.unwrap();
+        }
+
+        // Asked for as "La Boca", the listings say "boca"
+        let query = ScrapeQuery { sink: Some(db.clone()), ..query };
+        let full = PagedScraper::new(3).listing_under("boca").scrape_listing(query, 10).await.unwrap();
+        assert!(full.complete);
+        assert_eq!(full.marked_sold.len(), 1);
+        let sold = db.get_property(full.marked_sold[0]).await.unwrap().unwrap();
+        assert_eq!((sold.external_id.as_str(), sold.district.as_str()), ("4", "boca"));
+    }
+
+    /// Sink reporting `gone` as sold and recording what it was asked.
+    #[derive(Debug, Default)]
+    struct RecordingSink {
+        gone: Vec<Property>,
+        seen: std::sync::Mutex<Vec<String>>,
+        marked: std::sync::Mutex<Vec<(Vec<i64>, PropertyStatus)>>,
+    }
+
+    #[async_trait]
+    impl ListingSink for RecordingSink {
+        async fn detect_sold_properties(
+            &self,
+            seen: &[PropertyKey],
+            source: &str,
+            district: &str,
+            property_type: &PropertyType,
+            operation: Operation,
+        ) -> Result<Vec<Property>> {
+            assert_eq!((source, district, operation), ("test", "test", Operation::Sale));
+            assert_eq!(*property_type, PropertyType::Apartment);
+            self.seen.lock().unwrap().extend(seen.iter().map(|key| key.external_id.clone()));
+            Ok(self.gone.clone())
+        }
+
+        async fn mark_properties_status(&self, ids: &[i64], status: PropertyStatus) -> Result<()> {
+            self.marked.lock().unwrap().push((ids.to_vec(), status));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scrape_listing_reconciles_with_sink() {
+        // Listing 4 was stored by an earlier scrape and is no longer on the site
+        let previous = PagedScraper::new(4).scrape_listing(paged_query(), 10).await.unwrap();
+        let mut gone = previous.listings[3].0.clone();
+        gone.id = 4;
+        let sink = Arc::new(RecordingSink { gone: vec![gone], ..Default::default() });
+        let query = ScrapeQuery { sink: Some(sink.clone()), ..paged_query() };
+
+        let scrape = PagedScraper::new(3).scrape_listing(query, 10).await.unwrap();
+        assert!(scrape.complete);
+        assert_eq!(scrape.marked_sold, vec![4]);
+        assert_eq!(*sink.seen.lock().unwrap(), vec!["1", "2", "3"]);
+        assert_eq!(*sink.marked.lock().unwrap(), vec![(vec![4], PropertyStatus::Sold)]);
+    }
+
+    #[tokio::test]
+    async fn test_scrape_all_types_keeps_partial_results() {
+        let scraper = PagedScraper::new(2).failing_for(PropertyType::House);
+
+        let results = scraper.scrape_all_types("test", None, None, None, None, 10).await;
+        let types: Vec<PropertyType> = results.iter().map(|(property_type, _)| property_type.clone()).collect();
+        assert_eq!(types, [PropertyType::Apartment, PropertyType::House, PropertyType::Land]);
+
+        assert!(matches!(results[1].1, Err(BreaError::Scrape(ScrapeError::Http { status: 404, .. }))));
+        for (property_type, result) in [&results[0], &results[2]] {
+            let scrape = result.as_ref().unwrap();
+            assert_eq!(page_ids(&scrape.listings), vec!["1", "2"]);
+            assert!(scrape.listings.iter().all(|(property, _)| property.property_type.as_ref() == Some(property_type)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scrape_listing_concurrent_matches_sequential() {
+        let scraper = PagedScraper::new(8);
+
+        let sequential = scraper.scrape_listing(paged_query(), 6).await.unwrap().listings;
+        let concurrent = scraper.scrape_listing_concurrent(paged_query(), 6, 3).await.unwrap();
+
+        assert_eq!(page_ids(&concurrent), vec!["1", "2", "3", "4", "5", "6"]);
+        assert_eq!(page_ids(&concurrent), page_ids(&sequential));
+    }
+
+    #[tokio::test]
+    async fn test_scrape_listing_concurrent_stops_at_last_page() {
+        let scraper = PagedScraper::new(4);
+
+        let results = scraper.scrape_listing_concurrent(paged_query(), 20, 2).await.unwrap();
+        assert_eq!(page_ids(&results), vec!["1", "2", "3", "4"]);
+
+        // Only pages already in flight when page 4 reported the end are extra
+        let requested = scraper.requested.lock().unwrap();
+        assert!(requested.iter().all(|page| *page <= 5));
+    }
+
+    #[tokio::test]
+    async fn test_scrape_listing_concurrent_single_page() {
+        let scraper = PagedScraper::new(1);
+
+        let results = scraper.scrape_listing_concurrent(paged_query(), 10, 4).await.unwrap();
+        assert_eq!(page_ids(&results), vec!["1"]);
+        assert_eq!(*scraper.requested.lock().unwrap(), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn test_page_cap_stops_runaway_pagination() {
+        // has_next never turns false within any sane page count
+        let scraper = PagedScraper::new(u32::MAX);
+
+        let scrape = scraper.scrape_listing(paged_query(), 1000).await.unwrap();
+        assert_eq!(scrape.listings.len(), DEFAULT_PAGE_CAP as usize);
+        assert!(!scrape.complete);
+        assert_eq!(scraper.requested.lock().unwrap().len(), DEFAULT_PAGE_CAP as usize);
+
+        let query = paged_query().with_page_range(1, 1000).unwrap().with_page_cap(5);
+        let results = scraper.scrape_listing_concurrent(query, 1, 3).await.unwrap();
+        assert_eq!(page_ids(&results), vec!["1", "2", "3", "4", "5"]);
+    }
+
+    #[tokio::test]
+    async fn test_create_scraper_with_config() {
+        use wiremock::matchers::{header, method};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let detail_page = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/../../tests/fixtures/argenprop_detail.html"));
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header("user-agent", "brea-test/1.0"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(detail_page))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ScraperConfig {
+            user_agent: "brea-test/1.0".to_string(),
+            requests_per_second: 0.0,
+            retry_policy: RetryPolicy { max_retries: 0, base_delay: Duration::from_millis(10) },
+            ..Default::default()
+        };
+        let scraper = ScraperFactory::create_scraper_with_config(ScraperType::Argenprop, &config).unwrap();
+        let url = Url::parse(&format!("{}/casa-en-venta-en-boca--15866908", server.uri())).unwrap();
+        let (property, _) = scraper.scrape_detail(&url).await.unwrap();
+        assert_eq!(property.external_id, "casa-en-venta-en-boca--15866908");
+
+        let config = ScraperConfig { proxy: Some("socks5://127.0.0.1:1080".to_string()), ..Default::default() };
+        let result = ScraperFactory::create_scraper_with_config(ScraperType::Argenprop, &config);
+        assert!(matches!(result, Err(BreaError::InvalidUrl(_))));
+    }
+
+    #[test]
+    fn test_every_scraper_type_is_available() {
+        for scraper_type in ScraperType::all() {
+            let scraper = ScraperFactory::create_scraper(*scraper_type);
+            assert!(!scraper.supported_property_types().is_empty(), "{}", scraper_type.name());
+            assert_eq!(ScraperType::from_str(scraper_type.name()).unwrap(), *scraper_type);
+        }
+        assert_eq!("ArgenProp".parse::<ScraperType>().unwrap(), ScraperType::Argenprop);
+        assert!(matches!("zonaprop".parse::<ScraperType>(), Err(BreaError::InvalidArgument(_))));
+    }
+}
@@ -1,40 +1,143 @@
 use brea_core::{
     PropertyDisplay, PropertyType, Result, BreaError,
-    Database,
+    Database, Operation, Property, PropertyImage, PropertyStatus, SavedSearch,
 };
-use brea_core::db::migrations::{apply_migrations, rollback_migration, get_applied_migrations};
-use brea_core::db::types::{DbPropertyStatus, STATUS_ACTIVE, STATUS_SOLD, STATUS_REMOVED};
-use brea_scrapers::{ScraperType, ScrapeQuery, ScraperFactory};
-use clap::{Parser, Subcommand, ValueEnum};
+use brea_core::districts::validate_district;
+use brea_core::db::{PropertyQueryBuilder, RepairOptions, SaveOutcome, DEFAULT_PRICE_HISTORY_RETENTION};
+use brea_core::geocode::{Geocoder, NominatimGeocoder, NOMINATIM_URL};
+use brea_core::export::{PropertyExport, TimestampFormat};
+use brea_core::images::{download_property_images, ImageMode, DEFAULT_IMAGE_CONCURRENCY};
+use brea_core::notify::{Notification, Notifier, WebhookNotifier};
+use brea_core::db::migrations::{apply_migrations, rollback_migration};
+use brea_core::db::types::{DbPropertyStatus, DbTimestamp, STATUS_ACTIVE, STATUS_SOLD, STATUS_REMOVED};
+use brea_scrapers::{
+    scrape_districts, ArgenPropScraper, ScraperConfig, ScraperType, ScrapeQuery, ScraperFactory, DEFAULT_PAGE_CAP,
+};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
 use csv::Writer;
-use std::path::PathBuf;
-use tracing::{info, Level};
+use prettytable::{row, Table};
+use std::path::{Path, PathBuf};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn, Level};
 use std::sync::Arc;
-use std::str::FromStr;
+
+/// Environment variable naming the database file when `--database` isn't given
+const DATABASE_ENV: &str = "BREA_DATABASE";
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
 struct Cli {
+    /// Only log warnings and errors (-q, --quiet)
+    #[arg(short = 'q', long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Log more: -v for debug messages, -vv for everything (-v, --verbose)
+    #[arg(short = 'v', long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+impl Cli {
+    /// Most detailed level logged, INFO unless `--quiet` or `--verbose` say
+    /// otherwise.
+    fn log_level(&self) -> Level {
+        match (self.quiet, self.verbose) {
+            (true, _) => Level::WARN,
+            (false, 0) => Level::INFO,
+            (false, 1) => Level::DEBUG,
+            (false, _) => Level::TRACE,
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Scrape property listings from various sources
     #[command(about = "Scrape property listings from various sources")]
     #[command(long_about = "Scrape property listings from various sources. Currently supports ArgenProp.")]
     Scrape(ScrapeCommand),
+
+    /// Scrape a list of districts, resuming interrupted runs
+    #[command(about = "Scrape a list of districts, resuming interrupted runs")]
+    #[command(long_about = "Scrape every district listed in a file for the given property types, saving listings as each page is fetched. Progress is kept in a checkpoint file so a crashed or interrupted run continues where it stopped.")]
+    ScrapeAll(ScrapeAllCommand),
+
+    /// Scrape an ArgenProp results page from its URL
+    #[command(about = "Scrape an ArgenProp results page from its URL")]
+    #[command(long_about = "Scrape a results page built on argenprop.com, for example with filters the scrape command doesn't offer. The district, property type and operation to store the listings under are given as options.")]
+    ScrapeUrl(ScrapeUrlCommand),
     
     /// List properties from the database
     #[command(about = "List properties from the database")]
     #[command(long_about = "List properties from the database with optional filtering and sorting.")]
     List(ListCommand),
     
-    /// Export property data to CSV
-    #[command(about = "Export property data to CSV")]
-    #[command(long_about = "Export property data to a CSV file for external analysis.")]
+    /// Search properties by keyword
+    #[command(about = "Search properties by keyword")]
+    #[command(long_about = "Full-text search over property titles, descriptions and addresses.")]
+    Search(SearchCommand),
+
+    /// Tag properties to keep track of them
+    #[command(about = "Tag properties to keep track of them")]
+    #[command(long_about = "Put one or more free-form tags on a property, such as \"shortlist\" or \"visited\". A tag the property already has is left as is. List tagged properties with `list --tag`.")]
+    Tag(TagCommand),
+
+    /// Remove tags from a property
+    #[command(about = "Remove tags from a property")]
+    #[command(long_about = "Take one or more tags off a property. Tags it doesn't have are reported and skipped.")]
+    Untag(TagCommand),
+
+    /// Write notes about a property
+    #[command(about = "Write notes about a property")]
+    #[command(long_about = "Set the free-text notes of a property, replacing any it had, or clear them with --clear. Notes show up in list and are kept when the listing is scraped again.")]
+    Note(NoteCommand),
+
+    /// Fill in missing coordinates from property addresses
+    #[command(about = "Fill in missing coordinates from property addresses")]
+    #[command(long_about = "Geocode the addresses of properties that have no coordinates yet using OpenStreetMap Nominatim. Requests are rate limited; addresses that can't be located are left without coordinates.")]
+    Geocode(GeocodeCommand),
+
+    /// Alert on new listings matching saved searches
+    #[command(about = "Alert on new listings matching saved searches")]
+    #[command(long_about = "Manage saved searches and, by default, scrape them and print the listings that appeared since the last run and match any of them.")]
+    Watch(WatchCommand),
+
+    /// Summarize the properties in the database
+    #[command(about = "Summarize the properties in the database")]
+    #[command(long_about = "Show listing counts by status and property type, price and size averages, and price per square meter by district.")]
+    Stats(StatsCommand),
+
+    /// Show listings whose price recently dropped
+    #[command(about = "Show listings whose price recently dropped")]
+    #[command(long_about = "List properties whose latest price is at least a given percentage below a higher price recorded in the last days, largest drop first.")]
+    PriceDrops(PriceDropsCommand),
+
+    /// Find listings posted more than once
+    #[command(about = "Find listings posted more than once")]
+    #[command(long_about = "Group active listings that share a price and either an address and room count or a title, ignoring case and punctuation. With --apply, every listing but the oldest of each group is marked removed.")]
+    Dedupe(DedupeCommand),
+
+    /// Correct stored prices after a price parsing fix
+    #[command(about = "Correct stored prices after a price parsing fix")]
+    #[command(long_about = "Fetch the detail page of every active listing again and store the price read from it, recording each corrected price in the history. With --raw-only nothing is fetched: prices in USD are recomputed from each listing's stored price and currency.")]
+    ReprocessPrices(ReprocessPricesCommand),
+
+    /// List the available scrapers
+    #[command(about = "List the available scrapers")]
+    #[command(long_about = "List every scraper that can be picked with --scraper, with the property types it supports.")]
+    Scrapers,
+
+    /// Check a scraper still understands its site
+    #[command(about = "Check a scraper still understands its site")]
+    #[command(long_about = "Scrape one known results page and report which selectors matched and whether complete listings came out, to catch markup changes before a long scrape. Network failures are reported apart from markup problems.")]
+    Doctor(DoctorCommand),
+
+    /// Export property data to CSV or JSON Lines
+    #[command(about = "Export property data to CSV or JSON Lines")]
+    #[command(long_about = "Export property data to a CSV file for external analysis, or stream it as JSON Lines for large databases.")]
     Export(ExportCommand),
 
     /// Update existing properties with fresh data
@@ -42,6 +145,21 @@ enum Commands {
     #[command(long_about = "Update existing properties by re-scraping their listings. Uses the original property type and location for efficient updates.")]
     Update(UpdateCommand),
 
+    /// Show what changed since the last scrape of a search
+    #[command(about = "Show what changed since the last scrape of a search")]
+    #[command(long_about = "Scrape a district and property type again, save the listings and print those that are new, those whose price changed and, when every page was reached, those no longer listed. Listings that disappeared are left active.")]
+    Diff(DiffCommand),
+
+    /// Serve the database as a JSON API
+    #[command(about = "Serve the database as a JSON API")]
+    #[command(long_about = "Start an HTTP server answering GET /properties (with the list filters as query parameters), GET /properties/:id and GET /properties/:id/price-history with JSON. The API is read-only.")]
+    Serve(ServeCommand),
+
+    /// Merge another brea database into this one
+    #[command(about = "Merge another brea database into this one")]
+    #[command(long_about = "Bring in the listings of another database, e.g. one scraped on another machine. Listings are matched by source and listing id; the most recently seen copy wins, notes, tags and coordinates are kept, and price histories are combined without repeats. Photos aren't merged. Both databases must be up to date with `database -a up`.")]
+    Merge(MergeCommand),
+
     /// Manage database migrations
     #[command(about = "Manage database migrations")]
     #[command(long_about = "Apply or rollback database migrations, and view migration status.")]
@@ -61,6 +179,23 @@ impl From<CliScraperType> for ScraperType {
     }
 }
 
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum CliOperation {
+    Sale,
+    Rent,
+    TemporaryRent,
+}
+
+impl From<CliOperation> for Operation {
+    fn from(operation: CliOperation) -> Self {
+        match operation {
+            CliOperation::Sale => Operation::Sale,
+            CliOperation::Rent => Operation::Rent,
+            CliOperation::TemporaryRent => Operation::TemporaryRent,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, ValueEnum)]
 pub enum CliPropertyStatus {
     Active,
@@ -78,6 +213,23 @@ impl From<CliPropertyStatus> for DbPropertyStatus {
     }
 }
 
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum CliImageMode {
+    None,
+    Primary,
+    All,
+}
+
+impl From<CliImageMode> for ImageMode {
+    fn from(mode: CliImageMode) -> Self {
+        match mode {
+            CliImageMode::None => ImageMode::None,
+            CliImageMode::Primary => ImageMode::Primary,
+            CliImageMode::All => ImageMode::All,
+        }
+    }
+}
+
 #[derive(Parser)]
 #[command(about = "Scrape property listings")]
 #[command(long_about = "Scrape property listings from various sources. Currently supports ArgenProp.")]
@@ -86,6 +238,10 @@ struct ScrapeCommand {
     #[arg(short = 'x', long, value_enum, default_value_t = CliScraperType::Argenprop)]
     scraper: CliScraperType,
 
+    /// Send requests through this HTTP or HTTPS proxy (--proxy)
+    #[arg(long)]
+    proxy: Option<String>,
+
     /// The district to search in (-n, --district)
     #[arg(short = 'n', long)]
     district: String,
@@ -94,6 +250,10 @@ struct ScrapeCommand {
     #[arg(short = 't', long, value_enum, num_args = 1.., value_delimiter = ',')]
     property_type: Vec<PropertyType>,
 
+    /// Listing operation: sale, rent or temporary-rent (-O, --operation)
+    #[arg(short = 'O', long, value_enum, default_value_t = CliOperation::Sale)]
+    operation: CliOperation,
+
     /// Minimum price in USD (-p, --min-price)
     #[arg(short = 'p', long)]
     min_price: Option<f64>,
@@ -114,8 +274,119 @@ struct ScrapeCommand {
     #[arg(short = 'c', long, default_value_t = 1)]
     max_pages: u32,
 
-    /// Database file path (-d, --database)
-    #[arg(short = 'd', long, default_value = "brea.db")]
+    /// First results page to scrape (--start-page)
+    #[arg(long, default_value_t = 1)]
+    start_page: u32,
+
+    /// Last results page to scrape, overriding --max-pages (--end-page)
+    #[arg(long)]
+    end_page: Option<u32>,
+
+    /// Never scrape more pages than this, whatever --max-pages or --end-page say (--page-cap)
+    #[arg(long, default_value_t = DEFAULT_PAGE_CAP)]
+    page_cap: u32,
+
+    /// POST new listings and price drops as JSON to this URL (--webhook-url)
+    #[arg(long)]
+    webhook_url: Option<String>,
+
+    /// No longer needed, listings without a price are always skipped
+    #[arg(long, hide = true)]
+    skip_incomplete: bool,
+
+    /// Fetch each listing's detail page for its full description, photos and coordinates (--detail)
+    #[arg(long)]
+    detail: bool,
+
+    /// Which listing photos to download: none, primary (one per listing) or all (--images)
+    #[arg(long, value_enum, default_value_t = CliImageMode::None)]
+    images: CliImageMode,
+
+    /// Same as --images all
+    #[arg(short = 'i', long, hide = true)]
+    download_images: bool,
+
+    /// Directory downloaded photos are stored in (--images-dir)
+    #[arg(long, default_value = "images")]
+    images_dir: PathBuf,
+
+    /// Number of photos of a listing downloaded at once (--image-concurrency)
+    #[arg(long, default_value_t = DEFAULT_IMAGE_CONCURRENCY)]
+    image_concurrency: usize,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
+    database: PathBuf,
+}
+
+#[derive(Parser)]
+#[command(about = "Scrape an ArgenProp results page from its URL")]
+struct ScrapeUrlCommand {
+    /// argenprop.com results page URL
+    url: String,
+
+    /// Send requests through this HTTP or HTTPS proxy (--proxy)
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// District the listings are stored under (-n, --district)
+    #[arg(short = 'n', long)]
+    district: String,
+
+    /// Type of property the listings are stored as (-t, --property-type)
+    #[arg(short = 't', long, value_enum)]
+    property_type: PropertyType,
+
+    /// Listing operation: sale, rent or temporary-rent (-O, --operation)
+    #[arg(short = 'O', long, value_enum, default_value_t = CliOperation::Sale)]
+    operation: CliOperation,
+
+    /// POST new listings and price drops as JSON to this URL (--webhook-url)
+    #[arg(long)]
+    webhook_url: Option<String>,
+
+    /// No longer needed, listings without a price are always skipped
+    #[arg(long, hide = true)]
+    skip_incomplete: bool,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
+    database: PathBuf,
+}
+
+#[derive(Parser)]
+#[command(about = "Scrape a list of districts, resuming interrupted runs")]
+struct ScrapeAllCommand {
+    /// The scraper to use (-x, --scraper)
+    #[arg(short = 'x', long, value_enum, default_value_t = CliScraperType::Argenprop)]
+    scraper: CliScraperType,
+
+    /// Send requests through this HTTP or HTTPS proxy (--proxy)
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// File with one district per line; blank lines and # comments are skipped (-D, --districts-file)
+    #[arg(short = 'D', long)]
+    districts_file: PathBuf,
+
+    /// Type of property (-t, --property-type). Can be specified multiple times.
+    #[arg(short = 't', long, value_enum, num_args = 1.., value_delimiter = ',', required = true)]
+    property_type: Vec<PropertyType>,
+
+    /// Maximum number of pages to scrape per district and type (-c, --max-pages)
+    #[arg(short = 'c', long, default_value_t = 10)]
+    max_pages: u32,
+
+    /// Number of districts and types scraped at once (-j, --concurrency)
+    #[arg(short = 'j', long, default_value_t = 2)]
+    concurrency: usize,
+
+    /// Progress file used to resume interrupted runs (--checkpoint)
+    #[arg(long, default_value = "scrape-all.checkpoint.json")]
+    checkpoint: PathBuf,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
     database: PathBuf,
 }
 
@@ -123,14 +394,18 @@ struct ScrapeCommand {
 #[command(about = "List properties from the database")]
 #[command(long_about = "List properties from the database with optional filtering and sorting.")]
 struct ListCommand {
-    /// Database file path (-d, --database)
-    #[arg(short = 'd', long, default_value = "brea.db")]
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
     database: PathBuf,
 
     /// Source to filter by (-f, --source)
     #[arg(short = 'f', long)]
     source: Option<String>,
 
+    /// Type of property to filter by (-t, --property-type)
+    #[arg(short = 't', long, value_enum)]
+    property_type: Option<PropertyType>,
+
     /// Minimum price in USD (-p, --min-price)
     #[arg(short = 'p', long)]
     min_price: Option<f64>,
@@ -147,6 +422,42 @@ struct ListCommand {
     #[arg(short = 'M', long)]
     max_size: Option<f64>,
 
+    /// Minimum price per square meter in USD (--min-price-per-m2)
+    #[arg(long)]
+    min_price_per_m2: Option<f64>,
+
+    /// Maximum price per square meter in USD (--max-price-per-m2)
+    #[arg(long)]
+    max_price_per_m2: Option<f64>,
+
+    /// Minimum number of rooms (--min-rooms)
+    #[arg(long)]
+    min_rooms: Option<i32>,
+
+    /// Maximum number of rooms (--max-rooms)
+    #[arg(long)]
+    max_rooms: Option<i32>,
+
+    /// Minimum number of bathrooms, toilettes not counted (--min-baths)
+    #[arg(long)]
+    min_baths: Option<i32>,
+
+    /// Only listings that say they come with parking (--parking)
+    #[arg(long)]
+    parking: bool,
+
+    /// Only listings carrying this tag (--tag)
+    #[arg(long)]
+    tag: Option<String>,
+
+    /// Minimum building age in years (--min-age)
+    #[arg(long)]
+    min_age: Option<i32>,
+
+    /// Maximum building age in years (--max-age)
+    #[arg(long)]
+    max_age: Option<i32>,
+
     /// Maximum number of properties to display (-l, --limit)
     #[arg(short = 'l', long, default_value_t = 10)]
     limit: i64,
@@ -167,26 +478,307 @@ struct ListCommand {
     #[arg(short = 'g', long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=5))]
     graph_height: u8,
 
+    /// Graph the moving median over this many prices to hide spikes (--smooth)
+    #[arg(long, default_value_t = 0)]
+    smooth: usize,
+
+    /// Only graph prices seen in the last this many days (--graph-days)
+    #[arg(long)]
+    graph_days: Option<i64>,
+
     /// Property status to filter by (-S, --status)
     #[arg(short = 'S', long, value_enum, default_value_t = CliPropertyStatus::Active)]
     status: CliPropertyStatus,
+
+    /// Only listings first stored at or after this RFC 3339 timestamp (--added-since)
+    #[arg(long)]
+    added_since: Option<String>,
+
+    /// Only listings that changed at or after this RFC 3339 timestamp (--updated-since)
+    #[arg(long)]
+    updated_since: Option<String>,
+
+    /// Print only the number of matching properties (--count)
+    #[arg(long)]
+    count: bool,
+
+    /// How to print the listings (-O, --output-format)
+    #[arg(short = 'O', long, value_enum, default_value_t = ListFormat::Pretty)]
+    output_format: ListFormat,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ListFormat {
+    /// Each listing with its details and price graph
+    Pretty,
+    /// A JSON array of the listings
+    Json,
+    /// One aligned line per listing
+    Table,
+}
+
+#[derive(Parser)]
+#[command(about = "Search properties by keyword")]
+#[command(long_about = "Full-text search over property titles, descriptions and addresses. Supports SQLite FTS5 syntax such as OR, \"exact phrases\" and prefix*.")]
+struct SearchCommand {
+    /// Words to search for, e.g. "terraza cochera"
+    query: String,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
+    database: PathBuf,
+}
+
+#[derive(Parser)]
+struct TagCommand {
+    /// Id of the property, as shown by `list`
+    id: i64,
+
+    /// Tags to add or remove, e.g. shortlist
+    #[arg(required = true)]
+    tags: Vec<String>,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
+    database: PathBuf,
+}
+
+#[derive(Parser)]
+struct NoteCommand {
+    /// Id of the property, as shown by `list`
+    id: i64,
+
+    /// The notes, e.g. "ask about the terrace"
+    #[arg(required_unless_present = "clear")]
+    text: Option<String>,
+
+    /// Remove the notes instead (--clear)
+    #[arg(long, conflicts_with = "text")]
+    clear: bool,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
+    database: PathBuf,
+}
+
+#[derive(Parser)]
+struct MergeCommand {
+    /// Database file to merge from, left unchanged
+    other: PathBuf,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
+    database: PathBuf,
+}
+
+#[derive(Parser)]
+#[command(about = "Alert on new listings matching saved searches")]
+struct WatchCommand {
+    /// What to do with saved searches (-a, --action)
+    #[arg(short = 'a', long, value_enum, default_value_t = WatchAction::Run)]
+    action: WatchAction,
+
+    /// The scraper to use (-x, --scraper)
+    #[arg(short = 'x', long, value_enum, default_value_t = CliScraperType::Argenprop)]
+    scraper: CliScraperType,
+
+    /// Send requests through this HTTP or HTTPS proxy (--proxy)
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// District of the search to add (-n, --district)
+    #[arg(short = 'n', long)]
+    district: Option<String>,
+
+    /// Type of property of the search to add (-t, --property-type)
+    #[arg(short = 't', long, value_enum)]
+    property_type: Option<PropertyType>,
+
+    /// Minimum price in USD (-p, --min-price)
+    #[arg(short = 'p', long)]
+    min_price: Option<f64>,
+
+    /// Maximum price in USD (-P, --max-price)
+    #[arg(short = 'P', long)]
+    max_price: Option<f64>,
+
+    /// Minimum size in square meters (-m, --min-size)
+    #[arg(short = 'm', long)]
+    min_size: Option<f64>,
+
+    /// Maximum size in square meters (-M, --max-size)
+    #[arg(short = 'M', long)]
+    max_size: Option<f64>,
+
+    /// Maximum number of pages to scrape per search (-c, --max-pages)
+    #[arg(short = 'c', long, default_value_t = 1)]
+    max_pages: u32,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
+    database: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum WatchAction {
+    /// Scrape the saved searches and print new matches
+    Run,
+    /// Save a search from the filter flags
+    Add,
+    /// List saved searches
+    List,
+}
+
+#[derive(Parser)]
+#[command(about = "Serve the database as a JSON API")]
+struct ServeCommand {
+    /// Address to listen on (-H, --host)
+    #[arg(short = 'H', long, default_value = "127.0.0.1")]
+    host: String,
+
+    /// Port to listen on (-p, --port)
+    #[arg(short = 'p', long, default_value_t = 8080)]
+    port: u16,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
+    database: PathBuf,
+}
+
+#[derive(Parser)]
+#[command(about = "Summarize the properties in the database")]
+#[command(long_about = "Summarize the properties in the database. With a district and a property type, show the price and USD/m² spread of those listings instead.")]
+struct StatsCommand {
+    /// Only listings in this district (-n, --district), needs --type
+    #[arg(short = 'n', long, requires = "property_type")]
+    district: Option<String>,
+
+    /// Only listings of this type (-t, --type), needs --district
+    #[arg(short = 't', long = "type", value_enum, requires = "district")]
+    property_type: Option<PropertyType>,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
+    database: PathBuf,
+}
+
+#[derive(Parser)]
+#[command(about = "Check a scraper still understands its site")]
+struct DoctorCommand {
+    /// The scraper to check (-x, --scraper)
+    #[arg(short = 'x', long, value_enum, default_value_t = CliScraperType::Argenprop)]
+    scraper: CliScraperType,
+
+    /// Send requests through this HTTP or HTTPS proxy (--proxy)
+    #[arg(long)]
+    proxy: Option<String>,
+}
+
+#[derive(Parser)]
+#[command(about = "Show listings whose price recently dropped")]
+struct PriceDropsCommand {
+    /// How many days of price history to look at (-D, --days)
+    #[arg(short = 'D', long, default_value_t = 30)]
+    days: i64,
+
+    /// Smallest drop to report, in percent (-m, --min-pct)
+    #[arg(short = 'm', long, default_value_t = 5.0)]
+    min_pct: f64,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
+    database: PathBuf,
+}
+
+#[derive(Parser)]
+#[command(about = "Find listings posted more than once")]
+struct DedupeCommand {
+    /// Mark all but the oldest listing of each group as removed (--apply)
+    #[arg(long)]
+    apply: bool,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
+    database: PathBuf,
+}
+
+#[derive(Parser)]
+#[command(about = "Correct stored prices after a price parsing fix")]
+struct ReprocessPricesCommand {
+    /// Recompute prices from the stored price and currency instead of fetching listings (--raw-only)
+    #[arg(long)]
+    raw_only: bool,
+
+    /// The scraper to use (-x, --scraper)
+    #[arg(short = 'x', long, value_enum, default_value_t = CliScraperType::Argenprop)]
+    scraper: CliScraperType,
+
+    /// Send requests through this HTTP or HTTPS proxy (--proxy)
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
+    database: PathBuf,
+}
+
+#[derive(Parser)]
+#[command(about = "Fill in missing coordinates from property addresses")]
+struct GeocodeCommand {
+    /// Maximum number of properties to geocode (-l, --limit)
+    #[arg(short = 'l', long)]
+    limit: Option<i64>,
+
+    /// Nominatim server to query (-u, --nominatim-url)
+    #[arg(short = 'u', long, default_value = NOMINATIM_URL)]
+    nominatim_url: String,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
+    database: PathBuf,
 }
 
 #[derive(Parser)]
-#[command(about = "Export property data to CSV")]
-#[command(long_about = "Export property data to a CSV file for external analysis.")]
+#[command(about = "Export property data to CSV or JSON Lines")]
+#[command(long_about = "Export property data to a CSV file for external analysis, or stream it as JSON Lines for large databases.")]
 struct ExportCommand {
     /// Output file path (-o, --output)
     #[arg(short = 'o', long, default_value = "properties.csv")]
     output: PathBuf,
 
-    /// Database file path (-d, --database)
-    #[arg(short = 'd', long, default_value = "brea.db")]
+    /// Output format (-f, --format)
+    #[arg(short = 'f', long, value_enum, default_value_t = ExportFormat::Csv)]
+    format: ExportFormat,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
     database: PathBuf,
 
     /// Property status to filter by (-S, --status)
     #[arg(short = 'S', long, value_enum, default_value_t = CliPropertyStatus::Active)]
     status: CliPropertyStatus,
+
+    /// Only export properties created or changed at or after this RFC 3339
+    /// timestamp, e.g. 2024-03-20T00:00:00Z (--since)
+    #[arg(long)]
+    since: Option<String>,
+
+    /// Write created_at, updated_at and last_seen_at as Unix epoch seconds
+    /// instead of RFC 3339 (--epoch)
+    #[arg(long)]
+    epoch: bool,
+
+    /// Include each property's price history, JSON Lines only (--with-history)
+    #[arg(long)]
+    with_history: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    /// Comma-separated values, loaded in memory
+    Csv,
+    /// One JSON object per line, streamed from the database
+    Jsonl,
 }
 
 #[derive(Parser)]
@@ -196,12 +788,60 @@ struct UpdateCommand {
     #[arg(short = 'x', long, value_enum, default_value_t = CliScraperType::Argenprop)]
     scraper: CliScraperType,
 
+    /// Send requests through this HTTP or HTTPS proxy (--proxy)
+    #[arg(long)]
+    proxy: Option<String>,
+
     /// Maximum number of pages to scrape (-c, --max-pages)
     #[arg(short = 'c', long)]
     max_pages: Option<u32>,
 
-    /// Database file path (-d, --database)
-    #[arg(short = 'd', long, default_value = "brea.db")]
+    /// POST new listings and price drops as JSON to this URL (--webhook-url)
+    #[arg(long)]
+    webhook_url: Option<String>,
+
+    /// Print only the listings whose price changed, then a summary (--changes-only)
+    #[arg(long)]
+    changes_only: bool,
+
+    /// Also print the listings this run marked sold (--status-changes)
+    #[arg(long)]
+    status_changes: bool,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
+    database: PathBuf,
+}
+
+#[derive(Parser)]
+#[command(about = "Show what changed since the last scrape of a search")]
+struct DiffCommand {
+    /// The scraper to use (-x, --scraper)
+    #[arg(short = 'x', long, value_enum, default_value_t = CliScraperType::Argenprop)]
+    scraper: CliScraperType,
+
+    /// Send requests through this HTTP or HTTPS proxy (--proxy)
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// District to scrape (-n, --district)
+    #[arg(short = 'n', long)]
+    district: String,
+
+    /// Type of property to scrape (-t, --property-type)
+    #[arg(short = 't', long, value_enum)]
+    property_type: PropertyType,
+
+    /// Listing operation: sale, rent or temporary-rent (-O, --operation)
+    #[arg(short = 'O', long, value_enum, default_value_t = CliOperation::Sale)]
+    operation: CliOperation,
+
+    /// Maximum number of pages to scrape (-c, --max-pages)
+    #[arg(short = 'c', long, default_value_t = DEFAULT_PAGE_CAP)]
+    max_pages: u32,
+
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
     database: PathBuf,
 }
 
@@ -211,8 +851,48 @@ enum SortOrder {
     Desc,
 }
 
+fn scraper_config(proxy: &Option<String>) -> ScraperConfig {
+    ScraperConfig { proxy: proxy.clone(), ..Default::default() }
+}
+
+/// Warns about a district missing from the known ones, which usually is a
+/// typo that makes the scrape come back empty.
+fn warn_unknown_district(district: &str) {
+    if let Err(e) = validate_district(district) {
+        warn!("{}, scraping it anyway", e);
+    }
+}
+
+/// Token triggered by the first Ctrl-C, so a long scrape can stop after the
+/// page it is on and still save what it gathered. A second Ctrl-C exits
+/// right away.
+fn cancel_on_ctrl_c() -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let token = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            warn!("Interrupted, stopping after the current page (Ctrl-C again to quit now)");
+            token.cancel();
+        }
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+    cancel
+}
+
+/// `--skip-incomplete` is still accepted so existing scripts keep working.
+fn warn_skip_incomplete(skip_incomplete: bool) {
+    if skip_incomplete {
+        warn!("--skip-incomplete is deprecated and has no effect, listings without a price are always skipped");
+    }
+}
+
 async fn scrape_properties(cmd: &ScrapeCommand, db: Arc<Database>) -> Result<()> {
-    let scraper = ScraperFactory::create_scraper(cmd.scraper.into());
+    warn_skip_incomplete(cmd.skip_incomplete);
+    warn_unknown_district(&cmd.district);
+    let cancel = cancel_on_ctrl_c();
+    let scraper = ScraperFactory::create_scraper_with_config(cmd.scraper.into(), &scraper_config(&cmd.proxy))?;
     let query = ScrapeQuery::new(
         cmd.district.clone(),
         cmd.property_type[0].clone(),
@@ -220,31 +900,159 @@ async fn scrape_properties(cmd: &ScrapeCommand, db: Arc<Database>) -> Result<()>
         cmd.max_price,
         cmd.min_size,
         cmd.max_size,
-        Some(Arc::clone(&db)),
-    );
+        Some(db.clone()),
+    )
+    .with_operation(cmd.operation.into())
+    .with_page_cap(cmd.page_cap)
+    .with_cancellation(cancel.clone());
+    let query = match cmd.end_page {
+        Some(end_page) => query.with_page_range(cmd.start_page, end_page)?,
+        None => query.with_page(cmd.start_page),
+    };
 
-    let results = scraper.scrape_listing(query, cmd.max_pages).await?;
+    let mut results = scraper.scrape_listing(query, cmd.max_pages).await?.listings;
     let results_len = results.len();
 
+    if cmd.detail {
+        for (property, images) in results.iter_mut() {
+            if cancel.is_cancelled() {
+                break;
+            }
+            if let Err(e) = scraper.enrich_with_detail(property, images).await {
+                warn!("Failed to fetch the detail page of {}: {}", property.url, e);
+            }
+        }
+    }
+
+    let notifier = cmd.webhook_url.as_deref().map(WebhookNotifier::new);
+    for (property, _images) in results.iter_mut() {
+        let Some(outcome) = db.save_property_or_skip(property).await? else {
+            continue;
+        };
+        if let Some(notifier) = &notifier {
+            send_notification(notifier, property, &outcome).await;
+        }
+    }
+    // Listings skipped for want of an exchange rate were never stored
+    results.retain(|(property, _)| property.id != 0);
+
+    let image_mode = if cmd.download_images { ImageMode::All } else { cmd.images.into() };
+    if image_mode != ImageMode::None {
+        download_images(&mut results, &db, &cmd.images_dir, image_mode, cmd.image_concurrency).await?;
+    }
+
     let mut displays = Vec::new();
     for (property, _images) in &results {
         let price_history = db.get_price_history(property.id).await?;
         displays.push(PropertyDisplay::new(property.clone(), price_history));
     }
     for display in &displays {
-        println!("{}", display.to_string());
+        println!("{}", display);
     }
 
     info!("Found {} properties", results_len);
     Ok(())
 }
 
-async fn update_properties(cmd: &UpdateCommand, db: Arc<Database>) -> Result<()> {
-    let scraper = ScraperFactory::create_scraper(cmd.scraper.into());
+async fn scrape_all(cmd: &ScrapeAllCommand, db: &Database) -> Result<()> {
+    let districts: Vec<String> = std::fs::read_to_string(&cmd.districts_file)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect();
+    if districts.is_empty() {
+        return Err(BreaError::InvalidArgument(format!(
+            "No districts found in {}",
+            cmd.districts_file.display()
+        )));
+    }
+    for district in &districts {
+        warn_unknown_district(district);
+    }
+
+    let scraper = ScraperFactory::create_scraper_with_config(cmd.scraper.into(), &scraper_config(&cmd.proxy))?;
+    let saved = scrape_districts(
+        scraper.as_ref(),
+        db,
+        &districts,
+        &cmd.property_type,
+        &cmd.checkpoint,
+        cmd.max_pages,
+        cmd.concurrency,
+    )
+    .await?;
+
+    info!("Saved {} properties across {} districts", saved, districts.len());
+    Ok(())
+}
+
+async fn scrape_url(cmd: &ScrapeUrlCommand, db: &Database) -> Result<()> {
+    warn_skip_incomplete(cmd.skip_incomplete);
+    let scraper = ArgenPropScraper::with_config(&scraper_config(&cmd.proxy))?;
+    let query = ScrapeQuery::new(cmd.district.clone(), cmd.property_type.clone(), None, None, None, None, None)
+        .with_operation(cmd.operation.into());
+    let (mut results, _) = scraper.scrape_url(&cmd.url, &query).await?;
+
+    let notifier = cmd.webhook_url.as_deref().map(WebhookNotifier::new);
+    for (property, _images) in results.iter_mut() {
+        let Some(outcome) = db.save_property_or_skip(property).await? else {
+            continue;
+        };
+        if let Some(notifier) = &notifier {
+            send_notification(notifier, property, &outcome).await;
+        }
+    }
+    // Listings skipped for want of an exchange rate were never stored
+    results.retain(|(property, _)| property.id != 0);
+
+    for (property, _images) in &results {
+        let price_history = db.get_price_history(property.id).await?;
+        println!("{}", PropertyDisplay::new(property.clone(), price_history));
+    }
+
+    info!("Found {} properties", results.len());
+    Ok(())
+}
+
+/// Notifies about a saved listing if it's new or got cheaper. Delivery
+/// failures are only logged so a flaky endpoint doesn't stop a scrape.
+async fn send_notification(notifier: &dyn Notifier, property: &Property, outcome: &SaveOutcome) {
+    if let Some(notification) = Notification::from_outcome(property, outcome) {
+        if let Err(e) = notifier.notify(&notification).await {
+            warn!("Failed to send notification for {}: {}", property.external_id, e);
+        }
+    }
+}
+
+async fn download_images(
+    results: &mut [(Property, Vec<PropertyImage>)],
+    db: &Database,
+    images_dir: &Path,
+    mode: ImageMode,
+    concurrency: usize,
+) -> Result<()> {
+    let client = reqwest::Client::new();
+    let mut downloaded = 0;
+
+    for (property, images) in results.iter_mut() {
+        downloaded += download_property_images(db, &client, property.id, images, images_dir, mode, concurrency).await?;
+    }
+
+    info!("Downloaded {} images to {}", downloaded, images_dir.display());
+    Ok(())
+}
+
+async fn update_properties(cmd: &UpdateCommand, db: Arc<Database>) -> Result<()> {
+    let scraper = ScraperFactory::create_scraper_with_config(cmd.scraper.into(), &scraper_config(&cmd.proxy))?;
     let properties = db.get_properties().await?;
+    let (mut inserted, mut rose, mut fell, mut unchanged) = (0, 0, 0, 0);
+    let notifier = cmd.webhook_url.as_deref().map(WebhookNotifier::new);
+    let mut saved = Vec::new();
+    let mut sold_ids = Vec::new();
 
     for property in properties {
-        if let Some(property_type) = property.property_type.as_ref().and_then(|t| PropertyType::from_str(t).ok()) {
+        if let Some(property_type) = property.property_type.clone() {
             let query = ScrapeQuery::new(
                 property.district.clone(),
                 property_type,
@@ -252,37 +1060,150 @@ async fn update_properties(cmd: &UpdateCommand, db: Arc<Database>) -> Result<()>
                 None,
                 None, // No size filters for updates
                 None,
-                Some(Arc::clone(&db)),
-            );
+                Some(db.clone()),
+            )
+            .with_operation(property.operation);
 
-            let mut results = scraper.scrape_listing(query, cmd.max_pages.unwrap_or(1)).await?;
+            let scrape = scraper.scrape_listing(query, cmd.max_pages.unwrap_or(1)).await?;
+            sold_ids.extend(scrape.marked_sold);
+            let mut results = scrape.listings;
             
             // Save updated properties first
             for (ref mut property, _images) in &mut results {
-                db.save_property(property).await?;
+                let Some(outcome) = db.save_property_or_skip(property).await? else {
+                    continue;
+                };
+                if let Some(notifier) = &notifier {
+                    send_notification(notifier, property, &outcome).await;
+                }
+                match &outcome {
+                    SaveOutcome::Inserted => inserted += 1,
+                    SaveOutcome::Updated { price_change: Some(change) } if change.new_price > change.old_price => rose += 1,
+                    SaveOutcome::Updated { price_change: Some(_) } => fell += 1,
+                    SaveOutcome::Updated { price_change: None } | SaveOutcome::Unchanged => unchanged += 1,
+                }
+                if cmd.changes_only {
+                    saved.push((property.clone(), outcome));
+                }
             }
             
+            results.retain(|(property, _)| property.id != 0);
+
             // Display updated properties in the same format as the list command
-            let mut displays = Vec::new();
-            for (property, _images) in &results {
-                let price_history = db.get_price_history(property.id).await?;
-                displays.push(PropertyDisplay::new(property.clone(), price_history));
-            }
-            for display in &displays {
-                println!("{}", display.to_string());
+            if !cmd.changes_only {
+                let mut displays = Vec::new();
+                for (property, _images) in &results {
+                    let price_history = db.get_price_history(property.id).await?;
+                    displays.push(PropertyDisplay::new(property.clone(), price_history));
+                }
+                for display in &displays {
+                    println!("{}", display);
+                }
             }
 
-            info!("Updated {} properties", results.len());
         }
     }
+
+    if cmd.changes_only {
+        if rose + fell > 0 {
+            price_changes_table(&saved).printstd();
+        }
+        println!(
+            "{} prices rose, {} fell, {} unchanged, {} new listings",
+            rose, fell, unchanged, inserted
+        );
+    }
+    if cmd.status_changes {
+        let mut sold = Vec::new();
+        for id in &sold_ids {
+            sold.extend(db.get_property(*id).await?);
+        }
+        if sold.is_empty() {
+            println!("No listings were marked sold");
+        } else {
+            println!("{} listings marked sold:", sold.len());
+            properties_table(&sold).printstd();
+        }
+    }
+
+    info!(
+        "Prices rose for {}, fell for {} and were unchanged for {} properties; {} new listings",
+        rose, fell, unchanged, inserted
+    );
+    Ok(())
+}
+
+/// Old and new price of each saved listing whose price changed. Listings
+/// that are new or kept their price are left out.
+fn price_changes_table(saved: &[(Property, SaveOutcome)]) -> Table {
+    price_moves_table(saved.iter().filter_map(|(property, outcome)| match outcome {
+        SaveOutcome::Updated { price_change: Some(change) } => Some((property, change.old_price, change.new_price)),
+        _ => None,
+    }))
+}
+
+/// One row per listing with its old and new price.
+fn price_moves_table<'a>(moves: impl IntoIterator<Item = (&'a Property, f64, f64)>) -> Table {
+    let mut table = Table::new();
+    table.set_titles(row!["ID", "Title", "District", "Old (USD)", "New (USD)", "Change"]);
+    for (property, old_price, new_price) in moves {
+        table.add_row(row![
+            property.id,
+            property.title,
+            property.district,
+            r->format!("{:.0}", old_price),
+            r->format!("{:.0}", new_price),
+            r->format!("{:+.1}%", (new_price - old_price) / old_price * 100.0)
+        ]);
+    }
+    table
+}
+
+async fn diff_listings(cmd: &DiffCommand, db: &Database) -> Result<()> {
+    warn_unknown_district(&cmd.district);
+    let scraper_type: ScraperType = cmd.scraper.into();
+    let scraper = ScraperFactory::create_scraper_with_config(scraper_type, &scraper_config(&cmd.proxy))?;
+    let operation = cmd.operation.into();
+    let query = ScrapeQuery::new(cmd.district.clone(), cmd.property_type.clone(), None, None, None, None, None)
+        .with_operation(operation);
+
+    let since = chrono::Utc::now();
+    let mut scrape = scraper.scrape_listing(query, cmd.max_pages).await?;
+    for (property, _images) in scrape.listings.iter_mut() {
+        db.save_property_or_skip(property).await?;
+    }
+    let external_ids: Vec<&str> = scrape.listings.iter().map(|(property, _)| property.external_id.as_str()).collect();
+    // Compare under the district the listings are filed under, which may
+    // differ from how it was asked for ("La Boca" is stored as "boca")
+    let district = scrape.listings.first().map_or(cmd.district.as_str(), |(property, _)| property.district.as_str());
+    let diff = db
+        .diff_against(&external_ids, scraper_type.name(), district, &cmd.property_type, operation, since)
+        .await?;
+
+    println!("{} new listings", diff.added.len());
+    if !diff.added.is_empty() {
+        properties_table(&diff.added).printstd();
+    }
+    println!("{} price changes", diff.price_changed.len());
+    if !diff.price_changed.is_empty() {
+        price_moves_table(diff.price_changed.iter().map(|(property, old, new)| (property, *old, *new))).printstd();
+    }
+    if scrape.complete {
+        println!("{} listings no longer listed", diff.removed.len());
+        if !diff.removed.is_empty() {
+            properties_table(&diff.removed).printstd();
+        }
+    } else {
+        println!("The last page wasn't reached, so listings that disappeared can't be told apart");
+    }
     Ok(())
 }
 
 #[derive(Parser)]
 #[command(about = "Manage database migrations")]
 struct DatabaseCommand {
-    /// Database file path (-d, --database)
-    #[arg(short = 'd', long, default_value = "brea.db")]
+    /// Database file path (-d, --database, or BREA_DATABASE)
+    #[arg(short = 'd', long, env = DATABASE_ENV, default_value = "brea.db")]
     database: PathBuf,
 
     /// Migration action to perform (-a, --action)
@@ -292,6 +1213,14 @@ struct DatabaseCommand {
     /// Target migration version for rollback (-t, --target-version)
     #[arg(short = 't', long = "target-version")]
     target_version: Option<i32>,
+
+    /// Price history entries to keep per property when cleaning up (-k, --keep)
+    #[arg(short = 'k', long, default_value_t = DEFAULT_PRICE_HISTORY_RETENTION)]
+    keep: usize,
+
+    /// Only report what repair would fix (--dry-run)
+    #[arg(long)]
+    dry_run: bool,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
@@ -300,20 +1229,26 @@ enum DatabaseAction {
     Up,
     /// Rollback to a specific version
     Down,
-    /// List applied migrations
+    /// Show every migration, whether it is applied and when
     List,
+    /// Trim price history to the most recent entries per property
+    Cleanup,
+    /// Trim price history, then vacuum and analyze the database
+    Maintenance,
+    /// Delete orphaned photos and merge listings stored twice
+    Repair,
 }
 
 async fn handle_migrations(cmd: &DatabaseCommand) -> Result<()> {
     match cmd.action {
         DatabaseAction::Up => {
-            let db = Database::new(&cmd.database).await?;
+            let db = Database::new_without_migrations(&cmd.database).await?;
             info!("Applying all pending migrations...");
             apply_migrations(db.pool()).await?;
             info!("All migrations applied successfully.");
         }
         DatabaseAction::Down => {
-            let db = Database::new(&cmd.database).await?;
+            let db = Database::new_without_migrations(&cmd.database).await?;
             let version = cmd.target_version.ok_or_else(|| {
                 BreaError::InvalidPropertyType("Target version is required for rollback".to_string())
             })?;
@@ -323,15 +1258,48 @@ async fn handle_migrations(cmd: &DatabaseCommand) -> Result<()> {
         }
         DatabaseAction::List => {
             let db = Database::new_without_migrations(&cmd.database).await?;
-            let migrations = get_applied_migrations(db.pool()).await?;
-            if migrations.is_empty() {
-                info!("No migrations have been applied.");
-            } else {
-                info!("Applied migrations:");
-                for migration in migrations {
-                    println!("{}", migration);
-                }
+            let mut table = Table::new();
+            table.add_row(row!["Version", "Status", "Applied at"]);
+            for (version, applied_at, pending) in db.migration_status().await? {
+                table.add_row(row![
+                    version,
+                    if pending { "pending" } else { "applied" },
+                    applied_at.map_or("-".to_string(), |at| at.format("%Y-%m-%d %H:%M:%S").to_string()),
+                ]);
+            }
+            table.printstd();
+        }
+        DatabaseAction::Cleanup => {
+            let db = Database::new(&cmd.database).await?;
+            let removed = db.cleanup_price_history(cmd.keep).await?;
+            info!("Removed {} price history entries, keeping the latest {} per property.", removed, cmd.keep.max(1));
+        }
+        DatabaseAction::Maintenance => {
+            let db = Database::new(&cmd.database).await?;
+            let removed = db.cleanup_price_history(cmd.keep).await?;
+            info!("Removed {} price history entries, keeping the latest {} per property.", removed, cmd.keep.max(1));
+
+            let size_before = std::fs::metadata(&cmd.database)?.len();
+            db.vacuum().await?;
+            let size_after = std::fs::metadata(&cmd.database)?.len();
+            info!("Vacuumed database: {} bytes -> {} bytes.", size_before, size_after);
+
+            db.analyze().await?;
+            info!("Query planner statistics refreshed.");
+        }
+        DatabaseAction::Repair => {
+            let db = Database::new(&cmd.database).await?;
+            let report = db.repair_integrity(RepairOptions { dry_run: cmd.dry_run, ..Default::default() }).await?;
+            for (kept, merged) in &report.merged_duplicates {
+                println!("Property {} duplicates {}", merged, kept);
             }
+            let verb = if cmd.dry_run { "Would remove" } else { "Removed" };
+            info!(
+                "{} {} orphaned photos and {} duplicate properties.",
+                verb,
+                report.orphaned_images.len(),
+                report.merged_duplicates.len()
+            );
         }
     }
     Ok(())
@@ -339,23 +1307,85 @@ async fn handle_migrations(cmd: &DatabaseCommand) -> Result<()> {
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
     // Initialize logging
+    // Logs go to stderr so `list -O json` output can be piped
     tracing_subscriber::fmt()
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env()
-            .add_directive(Level::INFO.into()))
+            .add_directive(cli.log_level().into()))
+        .with_writer(std::io::stderr)
         .init();
 
-    let cli = Cli::parse();
-
     match &cli.command {
         Commands::Scrape(cmd) => {
             let db = Arc::new(Database::new(&cmd.database).await?);
             scrape_properties(cmd, db).await
         }
+        Commands::ScrapeAll(cmd) => {
+            let db = Database::new(&cmd.database).await?;
+            scrape_all(cmd, &db).await
+        }
+        Commands::ScrapeUrl(cmd) => {
+            let db = Database::new(&cmd.database).await?;
+            scrape_url(cmd, &db).await
+        }
         Commands::List(cmd) => {
             let db = Database::new(&cmd.database).await?;
             list_properties(cmd, &db).await
         }
+        Commands::Search(cmd) => {
+            let db = Database::new(&cmd.database).await?;
+            search_properties(cmd, &db).await
+        }
+        Commands::Tag(cmd) => {
+            let db = Database::new(&cmd.database).await?;
+            tag_property(cmd, &db).await
+        }
+        Commands::Untag(cmd) => {
+            let db = Database::new(&cmd.database).await?;
+            untag_property(cmd, &db).await
+        }
+        Commands::Note(cmd) => {
+            let db = Database::new(&cmd.database).await?;
+            db.set_notes(cmd.id, cmd.text.as_deref()).await?;
+            info!("{} the notes of property {}", if cmd.clear { "Cleared" } else { "Updated" }, cmd.id);
+            Ok(())
+        }
+        Commands::Geocode(cmd) => {
+            let db = Database::new(&cmd.database).await?;
+            geocode_properties(cmd, &db).await
+        }
+        Commands::Watch(cmd) => {
+            let db = Arc::new(Database::new(&cmd.database).await?);
+            watch(cmd, db).await
+        }
+        Commands::Stats(cmd) => {
+            let db = Database::new(&cmd.database).await?;
+            match (&cmd.district, &cmd.property_type) {
+                (Some(district), Some(property_type)) => show_price_stats(&db, district, property_type).await,
+                _ => show_stats(&db).await,
+            }
+        }
+        Commands::PriceDrops(cmd) => {
+            let db = Database::new(&cmd.database).await?;
+            show_price_drops(cmd, &db).await
+        }
+        Commands::Dedupe(cmd) => {
+            let db = Database::new(&cmd.database).await?;
+            dedupe_properties(cmd, &db).await
+        }
+        Commands::ReprocessPrices(cmd) => {
+            let db = Database::new(&cmd.database).await?;
+            reprocess_prices(cmd, &db).await
+        }
+        Commands::Scrapers => {
+            list_scrapers();
+            Ok(())
+        }
+        Commands::Doctor(cmd) => {
+            run_self_test(cmd).await
+        }
         Commands::Export(cmd) => {
             let db = Database::new(&cmd.database).await?;
             export_properties(cmd, &db).await
@@ -364,6 +1394,29 @@ async fn main() -> Result<()> {
             let db = Arc::new(Database::new(&cmd.database).await?);
             update_properties(cmd, db).await
         }
+        Commands::Diff(cmd) => {
+            let db = Database::new(&cmd.database).await?;
+            diff_listings(cmd, &db).await
+        }
+        Commands::Serve(cmd) => {
+            let db = Database::new(&cmd.database).await?;
+            let listener = tokio::net::TcpListener::bind((cmd.host.as_str(), cmd.port)).await?;
+            info!("Serving {} on http://{}", cmd.database.display(), listener.local_addr()?);
+            brea_core::api::serve(db, listener).await
+        }
+        Commands::Merge(cmd) => {
+            let db = Database::new(&cmd.database).await?;
+            let report = db.merge_from(&cmd.other).await?;
+            info!(
+                "Merged {}: {} new properties, {} updated, {} already up to date, {} price history entries added",
+                cmd.other.display(),
+                report.inserted,
+                report.updated,
+                report.skipped,
+                report.price_history
+            );
+            Ok(())
+        }
         Commands::Database(cmd) => {
             handle_migrations(cmd).await
         }
@@ -371,34 +1424,668 @@ async fn main() -> Result<()> {
 }
 
 async fn list_properties(cmd: &ListCommand, db: &Database) -> Result<()> {
-    let properties = db.get_properties().await?;
-    let mut displays = Vec::new();
+    if cmd.count {
+        println!("{}", db.count_properties(list_filters(cmd)?).await?);
+        return Ok(());
+    }
+
+    let listed = list_page(cmd, db).await?;
+
+    match cmd.output_format {
+        ListFormat::Pretty => {
+            for property in &listed {
+                let price_history = match cmd.graph_days {
+                    Some(days) => {
+                        let now = chrono::Utc::now();
+                        let window = db.get_price_history_range(property.id, now - chrono::Duration::days(days), now).await?;
+                        // PropertyDisplay takes the newest price first
+                        window.into_iter().rev().collect()
+                    }
+                    None => db.get_price_history(property.id).await?,
+                };
+                let display = PropertyDisplay::new(property.clone(), price_history)
+                    .with_graph_dimensions(40, cmd.graph_height.into())
+                    .with_smoothing(cmd.smooth);
+                println!("{}", display);
+            }
+        }
+        ListFormat::Json => println!("{}", properties_json(&listed)?),
+        ListFormat::Table => {
+            properties_table(&listed).printstd();
+        }
+    }
+
+    info!("Listed {} properties", listed.len());
+    Ok(())
+}
+
+/// The filters of `list`, shared by `--count` and the listing itself.
+fn list_filters(cmd: &ListCommand) -> Result<PropertyQueryBuilder<'_>> {
+    let added_since = cmd.added_since.as_deref().map(parse_since).transpose()?;
+    let updated_since = cmd.updated_since.as_deref().map(parse_since).transpose()?;
+
+    let mut filters = PropertyQueryBuilder::new()
+        .with_status(DbPropertyStatus::from(cmd.status))
+        .with_created_range(added_since.map(DbTimestamp::from), None)
+        .with_updated_range(updated_since.map(DbTimestamp::from), None)
+        .with_price_range(cmd.min_price, cmd.max_price)
+        .with_size_range(cmd.min_size, cmd.max_size)
+        .with_price_per_m2_range(cmd.min_price_per_m2, cmd.max_price_per_m2)
+        .with_rooms_range(cmd.min_rooms, cmd.max_rooms)
+        .with_bathrooms_range(cmd.min_baths, None)
+        .with_antiquity_range(cmd.min_age, cmd.max_age);
+    if let Some(source) = &cmd.source {
+        filters = filters.with_source(source);
+    }
+    if let Some(property_type) = &cmd.property_type {
+        filters = filters.with_property_type(property_type);
+    }
+    if cmd.parking {
+        filters = filters.with_parking(true);
+    }
+    if let Some(tag) = &cmd.tag {
+        filters = filters.with_tag(tag);
+    }
+    Ok(filters)
+}
+
+/// The page of listings `list` shows, sorted, limited and offset by SQLite
+/// rather than after loading every row.
+async fn list_page(cmd: &ListCommand, db: &Database) -> Result<Vec<Property>> {
+    list_filters(cmd)?
+        .order_by(&cmd.sort_by, cmd.sort_order == SortOrder::Desc)?
+        .with_limit(Some(cmd.limit))
+        .with_offset(Some(cmd.offset))
+        .execute(db.pool())
+        .await
+}
+
+fn properties_json(properties: &[Property]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(properties)?)
+}
+
+/// Title, price, size, rooms and district of each property, one per row.
+fn properties_table(properties: &[Property]) -> Table {
+    let mut table = Table::new();
+    table.set_titles(row!["Title", "Price (USD)", "Size (m²)", "Rooms", "District"]);
+    for property in properties {
+        table.add_row(row![
+            property.title,
+            r->format!("{:.0}", property.price_usd),
+            r->property.covered_size.map(|size| format!("{:.1}", size)).unwrap_or_default(),
+            r->property.rooms.map(|rooms| rooms.to_string()).unwrap_or_default(),
+            property.district
+        ]);
+    }
+    table
+}
+
+async fn search_properties(cmd: &SearchCommand, db: &Database) -> Result<()> {
+    let properties = db.search_properties(&cmd.query).await?;
     for property in properties.iter() {
-        if property.status == DbPropertyStatus::from(cmd.status) {
-            let price_history = db.get_price_history(property.id).await?;
-            displays.push(PropertyDisplay::new(property.clone(), price_history));
+        let price_history = db.get_price_history(property.id).await?;
+        println!("{}", PropertyDisplay::new(property.clone(), price_history));
+    }
+
+    info!("Found {} properties matching '{}'", properties.len(), cmd.query);
+    Ok(())
+}
+
+async fn tag_property(cmd: &TagCommand, db: &Database) -> Result<()> {
+    for tag in &cmd.tags {
+        if db.add_tag(cmd.id, tag).await? {
+            info!("Tagged property {} '{}'", cmd.id, tag.trim());
+        } else {
+            info!("Property {} is already tagged '{}'", cmd.id, tag.trim());
         }
     }
-    for display in &displays {
-        println!("{}", display.to_string());
+    Ok(())
+}
+
+async fn untag_property(cmd: &TagCommand, db: &Database) -> Result<()> {
+    for tag in &cmd.tags {
+        if db.remove_tag(cmd.id, tag).await? {
+            info!("Removed tag '{}' from property {}", tag.trim(), cmd.id);
+        } else {
+            warn!("Property {} isn't tagged '{}'", cmd.id, tag.trim());
+        }
     }
+    Ok(())
+}
+
+fn format_range(min: Option<f64>, max: Option<f64>) -> String {
+    match (min, max) {
+        (None, None) => "any".to_string(),
+        (Some(min), None) => format!("from {}", min),
+        (None, Some(max)) => format!("up to {}", max),
+        (Some(min), Some(max)) => format!("{}-{}", min, max),
+    }
+}
 
-    info!("Listed {} properties", displays.len());
+async fn watch(cmd: &WatchCommand, db: Arc<Database>) -> Result<()> {
+    match cmd.action {
+        WatchAction::Add => {
+            let mut search = SavedSearch {
+                id: 0,
+                district: cmd.district.clone(),
+                property_type: cmd.property_type.clone(),
+                min_price: cmd.min_price,
+                max_price: cmd.max_price,
+                min_size: cmd.min_size,
+                max_size: cmd.max_size,
+                created_at: DbTimestamp::now(),
+                last_checked_at: None,
+            };
+            db.add_saved_search(&mut search).await?;
+            info!("Saved search {}", search.id);
+        }
+        WatchAction::List => {
+            for search in db.list_saved_searches().await? {
+                println!(
+                    "{}: district {}, type {}, price {} USD, size {} m²",
+                    search.id,
+                    search.district.as_deref().unwrap_or("any"),
+                    search.property_type.map_or("any".to_string(), |t| t.to_string()),
+                    format_range(search.min_price, search.max_price),
+                    format_range(search.min_size, search.max_size),
+                );
+            }
+        }
+        WatchAction::Run => {
+            let searches = db.list_saved_searches().await?;
+            if searches.is_empty() {
+                info!("No saved searches, add one with `brea watch -a add`");
+                return Ok(());
+            }
+
+            // Only searches naming both a district and a type can be scraped;
+            // the others just match what other scrapes bring in.
+            let mut targets = Vec::new();
+            for search in &searches {
+                if let (Some(district), Some(property_type)) = (&search.district, &search.property_type) {
+                    if !targets.contains(&(district, property_type)) {
+                        targets.push((district, property_type));
+                    }
+                }
+            }
+
+            let scraper = ScraperFactory::create_scraper_with_config(cmd.scraper.into(), &scraper_config(&cmd.proxy))?;
+            for (district, property_type) in targets {
+                let query = ScrapeQuery::new(
+                    district.clone(),
+                    property_type.clone(),
+                    None,
+                    None,
+                    None,
+                    None,
+                    Some(db.clone()),
+                );
+                for (mut property, _images) in scraper.scrape_listing(query, cmd.max_pages).await?.listings {
+                    db.save_property_or_skip(&mut property).await?;
+                }
+            }
+
+            let checked_at = DbTimestamp::now();
+            let matches = db.matching_new_properties().await?;
+            for property in &matches {
+                let price_history = db.get_price_history(property.id).await?;
+                println!("{}", PropertyDisplay::new(property.clone(), price_history));
+            }
+            db.mark_saved_searches_checked(checked_at).await?;
+
+            info!("{} new properties match your saved searches", matches.len());
+        }
+    }
     Ok(())
 }
 
+async fn show_stats(db: &Database) -> Result<()> {
+    let stats = db.compute_stats().await?;
+
+    let mut summary = Table::new();
+    summary.add_row(row!["Properties", stats.total]);
+    for (status, count) in &stats.by_status {
+        summary.add_row(row![format!("  {}", status), count]);
+    }
+    for (property_type, count) in &stats.by_property_type {
+        summary.add_row(row![format!("  {}", property_type), count]);
+    }
+    summary.add_row(row!["Median price", format!("USD {:.0}", stats.median_price_usd)]);
+    summary.add_row(row!["Average price", format!("USD {:.0}", stats.average_price_usd)]);
+    summary.add_row(row!["Average size", format!("{:.1} m²", stats.average_covered_size)]);
+    summary.printstd();
+
+    if !stats.price_per_m2_by_district.is_empty() {
+        let mut districts = Table::new();
+        districts.set_titles(row!["District", "USD/m²"]);
+        for (district, price_per_m2) in &stats.price_per_m2_by_district {
+            districts.add_row(row![district, format!("{:.0}", price_per_m2)]);
+        }
+        districts.printstd();
+    }
+
+    Ok(())
+}
+
+async fn show_price_stats(db: &Database, district: &str, property_type: &PropertyType) -> Result<()> {
+    let stats = db.district_type_stats(district, property_type).await?;
+    if stats.count == 0 {
+        println!("No {} listings with a price in {}", property_type, district);
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_titles(row!["", "Min", "Median", "Mean", "Max"]);
+    for (label, distribution) in [("USD", stats.price_usd), ("USD/m²", stats.price_per_m2)] {
+        if let Some(d) = distribution {
+            table.add_row(row![
+                label,
+                format!("{:.0}", d.min),
+                format!("{:.0}", d.median),
+                format!("{:.0}", d.mean),
+                format!("{:.0}", d.max)
+            ]);
+        }
+    }
+    println!("{} {} listings in {}", stats.count, property_type, district);
+    table.printstd();
+    Ok(())
+}
+
+fn list_scrapers() {
+    let mut table = Table::new();
+    table.set_titles(row!["Scraper", "Property types"]);
+    for scraper_type in ScraperType::all() {
+        let scraper = ScraperFactory::create_scraper(*scraper_type);
+        let property_types: Vec<String> = scraper
+            .supported_property_types()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        table.add_row(row![scraper_type.name(), property_types.join(", ")]);
+    }
+    table.printstd();
+}
+
+async fn run_self_test(cmd: &DoctorCommand) -> Result<()> {
+    let scraper = ScraperFactory::create_scraper_with_config(cmd.scraper.into(), &scraper_config(&cmd.proxy))?;
+    let report = scraper.self_test().await?;
+
+    println!("Page: {}", report.url);
+    if let Some(error) = &report.network_error {
+        println!("Could not fetch the page, the markup was not checked: {}", error);
+        return Err(BreaError::Scraping("self test could not reach the site, try again later".to_string()));
+    }
+    for selector in &report.matched_selectors {
+        println!("  ok     {}", selector);
+    }
+    for selector in &report.empty_selectors {
+        println!("  EMPTY  {}", selector);
+    }
+    println!("{} listings, {} with a title and a price", report.listings, report.complete_listings);
+
+    if !report.passed() {
+        return Err(BreaError::Scraping("self test failed, the site's markup may have changed".to_string()));
+    }
+    println!("Self test passed");
+    Ok(())
+}
+
+async fn show_price_drops(cmd: &PriceDropsCommand, db: &Database) -> Result<()> {
+    let since = chrono::Utc::now() - chrono::Duration::days(cmd.days);
+    let drops = db.price_drops(since, cmd.min_pct).await?;
+    if drops.is_empty() {
+        println!("No price drops of {}% or more in the last {} days", cmd.min_pct, cmd.days);
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_titles(row!["ID", "Title", "District", "Price (USD)", "Drop"]);
+    for (property, drop_pct) in &drops {
+        table.add_row(row![
+            property.id,
+            property.title,
+            property.district,
+            format!("{:.0}", property.price_usd),
+            format!("{:.1}%", drop_pct)
+        ]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+async fn dedupe_properties(cmd: &DedupeCommand, db: &Database) -> Result<()> {
+    let groups = db.find_duplicate_candidates().await?;
+    if groups.is_empty() {
+        println!("No duplicate listings found");
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_titles(row!["Group", "ID", "Source", "External ID", "Title", "Address", "Price (USD)", "Created", "Action"]);
+    let mut removed = Vec::new();
+    for (i, group) in groups.iter().enumerate() {
+        for (j, property) in group.iter().enumerate() {
+            let action = if j == 0 { "keep" } else { "remove" };
+            table.add_row(row![
+                i + 1,
+                property.id,
+                property.source,
+                property.external_id,
+                property.title,
+                property.address,
+                format!("{:.0}", property.price_usd),
+                property.created_at.inner().format("%Y-%m-%d"),
+                action
+            ]);
+            if j > 0 {
+                removed.push(property.id);
+            }
+        }
+    }
+    table.printstd();
+
+    if cmd.apply {
+        db.mark_properties_status(&removed, PropertyStatus::Removed).await?;
+        info!("Marked {} duplicate listings as removed", removed.len());
+    } else {
+        info!("Found {} groups of duplicates, rerun with --apply to remove them", groups.len());
+    }
+    Ok(())
+}
+
+async fn reprocess_prices(cmd: &ReprocessPricesCommand, db: &Database) -> Result<()> {
+    let changes = if cmd.raw_only {
+        db.reprocess_prices_from_raw().await?
+    } else {
+        let scraper = ScraperFactory::create_scraper_with_config(cmd.scraper.into(), &scraper_config(&cmd.proxy))?;
+        let properties = db.get_active_properties().await?;
+        info!("Fetching {} listings again...", properties.len());
+
+        let mut changes = Vec::new();
+        for mut property in properties {
+            let detail = match url::Url::parse(&property.url) {
+                Ok(url) => scraper.scrape_detail(&url).await,
+                Err(e) => Err(e.into()),
+            };
+            match detail {
+                Ok((detail, _images)) if detail.raw_price > 0.0 => {
+                    property.raw_price = detail.raw_price;
+                    property.currency = detail.currency;
+                    if let Some(SaveOutcome::Updated { price_change: Some(change) }) =
+                        db.save_property_or_skip(&mut property).await?
+                    {
+                        changes.push(change);
+                    }
+                }
+                Ok(_) => warn!("No price found on {}", property.url),
+                Err(e) => warn!("Failed to fetch {}: {}", property.url, e),
+            }
+        }
+        changes
+    };
+
+    for change in &changes {
+        println!("Property {}: {:.0} -> {:.0} USD", change.property_id, change.old_price, change.new_price);
+    }
+    info!("Corrected {} prices", changes.len());
+    Ok(())
+}
+
+async fn geocode_properties(cmd: &GeocodeCommand, db: &Database) -> Result<()> {
+    let geocoder = NominatimGeocoder::new().with_base_url(&cmd.nominatim_url);
+    let properties = db.get_properties_without_coordinates(cmd.limit).await?;
+    info!("Geocoding {} properties...", properties.len());
+
+    let mut located = 0;
+    for property in &properties {
+        let address = format!("{}, {}, Argentina", property.address, property.district);
+        match geocoder.geocode(&address).await {
+            Ok(Some((latitude, longitude))) => {
+                db.set_coordinates(property.id, latitude, longitude).await?;
+                located += 1;
+            }
+            Ok(None) => warn!("Could not locate '{}'", address),
+            Err(e) => warn!("Geocoding '{}' failed: {}", address, e),
+        }
+    }
+
+    info!("Geocoded {} of {} properties", located, properties.len());
+    Ok(())
+}
+
+/// Parses `--since` and the like, which have to carry a timezone so that
+/// results don't depend on where the command runs.
+fn parse_since(since: &str) -> Result<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc3339(since)
+        .map(|since| since.with_timezone(&chrono::Utc))
+        .map_err(|e| {
+            BreaError::InvalidArgument(format!(
+                "expected an RFC 3339 timestamp such as 2024-03-20T00:00:00Z, got {:?}: {}",
+                since, e
+            ))
+        })
+}
+
 async fn export_properties(cmd: &ExportCommand, db: &Database) -> Result<()> {
-    let properties = db.get_properties().await?;
+    let since = cmd.since.as_deref().map(parse_since).transpose()?;
+    let timestamps = if cmd.epoch { TimestampFormat::Unix } else { TimestampFormat::Rfc3339 };
+
+    if let ExportFormat::Jsonl = cmd.format {
+        let file = std::fs::File::create(&cmd.output)?;
+        let count = db.export_jsonl(std::io::BufWriter::new(file), since, timestamps, cmd.with_history).await?;
+        info!("Exported {} properties to {}", count, cmd.output.display());
+        return Ok(());
+    }
+
+    if cmd.with_history {
+        return Err(BreaError::InvalidArgument("--with-history needs --format jsonl".to_string()));
+    }
+    let properties = match since {
+        Some(since) => db.list_properties_updated_since(since).await?,
+        None => db.get_properties().await?,
+    };
     let mut writer = Writer::from_path(&cmd.output)?;
-    let properties_len = properties.len();
+    let mut exported = 0;
 
     for property in properties {
-        if let Some(_) = property.property_type.as_ref().and_then(|t| PropertyType::from_str(t).ok()) {
-            writer.serialize(&property)?;
+        if property.property_type.is_some() {
+            writer.serialize(PropertyExport::new(&property, timestamps))?;
+            exported += 1;
         }
     }
 
     writer.flush()?;
-    info!("Exported {} properties to {}", properties_len, cmd.output.display());
+    info!("Exported {} properties to {}", exported, cmd.output.display());
     Ok(())
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    fn listed_property(title: &str, price_usd: f64, rooms: Option<i32>) -> Property {
+        let now = DbTimestamp::now();
+        Property {
+            id: 1,
+            external_id: "test-1".to_string(),
+            source: "test".to_string(),
+            operation: Operation::Sale,
+            property_type: Some(PropertyType::Apartment),
+            district: "palermo".to_string(),
+            title: title.to_string(),
+            description: None,
+            price_usd,
+            raw_price: price_usd,
+            currency: brea_core::Currency::Usd,
+            address: "Test Address".to_string(),
+            latitude: None,
+            longitude: None,
+            covered_size: Some(50.0),
+            rooms,
+            antiquity: None,
+            under_construction: None,
+            bathrooms: None,
+            has_parking: None,
+            expenses_ars: None,
+            notes: None,
+            url: "https://example.com/test-1".to_string(),
+            status: DbPropertyStatus::new(STATUS_ACTIVE),
+            created_at: now.clone(),
+            updated_at: now.clone(),
+            last_seen_at: now,
+        }
+    }
+
+    #[test]
+    fn test_list_json_round_trips() {
+        let properties = vec![
+            listed_property("Dos ambientes", 100000.0, Some(2)),
+            listed_property("Monoambiente", 65000.0, None),
+        ];
+
+        let json = properties_json(&properties).unwrap();
+        let parsed: Vec<Property> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].title, "Dos ambientes");
+        assert_eq!(parsed[1].rooms, None);
+        assert_eq!(properties_json(&[]).unwrap(), "[]");
+    }
+
+    #[test]
+    fn test_price_changes_table_omits_unchanged() {
+        let change = |old_price: f64, new_price: f64| SaveOutcome::Updated {
+            price_change: Some(brea_core::db::PriceChange {
+                property_id: 1,
+                old_price,
+                new_price,
+                observed_at: DbTimestamp::now(),
+            }),
+        };
+        let saved = vec![
+            (listed_property("Bajó de precio", 95000.0, Some(2)), change(100000.0, 95000.0)),
+            (listed_property("Sin cambios", 80000.0, Some(2)), SaveOutcome::Unchanged),
+            (listed_property("Nuevo título", 70000.0, Some(1)), SaveOutcome::Updated { price_change: None }),
+            (listed_property("Recién publicado", 60000.0, Some(1)), SaveOutcome::Inserted),
+            (listed_property("Subió", 132000.0, Some(3)), change(120000.0, 132000.0)),
+        ];
+
+        let table = price_changes_table(&saved).to_string();
+        assert!(table.contains("Bajó de precio"));
+        assert!(table.contains("-5.0%"));
+        assert!(table.contains("Subió"));
+        assert!(table.contains("+10.0%"));
+        for omitted in ["Sin cambios", "Nuevo título", "Recién publicado"] {
+            assert!(!table.contains(omitted), "{}", omitted);
+        }
+    }
+
+    #[test]
+    fn test_list_table_aligns_columns() {
+        let properties = vec![
+            listed_property("Dos ambientes con balcón", 100000.0, Some(2)),
+            listed_property("PH", 65000.0, None),
+        ];
+
+        let table = properties_table(&properties).to_string();
+        let lines: Vec<&str> = table.lines().collect();
+        // Borders and separators around the titles and each row
+        assert_eq!(lines.len(), 7);
+        assert!(lines[1].starts_with("| Title                    | Price (USD) |"));
+        assert!(lines[3].starts_with("| Dos ambientes con balcón |      100000 |"));
+        assert!(lines[5].starts_with("| PH                       |       65000 |"));
+        // Every line has the same width, whatever its values
+        assert!(lines.iter().all(|line| line.chars().count() == lines[0].chars().count()));
+    }
+
+    #[test]
+    fn test_cli_scraper_types_match_scrapers() {
+        let cli_names: Vec<String> = CliScraperType::value_variants()
+            .iter()
+            .map(|variant| variant.to_possible_value().unwrap().get_name().to_string())
+            .collect();
+        let names: Vec<&str> = ScraperType::all().iter().map(ScraperType::name).collect();
+        assert_eq!(cli_names, names);
+
+        for variant in CliScraperType::value_variants() {
+            let name = variant.to_possible_value().unwrap().get_name().to_string();
+            assert_eq!(ScraperType::from(*variant).name(), name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_pages_in_sql() {
+        let db = Database::new_in_memory_shared().await.unwrap();
+        apply_migrations(db.pool()).await.unwrap();
+        for i in 1..=15 {
+            let mut property = listed_property(&format!("Listing {}", i), 1000.0 * i as f64, Some(2));
+            property.external_id = format!("test-{}", i);
+            property.url = format!("https://example.com/test-{}", i);
+            db.save_property(&mut property).await.unwrap();
+        }
+
+        let page = |args: &[&str]| {
+            let cli = Cli::try_parse_from(["brea", "list"].iter().chain(args)).unwrap();
+            match cli.command {
+                Commands::List(cmd) => cmd,
+                _ => unreachable!(),
+            }
+        };
+        let titles = |properties: Vec<Property>| properties.into_iter().map(|property| property.title).collect::<Vec<_>>();
+
+        let cmd = page(&["--limit", "5", "--offset", "5", "-s", "price_usd", "-r", "asc"]);
+        let listed = titles(list_page(&cmd, &db).await.unwrap());
+        assert_eq!(listed, ["Listing 6", "Listing 7", "Listing 8", "Listing 9", "Listing 10"]);
+
+        let cmd = page(&["--limit", "5", "--offset", "5", "-P", "8000"]);
+        let listed = titles(list_page(&cmd, &db).await.unwrap());
+        assert_eq!(listed, ["Listing 3", "Listing 2", "Listing 1"]);
+        assert_eq!(db.count_properties(list_filters(&cmd).unwrap()).await.unwrap(), 8);
+
+        assert!(list_page(&page(&["-s", "password"]), &db).await.is_err());
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let since = parse_since("2024-03-20T00:00:00-03:00").unwrap();
+        assert_eq!(since.to_rfc3339(), "2024-03-20T03:00:00+00:00");
+
+        for bad in ["2024-03-20", "yesterday", "2024-03-20T00:00:00"] {
+            let error = parse_since(bad).unwrap_err();
+            assert!(matches!(error, BreaError::InvalidArgument(_)), "{}", bad);
+            assert!(error.to_string().contains("RFC 3339"));
+        }
+    }
+
+    #[test]
+    fn test_log_level_flags() {
+        let level = |args: &[&str]| Cli::try_parse_from(["brea"].iter().chain(args)).unwrap().log_level();
+        assert_eq!(level(&["scrapers"]), Level::INFO);
+        assert_eq!(level(&["-q", "scrapers"]), Level::WARN);
+        assert_eq!(level(&["scrapers", "--quiet"]), Level::WARN);
+        assert_eq!(level(&["-v", "scrapers"]), Level::DEBUG);
+        assert_eq!(level(&["-vv", "scrapers"]), Level::TRACE);
+        assert_eq!(level(&["list", "-v", "-v", "-v"]), Level::TRACE);
+        assert!(Cli::try_parse_from(["brea", "-q", "-v", "scrapers"]).is_err());
+    }
+
+    #[test]
+    fn test_database_path_from_env() {
+        // Writing BREA_DATABASE would race with the other tests parsing
+        // arguments, so `stats -d` reads PATH, which is always set, or a
+        // variable nobody sets instead
+        let database = |env: &'static str, args: &[&str]| {
+            let command = Cli::command().mut_subcommand("stats", |stats| stats.mut_arg("database", |arg| arg.env(env)));
+            let matches = command.try_get_matches_from(args).unwrap();
+            match Cli::from_arg_matches(&matches).unwrap().command {
+                Commands::Stats(cmd) => cmd.database,
+                _ => unreachable!(),
+            }
+        };
+        let path = PathBuf::from(std::env::var_os("PATH").unwrap());
+        assert_eq!(database("PATH", &["brea", "stats"]), path);
+        assert_eq!(database("PATH", &["brea", "stats", "-d", "flag.db"]), PathBuf::from("flag.db"));
+        assert_eq!(database("BREA_TEST_UNSET_DATABASE", &["brea", "stats"]), PathBuf::from("brea.db"));
+    }
+}
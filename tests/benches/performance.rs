@@ -1,6 +1,6 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
 use tokio::runtime::Runtime;
-use brea_core::{Database, Property, PropertyType, db::types::{DbTimestamp, DbPropertyStatus, STATUS_ACTIVE}, db::migrations::apply_migrations};
+use brea_core::{Currency, Database, Operation, Property, PropertyType, db::DatabaseOptions, db::types::{DbTimestamp, DbPropertyStatus, STATUS_ACTIVE}, db::migrations::apply_migrations};
 use brea_scrapers::{argenprop::ArgenPropScraper, ScrapeQuery, Scraper};
 use fake::{Fake, Faker};
 use rand::Rng;
@@ -18,22 +18,34 @@ async fn setup_test_db() -> Database {
 fn generate_fake_properties(count: usize) -> Vec<Property> {
     (0..count)
         .map(|_| {
+            let price = rand::thread_rng().gen_range(50000.0..1000000.0);
             let property = Property {
                 id: 0,
                 external_id: Faker.fake(),
                 source: "benchmark".to_string(),
-                property_type: Some(PropertyType::Apartment.to_string()),
+                operation: Operation::Sale,
+                property_type: Some(PropertyType::Apartment),
                 district: Faker.fake(),
                 title: Faker.fake(),
                 description: Some(Faker.fake()),
-                price_usd: rand::thread_rng().gen_range(50000.0..1000000.0),
+                price_usd: price,
+                raw_price: price,
+                currency: Currency::Usd,
                 address: Faker.fake(),
+                latitude: None,
+                longitude: None,
                 covered_size: Some(rand::thread_rng().gen_range(30.0..500.0)),
                 rooms: Some(rand::thread_rng().gen_range(1..6)),
                 antiquity: Some(rand::thread_rng().gen_range(0..50)),
+                under_construction: None,
+                bathrooms: None,
+                has_parking: None,
+                expenses_ars: None,
+                notes: None,
                 url: Url::parse("https://example.com").unwrap().to_string(),
                 created_at: DbTimestamp::now(),
                 updated_at: DbTimestamp::now(),
+                last_seen_at: DbTimestamp::now(),
                 status: DbPropertyStatus::new(STATUS_ACTIVE),
             };
             property
@@ -54,9 +66,9 @@ fn bench_database_operations(c: &mut Criterion) {
             let properties = generate_fake_properties(size);
             b.to_async(&rt).iter(|| async {
                 let db = setup_test_db().await;
-                for mut property in properties.clone() {
-                    black_box(db.save_property(&mut property).await.unwrap());
-                }
+                let mut properties = properties.clone();
+                db.save_properties_batch(&mut properties).await.unwrap();
+                black_box(&properties);
             });
         });
     }
@@ -66,10 +78,8 @@ fn bench_database_operations(c: &mut Criterion) {
         group.bench_with_input(BenchmarkId::new("query", size), size, |b, &size| {
             b.to_async(&rt).iter(|| async {
                 let db = setup_test_db().await;
-                let properties = generate_fake_properties(size);
-                for mut property in properties.clone() {
-                    db.save_property(&mut property).await.unwrap();
-                }
+                let mut properties = generate_fake_properties(size);
+                db.save_properties_batch(&mut properties).await.unwrap();
                 black_box(db.get_properties().await.unwrap());
             });
         });
@@ -102,6 +112,23 @@ fn bench_scraper_operations(c: &mut Criterion) {
         });
     });
 
+    // Sequential vs concurrent multi-page scraping
+    group.bench_function("scrape_listing", |b| {
+        b.to_async(&rt).iter(|| async {
+            let scraper = ArgenPropScraper::new();
+            let query = ScrapeQuery::new("palermo".to_string(), PropertyType::Apartment, None, None, None, None, None);
+            black_box(scraper.scrape_listing(query, 5).await.unwrap().listings);
+        });
+    });
+
+    group.bench_function("scrape_listing_concurrent", |b| {
+        b.to_async(&rt).iter(|| async {
+            let scraper = ArgenPropScraper::new();
+            let query = ScrapeQuery::new("palermo".to_string(), PropertyType::Apartment, None, None, None, None, None);
+            black_box(scraper.scrape_listing_concurrent(query, 5, 4).await.unwrap());
+        });
+    });
+
     group.finish();
 }
 
@@ -116,7 +143,12 @@ fn bench_concurrent_operations(c: &mut Criterion) {
     for size in [10, 100].iter() {
         group.bench_with_input(BenchmarkId::new("concurrent_queries", size), size, |b, &size| {
             b.to_async(&rt).iter(|| async {
-                let db = setup_test_db().await;
+                // A file database, so the queries really run on separate connections
+                let dir = tempfile::tempdir().unwrap();
+                let db = Database::with_options(dir.path().join("bench.db"), DatabaseOptions::default())
+                    .await
+                    .unwrap();
+                apply_migrations(db.pool()).await.unwrap();
                 let properties = generate_fake_properties(size);
                 for mut property in properties.clone() {
                     db.save_property(&mut property).await.unwrap();
@@ -0,0 +1,123 @@
+use brea_core::api::{serve, PricePoint};
+use brea_core::db::migrations::apply_migrations;
+use brea_core::db::types::DbTimestamp;
+use brea_core::db::{DbPropertyStatus, STATUS_ACTIVE};
+use brea_core::{Currency, Database, Operation, Property, PropertyType};
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+
+fn test_property(external_id: &str, property_type: PropertyType, price_usd: f64) -> Property {
+    Property {
+        id: 0,
+        external_id: external_id.to_string(),
+        source: "test".to_string(),
+        operation: Operation::Sale,
+        property_type: Some(property_type),
+        district: "palermo".to_string(),
+        title: format!("Test Property {}", external_id),
+        description: None,
+        price_usd,
+        raw_price: price_usd,
+        currency: Currency::Usd,
+        address: "Test Address".to_string(),
+        latitude: None,
+        longitude: None,
+        covered_size: Some(50.0),
+        rooms: Some(2),
+        antiquity: Some(5),
+        under_construction: None,
+        bathrooms: None,
+        has_parking: None,
+        expenses_ars: None,
+        notes: None,
+        url: format!("https://example.com/{}", external_id),
+        status: DbPropertyStatus::new(STATUS_ACTIVE),
+        created_at: DbTimestamp::now(),
+        updated_at: DbTimestamp::now(),
+        last_seen_at: DbTimestamp::now(),
+    }
+}
+
+/// Starts the API on an ephemeral port over a file database, since every
+/// connection to ":memory:" would see its own empty database. Returns the
+/// base URL and the saved properties.
+async fn start_server(dir: &TempDir) -> (String, Vec<Property>) {
+    let db = Database::new(dir.path().join("brea.db")).await.unwrap();
+    apply_migrations(db.pool()).await.unwrap();
+    let mut properties = vec![
+        test_property("cheap", PropertyType::Apartment, 100000.0),
+        test_property("pricey", PropertyType::Apartment, 200000.0),
+        test_property("house", PropertyType::House, 150000.0),
+    ];
+    for property in &mut properties {
+        db.save_property(property).await.unwrap();
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(serve(db, listener));
+    (url, properties)
+}
+
+async fn get_ids(url: &str) -> Vec<String> {
+    let properties: Vec<Property> = reqwest::get(url).await.unwrap().error_for_status().unwrap().json().await.unwrap();
+    properties.into_iter().map(|property| property.external_id).collect()
+}
+
+#[tokio::test]
+async fn test_api_lists_properties() {
+    let dir = tempfile::tempdir().unwrap();
+    let (url, _) = start_server(&dir).await;
+
+    assert_eq!(get_ids(&format!("{}/properties", url)).await, ["cheap", "pricey", "house"]);
+    assert_eq!(
+        get_ids(&format!("{}/properties?property_type=apartment&max_price=150000", url)).await,
+        ["cheap"]
+    );
+    assert_eq!(
+        get_ids(&format!("{}/properties?sort_by=price_usd&desc=true&limit=2", url)).await,
+        ["pricey", "house"]
+    );
+    assert_eq!(get_ids(&format!("{}/properties?offset=1&limit=1", url)).await, ["pricey"]);
+    assert!(get_ids(&format!("{}/properties?status=sold", url)).await.is_empty());
+
+    for bad in ["property_type=castle", "sort_by=password", "status=gone"] {
+        let response = reqwest::get(format!("{}/properties?{}", url, bad)).await.unwrap();
+        assert_eq!(response.status(), 400, "{}", bad);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert!(body["error"].is_string());
+    }
+}
+
+#[tokio::test]
+async fn test_api_property_and_price_history() {
+    let dir = tempfile::tempdir().unwrap();
+    let (url, properties) = start_server(&dir).await;
+    let id = properties[1].id;
+
+    let property: Property = reqwest::get(format!("{}/properties/{}", url, id)).await.unwrap().json().await.unwrap();
+    assert_eq!(property.external_id, "pricey");
+
+    let history: Vec<PricePoint> = reqwest::get(format!("{}/properties/{}/price-history", url, id))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].price_usd, 200000.0);
+
+    let with_history: serde_json::Value = reqwest::get(format!("{}/properties/{}?with_history=true", url, id))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(with_history["external_id"], "pricey");
+    assert_eq!(with_history["price_history"][0]["price_usd"], 200000.0);
+
+    for path in ["properties/999", "properties/999?with_history=true", "properties/999/price-history"] {
+        let response = reqwest::get(format!("{}/{}", url, path)).await.unwrap();
+        assert_eq!(response.status(), 404, "{}", path);
+    }
+}
@@ -1,60 +1,67 @@
-use brea_core::{Database, Property, PropertyType, PropertyStatus};
-use brea_scrapers::{ArgenPropScraper, Scraper};
-use tempfile::tempdir;
-use std::sync::Arc;
+use brea_core::db::migrations::apply_migrations;
+use brea_core::db::types::DbTimestamp;
+use brea_core::db::{DbPropertyStatus, STATUS_ACTIVE, STATUS_SOLD};
+use brea_core::{BreaError, Currency, Database, Operation, Property, PropertyType};
 
-#[tokio::test]
-async fn test_list_with_filters() {
-    let temp_dir = tempdir().unwrap();
-    let db_path = temp_dir.path().join("test.db");
-    let db = Database::new(&db_path).await.unwrap();
+async fn test_db() -> Database {
+    let db = Database::new(":memory:").await.unwrap();
+    apply_migrations(db.pool()).await.unwrap();
+    db
+}
 
-    // Create and save test properties with different attributes
-    let properties = vec![
-        Property {
-            id: None,
-            external_id: "test-1".to_string(),
-            source: "test".to_string(),
-            property_type: Some(PropertyType::Apartment),
-            district: "test".to_string(),
-            title: "Test Property 1".to_string(),
-            description: None,
-            price_usd: 100000.0,
-            address: "Test Address 1".to_string(),
-            covered_size: Some(100.0),
-            rooms: Some(2),
-            antiquity: Some(5),
-            url: "https://example.com/1".parse().unwrap(),
-            status: PropertyStatus::Active,
-            created_at: chrono::Utc::now(),
-            updated_at: chrono::Utc::now(),
-        },
-        Property {
-            id: None,
-            external_id: "test-2".to_string(),
-            source: "test".to_string(),
-            property_type: Some(PropertyType::House),
-            district: "test".to_string(),
-            title: "Test Property 2".to_string(),
-            description: None,
-            price_usd: 200000.0,
-            address: "Test Address 2".to_string(),
-            covered_size: Some(200.0),
-            rooms: Some(3),
-            antiquity: Some(10),
-            url: "https://example.com/2".parse().unwrap(),
-            status: PropertyStatus::Sold,
-            created_at: chrono::Utc::now(),
-            updated_at: chrono::Utc::now(),
-        },
-    ];
+fn test_property(external_id: &str, property_type: PropertyType, price_usd: f64, covered_size: f64) -> Property {
+    Property {
+        id: 0,
+        external_id: external_id.to_string(),
+        source: "test".to_string(),
+        operation: Operation::Sale,
+        property_type: Some(property_type),
+        district: "test".to_string(),
+        title: format!("Test Property {}", external_id),
+        description: None,
+        price_usd,
+        raw_price: price_usd,
+        currency: Currency::Usd,
+        address: "Test Address".to_string(),
+        latitude: None,
+        longitude: None,
+        covered_size: Some(covered_size),
+        rooms: Some(2),
+        antiquity: Some(5),
+        under_construction: None,
+        bathrooms: None,
+        has_parking: None,
+        expenses_ars: None,
+        notes: None,
+        url: format!("https://example.com/{}", external_id),
+        status: DbPropertyStatus::new(STATUS_ACTIVE),
+        created_at: DbTimestamp::now(),
+        updated_at: DbTimestamp::now(),
+        last_seen_at: DbTimestamp::now(),
+    }
+}
 
+async fn save_all(db: &Database, properties: Vec<Property>) {
     for mut property in properties {
         db.save_property(&mut property).await.unwrap();
     }
+}
+
+#[tokio::test]
+async fn test_list_with_filters() {
+    let db = test_db().await;
+
+    let mut sold = test_property("test-2", PropertyType::House, 200000.0, 200.0);
+    sold.status = DbPropertyStatus::new(STATUS_SOLD);
+    save_all(&db, vec![
+        test_property("test-1", PropertyType::Apartment, 100000.0, 100.0),
+        sold,
+    ]).await;
 
     // Test price filtering
     let filtered = db.list_properties(
+        None,
+        None,
         None,
         Some(150000.0),
         Some(250000.0),
@@ -70,6 +77,8 @@ async fn test_list_with_filters() {
 
     // Test size filtering
     let filtered = db.list_properties(
+        None,
+        None,
         None,
         None,
         None,
@@ -85,6 +94,7 @@ async fn test_list_with_filters() {
 
     // Test source filtering
     let filtered = db.list_properties(
+        None,
         Some("test"),
         None,
         None,
@@ -93,10 +103,26 @@ async fn test_list_with_filters() {
         None,
         None,
         None,
+        None,
         false,
     ).await.unwrap();
     assert_eq!(filtered.len(), 2);
 
+    let filtered = db.list_properties(
+        None,
+        Some("other"),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        false,
+    ).await.unwrap();
+    assert!(filtered.is_empty());
+
     // Test sorting
     let sorted = db.list_properties(
         None,
@@ -106,6 +132,8 @@ async fn test_list_with_filters() {
         None,
         None,
         None,
+        None,
+        None,
         Some("price_usd"),
         true,
     ).await.unwrap();
@@ -119,81 +147,31 @@ async fn test_list_with_filters() {
         None,
         None,
         None,
+        None,
+        None,
         Some(1),
         Some(1),
-        None,
+        Some("price_usd"),
         false,
     ).await.unwrap();
     assert_eq!(paginated.len(), 1);
+    assert_eq!(paginated[0].price_usd, 200000.0);
 }
 
 #[tokio::test]
 async fn test_list_with_status() {
-    let temp_dir = tempdir().unwrap();
-    let db_path = temp_dir.path().join("test.db");
-    let db = Database::new(&db_path).await.unwrap();
+    let db = test_db().await;
 
-    // Create and save test properties with different statuses
-    let properties = vec![
-        Property {
-            id: None,
-            external_id: "test-1".to_string(),
-            source: "test".to_string(),
-            property_type: Some(PropertyType::Apartment),
-            district: "test".to_string(),
-            title: "Test Property 1".to_string(),
-            description: None,
-            price_usd: 100000.0,
-            address: "Test Address 1".to_string(),
-            covered_size: Some(100.0),
-            rooms: Some(2),
-            antiquity: Some(5),
-            url: "https://example.com/1".parse().unwrap(),
-            status: PropertyStatus::Active,
-            created_at: chrono::Utc::now(),
-            updated_at: chrono::Utc::now(),
-        },
-        Property {
-            id: None,
-            external_id: "test-2".to_string(),
-            source: "test".to_string(),
-            property_type: Some(PropertyType::House),
-            district: "test".to_string(),
-            title: "Test Property 2".to_string(),
-            description: None,
-            price_usd: 200000.0,
-            address: "Test Address 2".to_string(),
-            covered_size: Some(200.0),
-            rooms: Some(3),
-            antiquity: Some(10),
-            url: "https://example.com/2".parse().unwrap(),
-            status: PropertyStatus::Sold,
-            created_at: chrono::Utc::now(),
-            updated_at: chrono::Utc::now(),
-        },
-    ];
+    let mut sold = test_property("test-2", PropertyType::House, 200000.0, 200.0);
+    sold.status = DbPropertyStatus::new(STATUS_SOLD);
+    save_all(&db, vec![
+        test_property("test-1", PropertyType::Apartment, 100000.0, 100.0),
+        sold,
+    ]).await;
 
-    for mut property in properties {
-        db.save_property(&mut property).await.unwrap();
-    }
-
-    // Test listing active properties
-    let active = db.list_properties(
-        None,
-        None,
-        None,
-        None,
-        None,
-        None,
+    let all = db.list_properties(
         None,
         None,
-        false,
-    ).await.unwrap();
-    let active_count = active.iter().filter(|p| p.status == PropertyStatus::Active).count();
-    assert_eq!(active_count, 1);
-
-    // Test listing sold properties
-    let sold = db.list_properties(
         None,
         None,
         None,
@@ -204,59 +182,20 @@ async fn test_list_with_status() {
         None,
         false,
     ).await.unwrap();
-    let sold_count = sold.iter().filter(|p| p.status == PropertyStatus::Sold).count();
+    let active_count = all.iter().filter(|p| p.status.as_str() == STATUS_ACTIVE).count();
+    assert_eq!(active_count, 1);
+    let sold_count = all.iter().filter(|p| p.status.as_str() == STATUS_SOLD).count();
     assert_eq!(sold_count, 1);
 }
 
 #[tokio::test]
 async fn test_list_with_property_type() {
-    let temp_dir = tempdir().unwrap();
-    let db_path = temp_dir.path().join("test.db");
-    let db = Database::new(&db_path).await.unwrap();
+    let db = test_db().await;
 
-    // Create and save test properties with different property types
-    let properties = vec![
-        Property {
-            id: None,
-            external_id: "test-1".to_string(),
-            source: "test".to_string(),
-            property_type: Some(PropertyType::Apartment),
-            district: "test".to_string(),
-            title: "Test Property 1".to_string(),
-            description: None,
-            price_usd: 100000.0,
-            address: "Test Address 1".to_string(),
-            covered_size: Some(100.0),
-            rooms: Some(2),
-            antiquity: Some(5),
-            url: "https://example.com/1".parse().unwrap(),
-            status: PropertyStatus::Active,
-            created_at: chrono::Utc::now(),
-            updated_at: chrono::Utc::now(),
-        },
-        Property {
-            id: None,
-            external_id: "test-2".to_string(),
-            source: "test".to_string(),
-            property_type: Some(PropertyType::House),
-            district: "test".to_string(),
-            title: "Test Property 2".to_string(),
-            description: None,
-            price_usd: 200000.0,
-            address: "Test Address 2".to_string(),
-            covered_size: Some(200.0),
-            rooms: Some(3),
-            antiquity: Some(10),
-            url: "https://example.com/2".parse().unwrap(),
-            status: PropertyStatus::Active,
-            created_at: chrono::Utc::now(),
-            updated_at: chrono::Utc::now(),
-        },
-    ];
-
-    for mut property in properties {
-        db.save_property(&mut property).await.unwrap();
-    }
+    save_all(&db, vec![
+        test_property("test-1", PropertyType::Apartment, 100000.0, 100.0),
+        test_property("test-2", PropertyType::House, 200000.0, 200.0),
+    ]).await;
 
     // Test listing apartments
     let apartments = db.list_properties(
@@ -268,6 +207,8 @@ async fn test_list_with_property_type() {
         None,
         None,
         None,
+        None,
+        None,
         false,
     ).await.unwrap();
     assert_eq!(apartments.len(), 1);
@@ -283,6 +224,8 @@ async fn test_list_with_property_type() {
         None,
         None,
         None,
+        None,
+        None,
         false,
     ).await.unwrap();
     assert_eq!(houses.len(), 1);
@@ -291,58 +234,20 @@ async fn test_list_with_property_type() {
 
 #[tokio::test]
 async fn test_list_with_district() {
-    let temp_dir = tempdir().unwrap();
-    let db_path = temp_dir.path().join("test.db");
-    let db = Database::new(&db_path).await.unwrap();
+    let db = test_db().await;
 
-    // Create and save test properties with different districts
-    let properties = vec![
-        Property {
-            id: None,
-            external_id: "test-1".to_string(),
-            source: "test".to_string(),
-            property_type: Some(PropertyType::Apartment),
-            district: "Palermo".to_string(),
-            title: "Test Property 1".to_string(),
-            description: None,
-            price_usd: 100000.0,
-            address: "Test Address 1".to_string(),
-            covered_size: Some(100.0),
-            rooms: Some(2),
-            antiquity: Some(5),
-            url: "https://example.com/1".parse().unwrap(),
-            status: PropertyStatus::Active,
-            created_at: chrono::Utc::now(),
-            updated_at: chrono::Utc::now(),
-        },
-        Property {
-            id: None,
-            external_id: "test-2".to_string(),
-            source: "test".to_string(),
-            property_type: Some(PropertyType::Apartment),
-            district: "Recoleta".to_string(),
-            title: "Test Property 2".to_string(),
-            description: None,
-            price_usd: 200000.0,
-            address: "Test Address 2".to_string(),
-            covered_size: Some(200.0),
-            rooms: Some(3),
-            antiquity: Some(10),
-            url: "https://example.com/2".parse().unwrap(),
-            status: PropertyStatus::Active,
-            created_at: chrono::Utc::now(),
-            updated_at: chrono::Utc::now(),
-        },
-    ];
-
-    for mut property in properties {
-        db.save_property(&mut property).await.unwrap();
-    }
+    let mut palermo = test_property("test-1", PropertyType::Apartment, 100000.0, 100.0);
+    palermo.district = "Palermo".to_string();
+    let mut recoleta = test_property("test-2", PropertyType::Apartment, 200000.0, 200.0);
+    recoleta.district = "Recoleta".to_string();
+    save_all(&db, vec![palermo, recoleta]).await;
 
     // Test listing properties in Palermo
     let palermo = db.list_properties(
         None,
-        Some("Palermo".to_string()),
+        None,
+        Some("Palermo"),
+        None,
         None,
         None,
         None,
@@ -352,12 +257,14 @@ async fn test_list_with_district() {
         false,
     ).await.unwrap();
     assert_eq!(palermo.len(), 1);
-    assert_eq!(palermo[0].district, "Palermo");
+    assert_eq!(palermo[0].district, "palermo");
 
     // Test listing properties in Recoleta
     let recoleta = db.list_properties(
         None,
-        Some("Recoleta".to_string()),
+        None,
+        Some("Recoleta"),
+        None,
         None,
         None,
         None,
@@ -367,49 +274,17 @@ async fn test_list_with_district() {
         false,
     ).await.unwrap();
     assert_eq!(recoleta.len(), 1);
-    assert_eq!(recoleta[0].district, "Recoleta");
+    assert_eq!(recoleta[0].district, "recoleta");
 }
 
 #[tokio::test]
-async fn test_database_schema_errors() {
-    let temp_dir = tempdir().unwrap();
-    let db_path = temp_dir.path().join("test.db");
-    let db = Database::new(&db_path).await.unwrap();
-
-    // Create a property with invalid status
-    let mut property = Property {
-        id: None,
-        external_id: "test-1".to_string(),
-        source: "test".to_string(),
-        property_type: Some(PropertyType::Apartment),
-        district: "test".to_string(),
-        title: "Test Property 1".to_string(),
-        description: None,
-        price_usd: 100000.0,
-        address: "Test Address 1".to_string(),
-        covered_size: Some(100.0),
-        rooms: Some(2),
-        antiquity: Some(5),
-        url: "https://example.com/1".parse().unwrap(),
-        status: PropertyStatus::Active,
-        created_at: chrono::Utc::now(),
-        updated_at: chrono::Utc::now(),
-    };
-
-    // Save the property
-    db.save_property(&mut property).await.unwrap();
+async fn test_list_rejects_unknown_sort_field() {
+    let db = test_db().await;
 
-    // Manually update the status to an invalid value
-    sqlx::query(
-        "UPDATE properties SET status = ? WHERE id = ?",
-    )
-    .bind(chrono::Utc::now().to_string())
-    .bind(property.id.unwrap())
-    .execute(&db.pool)
-    .await
-    .unwrap();
+    save_all(&db, vec![
+        test_property("test-1", PropertyType::Apartment, 100000.0, 100.0),
+    ]).await;
 
-    // Try to list properties and verify we get a proper error
     let result = db.list_properties(
         None,
         None,
@@ -419,10 +294,12 @@ async fn test_database_schema_errors() {
         None,
         None,
         None,
+        None,
+        Some("price_usd; DROP TABLE properties"),
         false,
     ).await;
+    assert!(matches!(result, Err(BreaError::InvalidArgument(_))));
 
-    assert!(result.is_err());
-    let error = result.unwrap_err();
-    assert!(error.to_string().contains("Unknown property status"));
-} 
\ No newline at end of file
+    // The table is untouched
+    assert_eq!(db.get_properties().await.unwrap().len(), 1);
+}
@@ -1,3 +1,6 @@
+mod api;
+mod list;
+
 use brea_core::{Database, PropertyType};
 use brea_core::db::migrations::apply_migrations;
 use brea_core::db::types::DbTimestamp;
@@ -27,7 +30,8 @@ async fn test_single_property_scraping() {
     let properties = scraper
         .scrape_listing(query, 1)
         .await
-        .unwrap();
+        .unwrap()
+        .listings;
 
     // Verify that we got properties
     assert!(!properties.is_empty());
@@ -65,7 +69,8 @@ async fn test_multiple_properties_scraping() {
         let properties = scraper
             .scrape_listing(query, 1)
             .await
-            .unwrap();
+            .unwrap()
+            .listings;
         all_properties.extend(properties);
     }
 
@@ -103,7 +108,8 @@ async fn test_property_update_detection() {
     let initial_properties = scraper
         .scrape_listing(query, 1)
         .await
-        .unwrap();
+        .unwrap()
+        .listings;
 
     // Save initial properties
     for (mut property, _images) in initial_properties {
@@ -126,7 +132,8 @@ async fn test_property_update_detection() {
     let updated_properties = scraper
         .scrape_listing(query, 1)
         .await
-        .unwrap();
+        .unwrap()
+        .listings;
 
     // Save updated properties
     for (mut property, _images) in updated_properties {
@@ -158,7 +165,7 @@ async fn test_scraping() -> Result<(), Box<dyn std::error::Error>> {
         None,
     );
     
-    let properties = scraper.scrape_listing(query, 1).await?;
+    let properties = scraper.scrape_listing(query, 1).await?.listings;
     assert!(!properties.is_empty());
     Ok(())
 }
@@ -177,7 +184,7 @@ async fn test_scraping_with_filters() -> Result<(), Box<dyn std::error::Error>>
         None,
     );
     
-    let properties = scraper.scrape_listing(query, 1).await?;
+    let properties = scraper.scrape_listing(query, 1).await?.listings;
     assert!(!properties.is_empty());
     Ok(())
 }
@@ -196,10 +203,10 @@ async fn test_scraping_with_database() -> Result<(), Box<dyn std::error::Error>>
         None,
         None,
         None,
-        Some(Arc::clone(&db)),
+        Some(db.clone()),
     );
     
-    let properties = scraper.scrape_listing(query, 1).await?;
+    let properties = scraper.scrape_listing(query, 1).await?.listings;
     assert!(!properties.is_empty());
     Ok(())
 }
@@ -218,10 +225,10 @@ async fn test_scraping_with_database_and_filters() -> Result<(), Box<dyn std::er
         Some(200_000.0),
         Some(50.0),
         Some(100.0),
-        Some(Arc::clone(&db)),
+        Some(db.clone()),
     );
     
-    let properties = scraper.scrape_listing(query, 1).await?;
+    let properties = scraper.scrape_listing(query, 1).await?.listings;
     assert!(!properties.is_empty());
     Ok(())
 }